@@ -0,0 +1,268 @@
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashSet;
+
+/// The attributes holding a URL, by the element they are on.
+const URL_ATTRIBUTES: &[(&str, &str)] = &[
+    ("a", "href"),
+    ("area", "href"),
+    ("link", "href"),
+    ("img", "src"),
+    ("source", "src"),
+    ("script", "src"),
+    ("iframe", "src"),
+    ("frame", "src"),
+    ("embed", "src"),
+    ("video", "src"),
+    ("video", "poster"),
+    ("audio", "src"),
+    ("track", "src"),
+    ("object", "data"),
+    ("input", "src"),
+];
+
+/// The attributes holding a `srcset`, a list of image candidates.
+const SRCSET_ATTRIBUTES: &[(&str, &str)] = &[("img", "srcset"), ("source", "srcset")];
+
+/// `<link>` relations whose `href` is an origin to connect to early, not a resource.
+const HINT_RELS: &[&str] = &["preconnect", "dns-prefetch"];
+
+/// A URL referenced by a document, as written there.
+#[derive(Debug, PartialEq)]
+pub struct Reference {
+    pub url: String,
+
+    /// How a reader would know the reference: the text of a link, the `alt` of an image, or
+    /// else the element and attribute it is in, such as `<script src>`.
+    pub text: String,
+}
+
+impl Reference {
+    fn new(url: &str, text: String) -> Self {
+        Self {
+            url: url.trim().to_string(),
+            text,
+        }
+    }
+}
+
+/// Every URL `document` references: links, images, stylesheets, scripts, frames and media,
+/// each `srcset` candidate, and the `url()`s of its inline CSS.
+pub fn html_references(document: &Html) -> Vec<Reference> {
+    let mut references = Vec::new();
+    let all = Selector::parse("*").unwrap();
+    for element in document.select(&all) {
+        let name = element.value().name();
+        if name == "link" && is_hint(&element) {
+            continue;
+        }
+        for (_, attr) in URL_ATTRIBUTES.iter().filter(|(tag, _)| *tag == name) {
+            if let Some(url) = element.value().attr(attr) {
+                references.push(Reference::new(url, describe(&element, attr)));
+            }
+        }
+        for (_, attr) in SRCSET_ATTRIBUTES.iter().filter(|(tag, _)| *tag == name) {
+            if let Some(srcset) = element.value().attr(attr) {
+                for url in srcset_urls(srcset) {
+                    references.push(Reference::new(url, describe(&element, attr)));
+                }
+            }
+        }
+        if let Some(style) = element.value().attr("style") {
+            for url in css_urls(style) {
+                references.push(Reference::new(url, format!("<{name} style>")));
+            }
+        }
+        if name == "style" {
+            let css = element.text().collect::<String>();
+            for url in css_urls(&css) {
+                references.push(Reference::new(url, "<style>".into()));
+            }
+        }
+    }
+    references
+}
+
+fn is_hint(element: &ElementRef) -> bool {
+    let rel = element.value().attr("rel").unwrap_or_default();
+    rel.split_ascii_whitespace()
+        .any(|x| HINT_RELS.iter().any(|hint| x.eq_ignore_ascii_case(hint)))
+}
+
+fn describe(element: &ElementRef, attr: &str) -> String {
+    let name = element.value().name();
+    let text = match name {
+        "a" => element.text().collect::<String>(),
+        "area" | "img" | "input" => element.value().attr("alt").unwrap_or_default().into(),
+        _ => String::new(),
+    };
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        format!("<{name} {attr}>")
+    } else {
+        text
+    }
+}
+
+/// The `href` of the `<base>` of `document`, which its relative URLs are relative to.
+pub fn base_href(document: &Html) -> Option<&str> {
+    let selector = Selector::parse("base[href]").unwrap();
+    document
+        .select(&selector)
+        .next()
+        .and_then(|x| x.value().attr("href"))
+}
+
+/// The fragments that lead somewhere in `document`: the `id` of any element and the `name` of
+/// any `<a>`.
+pub fn anchors(document: &Html) -> HashSet<String> {
+    let ids = Selector::parse("[id]").unwrap();
+    let names = Selector::parse("a[name]").unwrap();
+    let ids = document.select(&ids).filter_map(|x| x.value().attr("id"));
+    let names = document
+        .select(&names)
+        .filter_map(|x| x.value().attr("name"));
+    ids.chain(names).map(str::to_string).collect()
+}
+
+/// The URLs of the candidates of a `srcset`, such as `small.png 1x, large.png 2x`.
+pub fn srcset_urls(srcset: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            return urls;
+        }
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let (url, after) = rest.split_at(end);
+        // A URL directly followed by a comma has no descriptors.
+        if let Some(url) = url.strip_suffix(',') {
+            urls.push(url.trim_end_matches(','));
+            rest = after;
+            continue;
+        }
+        urls.push(url);
+        // Skip the descriptors, up to the comma ending the candidate.
+        rest = match after.find(',') {
+            Some(comma) => &after[comma + 1..],
+            None => "",
+        };
+    }
+}
+
+/// The URLs of the `url()`s and `@import`s of a stylesheet.
+pub fn css_urls(css: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let lower = css.to_ascii_lowercase();
+    let mut at = 0;
+    while let Some(found) = lower[at..].find("url(") {
+        let start = at + found + "url(".len();
+        let Some(len) = css[start..].find(')') else {
+            break;
+        };
+        urls.push(unquote(&css[start..start + len]));
+        at = start + len;
+    }
+    // `@import "x.css"` names a URL without `url()`.
+    let mut at = 0;
+    while let Some(found) = lower[at..].find("@import") {
+        let start = at + found + "@import".len();
+        let rest = css[start..].trim_start();
+        if let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\'')) {
+            if let Some(len) = rest[1..].find(quote) {
+                urls.push(&rest[1..1 + len]);
+            }
+        }
+        at = start;
+    }
+    urls.retain(|x| !x.is_empty());
+    urls
+}
+
+fn unquote(s: &str) -> &str {
+    let s = s.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = s.strip_prefix(quote).and_then(|x| x.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_every_kind_of_reference() {
+        let document = Html::parse_document(
+            r#"<html><head>
+                <link rel="stylesheet" href="site.css">
+                <link rel="preconnect" href="https://fonts.example.com">
+                <script src="app.js"></script>
+                <style>body { background: url('bg.png') }</style>
+            </head><body>
+                <a href="/about">About
+                    us</a>
+                <img src="logo.png" alt="Logo" srcset="logo-2x.png 2x, logo-3x.png 3x">
+                <iframe src="embed.html"></iframe>
+                <div style="background-image: URL(tile.png)"></div>
+            </body></html>"#,
+        );
+        let references: Vec<_> = html_references(&document)
+            .into_iter()
+            .map(|x| (x.url, x.text))
+            .collect();
+        let expected = [
+            ("site.css", "<link href>"),
+            ("app.js", "<script src>"),
+            ("bg.png", "<style>"),
+            ("/about", "About us"),
+            ("logo.png", "Logo"),
+            ("logo-2x.png", "Logo"),
+            ("logo-3x.png", "Logo"),
+            ("embed.html", "<iframe src>"),
+            ("tile.png", "<div style>"),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(url, text)| (url.to_string(), text.to_string()))
+            .collect();
+        assert_eq!(references, expected);
+    }
+
+    #[test]
+    fn parses_srcsets() {
+        assert_eq!(srcset_urls("a.png"), ["a.png"]);
+        assert_eq!(srcset_urls("a.png 1x, b.png 2x"), ["a.png", "b.png"]);
+        assert_eq!(
+            srcset_urls("a.png, b.png 480w ,c.png"),
+            ["a.png", "b.png", "c.png"]
+        );
+        assert_eq!(srcset_urls(" a,b.png 100w"), ["a,b.png"]);
+        assert!(srcset_urls(" , ").is_empty());
+    }
+
+    #[test]
+    fn parses_css() {
+        let css = r#"
+            @import "reset.css";
+            @import url(theme.css);
+            h1 { background: url( "h1.png" ) }
+            p { background: url() }
+        "#;
+        assert_eq!(css_urls(css), ["theme.css", "h1.png", "reset.css"]);
+    }
+
+    #[test]
+    fn finds_anchors() {
+        let document = Html::parse_document(
+            r#"<h1 id="top">Title</h1><a name="old"></a><input name="field">"#,
+        );
+        let anchors = anchors(&document);
+        assert_eq!(anchors.len(), 2);
+        assert!(anchors.contains("top") && anchors.contains("old"));
+    }
+}
@@ -0,0 +1,326 @@
+use crate::robots::Robots;
+
+use reqwest::{Client, Url};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OnceCell, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// How hard each host is crawled.
+#[derive(Debug, Clone, clap::Args)]
+pub struct Politeness {
+    /// Sent as the `User-Agent` header, and looked up in robots.txt by the part before any `/`.
+    #[arg(long, default_value = concat!("link-checker/", env!("CARGO_PKG_VERSION")))]
+    pub user_agent: String,
+
+    /// Requests to one host at once.
+    #[arg(long, default_value = "2")]
+    pub per_host_concurrency: NonZeroUsize,
+
+    /// Requests to one host per second. A longer `Crawl-delay` in robots.txt wins.
+    #[arg(long, value_parser = parse_rate)]
+    pub per_host_rate: Option<f64>,
+
+    /// Fetch pages that robots.txt disallows, and ignore its `Crawl-delay`. Only for sites
+    /// you run.
+    #[arg(long)]
+    pub ignore_robots: bool,
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    // The rate is turned into the time between requests, which must fit a `Duration`.
+    match s.parse::<f64>() {
+        Ok(rate) if !rate.is_finite() || rate <= 0.0 => {
+            Err(format!("{s:?} is not a positive number"))
+        }
+        Ok(rate) if Duration::try_from_secs_f64(1.0 / rate).is_err() => {
+            Err(format!("{s:?} is too small a rate"))
+        }
+        Ok(rate) => Ok(rate),
+        _ => Err(format!("{s:?} is not a positive number")),
+    }
+}
+
+/// One host, by scheme, name and port.
+struct Host {
+    robots: OnceCell<Robots>,
+    requests: Arc<Semaphore>,
+
+    /// When the next request may start.
+    next: Mutex<Instant>,
+}
+
+/// The right to send one request to a host, until dropped.
+pub struct Turn {
+    _host: Option<OwnedSemaphorePermit>,
+    _slot: OwnedSemaphorePermit,
+}
+
+/// Every host seen in a crawl, each with its own robots.txt and limits, and the slots for
+/// requests to any of them.
+pub struct Hosts {
+    politeness: Politeness,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+    slots: Arc<Semaphore>,
+}
+
+impl Hosts {
+    /// Lets `concurrency` requests through at once over all the hosts.
+    pub fn new(politeness: Politeness, concurrency: NonZeroUsize) -> Self {
+        Self {
+            politeness,
+            hosts: Mutex::new(HashMap::new()),
+            slots: Arc::new(Semaphore::new(concurrency.get())),
+        }
+    }
+
+    /// Waits for a turn to request `url`, or returns `None` if robots.txt disallows it. The
+    /// first request to a host fetches its robots.txt.
+    pub async fn enter(&self, client: &Client, url: &Url) -> Option<Turn> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Some(Turn {
+                _host: None,
+                _slot: self.slot().await,
+            });
+        }
+        let host = self.host(url);
+        if self.politeness.ignore_robots {
+            return Some(self.turn(&host, None).await);
+        }
+
+        let robots = host
+            .robots
+            .get_or_init(|| async {
+                let _turn = self.turn(&host, None).await;
+                fetch_robots(client, url, &self.politeness.user_agent).await
+            })
+            .await;
+        let path = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+        if !robots.is_allowed(&path) {
+            return None;
+        }
+        Some(self.turn(&host, robots.crawl_delay).await)
+    }
+
+    fn host(&self, url: &Url) -> Arc<Host> {
+        let key = url.origin().ascii_serialization();
+        let mut hosts = self.hosts.lock().unwrap();
+        let host = hosts.entry(key).or_insert_with(|| {
+            Arc::new(Host {
+                robots: OnceCell::new(),
+                requests: Arc::new(Semaphore::new(self.politeness.per_host_concurrency.get())),
+                next: Mutex::new(Instant::now()),
+            })
+        });
+        host.clone()
+    }
+
+    async fn slot(&self) -> OwnedSemaphorePermit {
+        self.slots.clone().acquire_owned().await.unwrap()
+    }
+
+    /// Waits until `host` has a request free and its last request was long enough ago, and
+    /// then for a slot. Waiting on a busy host does not hold a slot other hosts could use.
+    async fn turn(&self, host: &Host, crawl_delay: Option<Duration>) -> Turn {
+        let permit = host.requests.clone().acquire_owned().await.unwrap();
+        let interval = self
+            .politeness
+            .per_host_rate
+            .map(|rate| Duration::from_secs_f64(1.0 / rate))
+            .max(crawl_delay)
+            .unwrap_or_default();
+        let start = {
+            let mut next = host.next.lock().unwrap();
+            let start = (*next).max(Instant::now());
+            *next = start + interval;
+            start
+        };
+        tokio::time::sleep_until(start).await;
+        Turn {
+            _host: Some(permit),
+            _slot: self.slot().await,
+        }
+    }
+}
+
+/// Fetches the robots.txt of the host of `url`. Without one, everything is allowed. If the
+/// host fails to serve it, nothing is, as RFC 9309 asks; but if the host cannot be reached at
+/// all, its pages are fetched anyway, so that they are reported broken rather than skipped.
+async fn fetch_robots(client: &Client, url: &Url, user_agent: &str) -> Robots {
+    let Ok(robots_url) = url.join("/robots.txt") else {
+        return Robots::allow_all();
+    };
+    let response = match client.get(robots_url).send().await {
+        Ok(response) => response,
+        Err(_) => return Robots::allow_all(),
+    };
+    let status = response.status();
+    if status.is_client_error() {
+        return Robots::allow_all();
+    }
+    if !status.is_success() {
+        return Robots::disallow_all();
+    }
+    match response.text().await {
+        Ok(text) => Robots::parse(&text, user_agent),
+        Err(_) => Robots::disallow_all(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Page, TestServer};
+
+    fn politeness(per_host_concurrency: usize, per_host_rate: Option<f64>) -> Politeness {
+        Politeness {
+            user_agent: "link-checker".into(),
+            per_host_concurrency: NonZeroUsize::new(per_host_concurrency).unwrap(),
+            per_host_rate,
+            ignore_robots: false,
+        }
+    }
+
+    fn hosts(politeness: Politeness, concurrency: usize) -> Arc<Hosts> {
+        Arc::new(Hosts::new(
+            politeness,
+            NonZeroUsize::new(concurrency).unwrap(),
+        ))
+    }
+
+    /// Requests `url` `n` times at once, each in its turn.
+    async fn fetch(hosts: &Arc<Hosts>, client: &Client, url: Url, n: usize) {
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..n {
+            let (hosts, client, url) = (hosts.clone(), client.clone(), url.clone());
+            tasks.spawn(async move {
+                let _turn = hosts.enter(&client, &url).await.unwrap();
+                client.get(url).send().await.unwrap();
+            });
+        }
+        while let Some(task) = tasks.join_next().await {
+            task.unwrap();
+        }
+    }
+
+    /// The time from the first request for `path` to the last. Connecting can hold up any
+    /// one request a little, so the tests leave some slack below the spacing they expect.
+    fn span(server: &TestServer, path: &str) -> Duration {
+        let times = server.times(path);
+        times[times.len() - 1] - times[0]
+    }
+
+    #[test]
+    fn rejects_rates_without_an_interval() {
+        assert_eq!(parse_rate("0.5"), Ok(0.5));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("inf").is_err());
+        assert!(parse_rate("NaN").is_err());
+        assert_eq!(
+            parse_rate("1e-30"),
+            Err("\"1e-30\" is too small a rate".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn limits_the_requests_to_a_host_at_once() {
+        let slow = Page::text("").with_delay(Duration::from_millis(100));
+        let server = TestServer::start([("/slow", slow)]).await;
+
+        let hosts = hosts(politeness(2, None), 16);
+        fetch(&hosts, &Client::new(), server.url("/slow"), 6).await;
+        assert_eq!(server.times("/slow").len(), 6);
+        assert_eq!(server.max_in_flight(), 2);
+    }
+
+    #[tokio::test]
+    async fn waiting_on_a_busy_host_does_not_hold_a_slot() {
+        let slow = Page::text("").with_delay(Duration::from_millis(500));
+        let busy = TestServer::start([("/slow", slow)]).await;
+        let idle = TestServer::start([("/", Page::text(""))]).await;
+        let client = Client::new();
+        let hosts = hosts(politeness(1, None), 2);
+
+        // One request to the busy host is answered slowly while the other waits for it.
+        let waiting = tokio::spawn({
+            let (hosts, client, url) = (hosts.clone(), client.clone(), busy.url("/slow"));
+            async move { fetch(&hosts, &client, url, 2).await }
+        });
+        while busy.times("/slow").is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let started = Instant::now();
+        fetch(&hosts, &client, idle.url("/"), 1).await;
+        assert!(started.elapsed() < Duration::from_millis(300));
+        waiting.await.unwrap();
+        assert_eq!(busy.max_in_flight(), 1);
+    }
+
+    #[tokio::test]
+    async fn spaces_requests_by_the_rate() {
+        let server = TestServer::start([("/", Page::text(""))]).await;
+
+        let hosts = hosts(politeness(4, Some(20.0)), 16);
+        fetch(&hosts, &Client::new(), server.url("/"), 4).await;
+        assert!(span(&server, "/") >= Duration::from_millis(120));
+    }
+
+    #[tokio::test]
+    async fn a_longer_crawl_delay_wins_unless_robots_are_ignored() {
+        let robots = Page::text("User-agent: *\nCrawl-delay: 0.2\n");
+        let server = TestServer::start([("/robots.txt", robots), ("/", Page::text(""))]).await;
+        let client = Client::new();
+
+        let polite = hosts(politeness(4, Some(100.0)), 16);
+        fetch(&polite, &client, server.url("/"), 3).await;
+        assert!(span(&server, "/") >= Duration::from_millis(350));
+
+        let ignoring = Politeness {
+            ignore_robots: true,
+            ..politeness(4, Some(100.0))
+        };
+        let started = Instant::now();
+        fetch(&hosts(ignoring, 16), &client, server.url("/"), 3).await;
+        assert!(started.elapsed() < Duration::from_millis(190));
+    }
+
+    #[tokio::test]
+    async fn robots_txt_status_decides_what_is_allowed() {
+        let client = Client::new();
+        let hosts = hosts(politeness(2, None), 16);
+        let allowed = |server: &TestServer, path: &str| {
+            let (hosts, client, url) = (hosts.clone(), client.clone(), server.url(path));
+            async move { hosts.enter(&client, &url).await.is_some() }
+        };
+
+        let rules = Page::text("User-agent: *\nDisallow: /private\n");
+        let server = TestServer::start([("/robots.txt", rules)]).await;
+        assert!(!allowed(&server, "/private").await);
+        assert!(allowed(&server, "/public").await);
+        assert_eq!(server.paths(), ["/robots.txt"]);
+
+        // None at all, or any other client error, allows everything.
+        let server = TestServer::start([]).await;
+        assert!(allowed(&server, "/private").await);
+        let server = TestServer::start([("/robots.txt", Page::status(403))]).await;
+        assert!(allowed(&server, "/private").await);
+
+        // A server error disallows everything.
+        let server = TestServer::start([("/robots.txt", Page::status(503))]).await;
+        assert!(!allowed(&server, "/").await);
+
+        // A host that cannot be reached is allowed, so its pages are reported broken.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let url = Url::parse(&format!("http://{addr}/")).unwrap();
+        assert!(hosts.enter(&client, &url).await.is_some());
+    }
+}
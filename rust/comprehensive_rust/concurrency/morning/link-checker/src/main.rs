@@ -1,153 +1,634 @@
-use reqwest::blocking::Client;
-use reqwest::Url;
-use scraper::{Html, Selector};
-use std::sync::{mpsc, Arc, Mutex};
-use std::thread;
+mod extract;
+mod hosts;
+mod report;
+mod robots;
+#[cfg(test)]
+mod test_server;
+
+use clap::Parser;
+use hosts::{Hosts, Politeness};
+use percent_encoding::percent_decode_str;
+use report::{Broken, Format, Referrer, Report};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, StatusCode, Url};
+use scraper::Html;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use tokio::task::JoinSet;
 
 #[derive(Error, Debug)]
 enum Error {
     #[error("request error: {0}")]
     ReqwestError(#[from] reqwest::Error),
     #[error("bad http response: {0}")]
-    BadResponse(String),
+    BadResponse(StatusCode),
+}
+
+impl Error {
+    fn status(&self) -> Option<u16> {
+        match self {
+            Error::ReqwestError(err) => err.status().map(|x| x.as_u16()),
+            Error::BadResponse(status) => Some(status.as_u16()),
+        }
+    }
+
+    /// A word for what went wrong, for the report.
+    fn kind(&self) -> &'static str {
+        match self {
+            Error::BadResponse(_) => "status",
+            Error::ReqwestError(err) if err.is_timeout() => "timeout",
+            Error::ReqwestError(err) if err.is_connect() => "connect",
+            Error::ReqwestError(err) if err.is_redirect() => "redirect",
+            Error::ReqwestError(err) if err.is_body() || err.is_decode() => "body",
+            Error::ReqwestError(_) => "request",
+        }
+    }
 }
 
+/// Checks the links of a site, starting from its seed pages.
+#[derive(Debug, Parser)]
+#[command(name = "link-checker")]
+struct Cli {
+    /// Pages to start from. Links are followed on the hosts of these pages.
+    #[arg(required = true)]
+    seeds: Vec<Url>,
+
+    /// Pages checked at once.
+    #[arg(long, default_value = "32")]
+    concurrency: NonZeroUsize,
+
+    /// Links to follow from a seed. Pages that far away are checked, but not read for links.
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Pages checked in all. Links found after that are not checked.
+    #[arg(long)]
+    max_pages: Option<usize>,
+
+    /// Seconds to wait for each page.
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+
+    /// Also read pages on other hosts for links, as if they were part of the site.
+    #[arg(long, conflicts_with = "skip_external")]
+    follow_external: bool,
+
+    /// Do not check links to other hosts at all.
+    #[arg(long)]
+    skip_external: bool,
+
+    /// Do not check that the element a `#fragment` points to exists.
+    #[arg(long)]
+    ignore_fragments: bool,
+
+    #[command(flatten)]
+    politeness: Politeness,
+
+    /// How to write the report of what was found.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Where to write the report, instead of standard output.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+/// The exit code when the crawl went fine but found broken links.
+const BROKEN_LINKS: u8 = 1;
+
+/// The exit code when the crawl itself failed. Bad arguments exit with it too.
+const CRAWLER_ERROR: u8 = 2;
+
 #[derive(Debug)]
 struct CrawlCommand {
     url: Url,
     extract_links: bool,
+
+    /// Whether to read the page for the fragments links to it may point to.
+    find_anchors: bool,
+
+    /// Links followed from a seed to get here.
+    depth: usize,
 }
 
-fn visit_page(client: &Client, command: &CrawlCommand) -> Result<Vec<Url>, Error> {
-    println!("Checking {:#}", command.url);
-    let response = client.get(command.url.clone()).send()?;
+/// A link found on a page.
+#[derive(Debug)]
+struct Link {
+    url: Url,
+    text: String,
+}
+
+/// What a page that was checked holds.
+#[derive(Debug, Default)]
+struct Page {
+    links: Vec<Link>,
+
+    /// The fragments that lead somewhere on the page, if it was read for them.
+    anchors: Option<HashSet<String>>,
+}
+
+/// Checks the page of `command`, returning what is on it, or `None` if robots.txt keeps it
+/// from being checked.
+async fn visit_page(
+    client: &Client,
+    hosts: &Hosts,
+    command: &CrawlCommand,
+) -> Result<Option<Page>, Error> {
+    let Some(_turn) = hosts.enter(client, &command.url).await else {
+        eprintln!("Skipping {:#}: disallowed by robots.txt", command.url);
+        return Ok(None);
+    };
+    eprintln!("Checking {:#}", command.url);
+    let response = client.get(command.url.clone()).send().await?;
     if !response.status().is_success() {
-        return Err(Error::BadResponse(response.status().to_string()));
+        return Err(Error::BadResponse(response.status()));
     }
 
-    let mut link_urls = Vec::new();
-    if !command.extract_links {
-        return Ok(link_urls);
+    // Without a content type, the page is taken for HTML.
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or("text/html")
+        .to_ascii_lowercase();
+    let is_html = ["text/html", "application/xhtml+xml"]
+        .iter()
+        .any(|x| content_type.starts_with(x));
+    let is_css = content_type.starts_with("text/css");
+
+    let mut page = Page::default();
+    let extract_links = command.extract_links && (is_html || is_css);
+    let find_anchors = command.find_anchors && is_html;
+    if !extract_links && !find_anchors {
+        return Ok(Some(page));
+    }
+
+    let mut base_url = response.url().to_owned();
+    let body_text = response.text().await?;
+    if is_css {
+        let references = extract::css_urls(&body_text)
+            .into_iter()
+            .map(|url| extract::Reference {
+                url: url.into(),
+                text: "url()".into(),
+            });
+        page.links = resolve(&base_url, references);
+        return Ok(Some(page));
     }
 
-    let base_url = response.url().to_owned();
-    let body_text = response.text()?;
     let document = Html::parse_document(&body_text);
+    if find_anchors {
+        page.anchors = Some(extract::anchors(&document));
+    }
+    if extract_links {
+        if let Some(base) = extract::base_href(&document).and_then(|x| base_url.join(x).ok()) {
+            base_url = base;
+        }
+        page.links = resolve(&base_url, extract::html_references(&document));
+    }
+    Ok(Some(page))
+}
 
-    let selector = Selector::parse("a").unwrap();
-    let href_values = document
-        .select(&selector)
-        .filter_map(|element| element.value().attr("href"));
-    for href in href_values {
-        match base_url.join(href) {
-            Ok(link_url) => {
-                link_urls.push(link_url);
-            }
+/// Turns the references of a page at `base_url` into links to check. Those that cannot be
+/// fetched, such as `mailto:` and `data:` URLs, are left out.
+fn resolve(base_url: &Url, references: impl IntoIterator<Item = extract::Reference>) -> Vec<Link> {
+    let mut links = Vec::new();
+    for reference in references {
+        match base_url.join(&reference.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => links.push(Link {
+                url,
+                text: reference.text,
+            }),
+            Ok(_) => {}
             Err(err) => {
-                println!("On {base_url:#}: ignored unparsable {href:?}: {err}");
+                eprintln!(
+                    "On {base_url:#}: ignored unparsable {:?}: {err}",
+                    reference.url
+                );
             }
         }
     }
-    Ok(link_urls)
+    links
 }
 
-struct SingleDomainCrawlerState {
-    domain: String,
-    visited: std::collections::HashSet<Url>,
+/// Which URLs have been seen and where they are linked from, and which of them are on the
+/// site being checked. URLs are kept without their fragments.
+struct CrawlerState<'a> {
+    cli: &'a Cli,
+    hosts: HashSet<String>,
+    visited: HashSet<Url>,
+
+    /// The pages linking to each URL, with the fragment each link has, if any.
+    referrers: HashMap<Url, Vec<(Option<String>, Referrer)>>,
+
+    /// The fragments that lead somewhere on each page read for them.
+    anchors: HashMap<Url, HashSet<String>>,
 }
 
-impl SingleDomainCrawlerState {
-    fn new(seed: &Url) -> Self {
-        let domain = seed.domain().unwrap().to_string();
-        let visited = std::collections::HashSet::new();
-        Self { domain, visited }
+impl<'a> CrawlerState<'a> {
+    fn new(cli: &'a Cli) -> Self {
+        let hosts = cli
+            .seeds
+            .iter()
+            .filter_map(|seed| seed.host_str())
+            .map(str::to_string)
+            .collect();
+        Self {
+            cli,
+            hosts,
+            visited: HashSet::new(),
+            referrers: HashMap::new(),
+            anchors: HashMap::new(),
+        }
+    }
+
+    fn is_internal(&self, url: &Url) -> bool {
+        url.host_str().is_some_and(|host| self.hosts.contains(host))
     }
 
-    fn should_extract_links(&self, url: &Url) -> bool {
-        match url.domain() {
-            None => false,
-            Some(domain) => self.domain == domain,
+    /// The command to check `url`, found by following `depth` links, if it is to be checked.
+    fn command(&self, url: Url, depth: usize) -> Option<CrawlCommand> {
+        let internal = self.is_internal(&url);
+        if !internal && self.cli.skip_external {
+            return None;
         }
+        let in_depth = self.cli.max_depth.is_none_or(|max| depth < max);
+        let extract_links = (internal || self.cli.follow_external) && in_depth;
+        Some(CrawlCommand {
+            url,
+            extract_links,
+            find_anchors: !self.cli.ignore_fragments,
+            depth,
+        })
+    }
+
+    fn is_full(&self) -> bool {
+        self.cli
+            .max_pages
+            .is_some_and(|max| self.visited.len() >= max)
     }
 
     fn mark_visited(&mut self, url: &Url) -> bool {
         self.visited.insert(url.clone())
     }
-}
 
-type CrawlResult = Result<Vec<Url>, (Url, Error)>;
-fn spawn_threads(
-    commands: mpsc::Receiver<CrawlCommand>,
-    results: mpsc::Sender<CrawlResult>,
-    thread_count: u32,
-) -> () {
-    let commands = Arc::new(Mutex::new(commands));
-    for _ in 0..thread_count {
-        let commands = commands.clone();
-        let results = results.clone();
-        thread::spawn(move || {
-            let client = Client::new();
-            loop {
-                let Ok(command) = commands.lock().unwrap().recv() else {
-                    break;
-                };
-                let result = visit_page(&client, &command).map_err(|err| (command.url, err));
-                let Ok(()) = results.send(result) else {
-                    break;
+    /// Records that `page` links to `url`, with `fragment` if the link has one.
+    fn add_referrer(&mut self, url: &Url, fragment: Option<String>, link: &Link, page: &Url) {
+        let referrer = Referrer {
+            page: page.to_string(),
+            text: link.text.clone(),
+        };
+        let referrers = self.referrers.entry(url.clone()).or_default();
+        let entry = (fragment, referrer);
+        if !referrers.contains(&entry) {
+            referrers.push(entry);
+        }
+    }
+
+    /// The pages linking to `url`, whatever fragment their links have.
+    fn referrers(&self, url: &Url) -> Vec<Referrer> {
+        let mut referrers: Vec<Referrer> = Vec::new();
+        for (_, referrer) in self.referrers.get(url).into_iter().flatten() {
+            if !referrers.contains(referrer) {
+                referrers.push(referrer.clone());
+            }
+        }
+        referrers
+    }
+
+    /// The links to fragments missing from the pages they point to, with the pages linking
+    /// to each. The empty fragment and `#top` always lead to the top of a page.
+    fn missing_fragments(&self) -> Vec<Broken> {
+        let mut missing: Vec<Broken> = Vec::new();
+        for (url, anchors) in &self.anchors {
+            for (fragment, referrer) in self.referrers.get(url).into_iter().flatten() {
+                let Some(fragment) = fragment else {
+                    continue;
                 };
+                let id = percent_decode_str(fragment).decode_utf8_lossy();
+                if id.is_empty() || id.eq_ignore_ascii_case("top") || anchors.contains(&*id) {
+                    continue;
+                }
+                let mut target = url.clone();
+                target.set_fragment(Some(fragment));
+                let target = target.to_string();
+                match missing.iter_mut().find(|x| x.url == target) {
+                    Some(broken) => broken.referrers.push(referrer.clone()),
+                    None => missing.push(Broken {
+                        url: target,
+                        status: None,
+                        kind: "fragment",
+                        error: format!("no element with id or name {id:?}"),
+                        referrers: vec![referrer.clone()],
+                    }),
+                }
             }
-        });
+        }
+        missing.sort_by(|a, b| a.url.cmp(&b.url));
+        missing
     }
 }
 
-fn event_loop(
-    seed: &Url,
-    commands: mpsc::Sender<CrawlCommand>,
-    results: mpsc::Receiver<CrawlResult>,
-) -> Vec<Url> {
-    let mut state = SingleDomainCrawlerState::new(&seed);
-    commands
-        .send(CrawlCommand {
-            url: seed.clone(),
-            extract_links: true,
-        })
-        .unwrap();
-    let mut pending = 1;
+/// A page visited, with what `visit_page` found there.
+type CrawlResult = (CrawlCommand, Result<Option<Page>, Error>);
+
+/// Visits the page of `command` on a new task. Clones of `client` share its connection pool.
+fn spawn_visit(
+    tasks: &mut JoinSet<CrawlResult>,
+    client: &Client,
+    hosts: &Arc<Hosts>,
+    command: CrawlCommand,
+) {
+    let client = client.clone();
+    let hosts = hosts.clone();
+    tasks.spawn(async move {
+        let result = visit_page(&client, &hosts, &command).await;
+        (command, result)
+    });
+}
+
+/// Crawls from the seeds of `cli`, visiting at most `cli.concurrency` pages at once. Each page
+/// gets a task straight away, which waits for its host to allow a request, and then for one
+/// of the `cli.concurrency` slots, so a slow host does not hold up the others.
+async fn event_loop(cli: &Cli, client: Client) -> anyhow::Result<Report> {
+    let mut state = CrawlerState::new(cli);
+    let hosts = Arc::new(Hosts::new(cli.politeness.clone(), cli.concurrency));
+    let mut tasks = JoinSet::new();
+    for seed in &cli.seeds {
+        let mut seed = seed.clone();
+        seed.set_fragment(None);
+        if state.mark_visited(&seed) {
+            if let Some(command) = state.command(seed, 0) {
+                spawn_visit(&mut tasks, &client, &hosts, command);
+            }
+        }
+    }
+    let mut report = Report::default();
     let mut bad_urls = Vec::new();
-    while pending > 0 {
-        let result = results.recv().unwrap();
-        pending -= 1;
+    let mut skipped = 0;
+    loop {
+        let Some(result) = tasks.join_next().await else {
+            break;
+        };
+        let (command, result) = result?;
         match result {
-            Ok(links) => {
-                for url in links {
-                    if state.mark_visited(&url) {
-                        let extract_links = state.should_extract_links(&url);
-                        commands.send(CrawlCommand { url, extract_links }).unwrap();
-                        pending += 1
+            Ok(Some(found)) => {
+                report.ok.push(command.url.to_string());
+                if let Some(anchors) = found.anchors {
+                    state.anchors.insert(command.url.clone(), anchors);
+                }
+                for link in found.links {
+                    // The fragment is for the browser; the page is fetched without it.
+                    let mut url = link.url.clone();
+                    let fragment = url.fragment().map(str::to_string);
+                    url.set_fragment(None);
+                    state.add_referrer(&url, fragment, &link, &command.url);
+                    if state.visited.contains(&url) {
+                        continue;
+                    }
+                    if state.is_full() {
+                        skipped += 1;
+                        continue;
+                    }
+                    if let Some(next) = state.command(url.clone(), command.depth + 1) {
+                        state.mark_visited(&url);
+                        spawn_visit(&mut tasks, &client, &hosts, next);
                     }
                 }
             }
-            Err((url, err)) => {
-                bad_urls.push(url);
-                println!("Got crawling error: {:#}", err);
-                continue;
+            Ok(None) => report.skipped.push(command.url.to_string()),
+            Err(err) => {
+                eprintln!("Got crawling error: {:#}", err);
+                bad_urls.push((command.url, err));
             }
         }
     }
-    bad_urls
+    // Pages found later may link to a broken URL too, so its referrers are only known now.
+    report.broken = bad_urls
+        .into_iter()
+        .map(|(url, err)| Broken {
+            status: err.status(),
+            kind: err.kind(),
+            error: err.to_string(),
+            referrers: state.referrers(&url),
+            url: url.to_string(),
+        })
+        .collect();
+    report.broken.extend(state.missing_fragments());
+    if skipped > 0 {
+        eprintln!(
+            "Stopped at {} pages, {skipped} links left unchecked",
+            state.visited.len()
+        );
+    }
+    Ok(report)
 }
 
-fn check_links(seed: &Url, thread_count: u32) -> Vec<Url> {
-    let (results_sender, results_receiver) = mpsc::channel::<CrawlResult>();
-    let (commands_sender, commands_receiver) = mpsc::channel::<CrawlCommand>();
-    spawn_threads(commands_receiver, results_sender, thread_count);
-    event_loop(seed, commands_sender, results_receiver)
+async fn check_links(cli: &Cli) -> anyhow::Result<Report> {
+    let client = Client::builder()
+        .user_agent(&cli.politeness.user_agent)
+        .timeout(Duration::from_secs(cli.timeout))
+        .build()?;
+    event_loop(cli, client).await
 }
 
-fn main() -> anyhow::Result<()> {
-    let seed = reqwest::Url::parse("https://www.google.org")?;
-    let bad_urls = check_links(&seed, 32);
-    println!("Bad URLs: {:#?}", bad_urls);
+/// Writes `report` where `cli` asks for it.
+fn write_report(cli: &Cli, report: &Report) -> anyhow::Result<()> {
+    match &cli.output {
+        Some(path) => {
+            let mut file = io::BufWriter::new(File::create(path)?);
+            report.write(cli.format, &mut file)?;
+            file.flush()?;
+        }
+        None => report.write(cli.format, &mut io::stdout().lock())?,
+    }
     Ok(())
 }
+
+/// Checks the links of `cli` and writes the report, returning the exit code.
+async fn run(cli: &Cli) -> ExitCode {
+    let report = match check_links(cli).await {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("link-checker: {err:#}");
+            return ExitCode::from(CRAWLER_ERROR);
+        }
+    };
+    if let Err(err) = write_report(cli, &report) {
+        eprintln!("link-checker: writing the report: {err:#}");
+        return ExitCode::from(CRAWLER_ERROR);
+    }
+    if report.broken.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(BROKEN_LINKS)
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    run(&Cli::parse()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_server::{Page, TestServer};
+
+    fn cli(seed: &Url, args: &[&str]) -> Cli {
+        Cli::try_parse_from(["link-checker", seed.as_str()].iter().chain(args)).unwrap()
+    }
+
+    fn html(links: &[&str]) -> Page {
+        let links: String = links
+            .iter()
+            .map(|x| format!("<a href=\"{x}\">{x}</a>"))
+            .collect();
+        Page::html(&format!("<html><body>{links}</body></html>"))
+    }
+
+    /// A site where `/a/deep` is two links from `/`, and `/missing` is broken.
+    async fn site() -> TestServer {
+        TestServer::start([
+            ("/", html(&["/a", "/b", "/missing"])),
+            ("/a", html(&["/a/deep", "/"])),
+            ("/a/deep", html(&["/a/deeper"])),
+            ("/a/deeper", html(&[])),
+            ("/b", html(&["/a"])),
+        ])
+        .await
+    }
+
+    /// The pages of `server` that were requested, leaving out robots.txt, sorted.
+    fn requested(server: &TestServer) -> Vec<String> {
+        let mut paths: Vec<_> = server
+            .paths()
+            .into_iter()
+            .filter(|x| x != "/robots.txt")
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn checks_every_page_linked_from_the_seed_once() {
+        let server = site().await;
+        let report = check_links(&cli(&server.url("/"), &[])).await.unwrap();
+
+        let paths = ["/", "/a", "/a/deep", "/a/deeper", "/b", "/missing"];
+        assert_eq!(requested(&server), paths);
+        assert_eq!(report.ok.len(), 5);
+        assert_eq!(report.broken.len(), 1);
+        assert_eq!(report.broken[0].url, server.url("/missing").as_str());
+        assert_eq!(report.broken[0].status, Some(404));
+    }
+
+    #[tokio::test]
+    async fn stops_at_max_pages() {
+        let server = site().await;
+        let report = check_links(&cli(&server.url("/"), &["--max-pages", "3"]))
+            .await
+            .unwrap();
+
+        assert_eq!(requested(&server).len(), 3);
+        assert_eq!(report.ok.len() + report.broken.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn checks_pages_at_max_depth_without_reading_them() {
+        let server = site().await;
+        check_links(&cli(&server.url("/"), &["--max-depth", "1"]))
+            .await
+            .unwrap();
+        assert_eq!(requested(&server), ["/", "/a", "/b", "/missing"]);
+
+        let server = site().await;
+        check_links(&cli(&server.url("/"), &["--max-depth", "2"]))
+            .await
+            .unwrap();
+        assert!(requested(&server).contains(&"/a/deep".to_string()));
+        assert!(!requested(&server).contains(&"/a/deeper".to_string()));
+    }
+
+    #[tokio::test]
+    async fn follows_links_on_other_hosts_only_when_asked() {
+        // The same server under another name is another host.
+        let other = TestServer::start([("/x", html(&["/y"])), ("/y", html(&[]))]).await;
+        let mut external = other.url("/x");
+        external.set_host(Some("localhost")).unwrap();
+        let server = TestServer::start([("/", html(&[external.as_str()]))]).await;
+        let seed = server.url("/");
+
+        check_links(&cli(&seed, &[])).await.unwrap();
+        assert_eq!(requested(&other), ["/x"]);
+
+        check_links(&cli(&seed, &["--follow-external"]))
+            .await
+            .unwrap();
+        assert_eq!(requested(&other), ["/x", "/x", "/y"]);
+
+        check_links(&cli(&seed, &["--skip-external"]))
+            .await
+            .unwrap();
+        assert_eq!(requested(&other), ["/x", "/x", "/y"]);
+    }
+
+    #[tokio::test]
+    async fn checks_at_most_concurrency_pages_at_once_over_all_hosts() {
+        let slow = |links: &[&str]| html(links).with_delay(Duration::from_millis(100));
+        let pages = ["/1", "/2", "/3", "/4", "/5", "/6"];
+        let server = TestServer::start(
+            [("/", slow(&pages))]
+                .into_iter()
+                .chain(pages.map(|x| (x, slow(&[])))),
+        )
+        .await;
+        // The same server under two names is two hosts, each allowing 8 requests at once.
+        let mut other = server.url("/");
+        other.set_host(Some("localhost")).unwrap();
+
+        let args = [
+            "--concurrency",
+            "3",
+            "--per-host-concurrency",
+            "8",
+            other.as_str(),
+        ];
+        let report = check_links(&cli(&server.url("/"), &args)).await.unwrap();
+        assert_eq!(report.ok.len(), 14);
+        assert_eq!(server.max_in_flight(), 3);
+    }
+
+    #[tokio::test]
+    async fn exit_code_tells_broken_links_from_crawler_errors() {
+        let dir = std::env::temp_dir().join(format!("link-checker-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let report = dir.join("report.txt");
+        let report = report.to_str().unwrap();
+
+        let server = site().await;
+        let code = run(&cli(&server.url("/b"), &["--max-depth", "0", "-o", report])).await;
+        assert_eq!(code, ExitCode::SUCCESS);
+
+        let code = run(&cli(&server.url("/"), &["-o", report])).await;
+        assert_eq!(code, ExitCode::from(BROKEN_LINKS));
+        let written = std::fs::read_to_string(report).unwrap();
+        assert!(written.contains("/missing"), "{}", written);
+
+        let unwritable = dir.join("no/such/dir/report.txt");
+        let code = run(&cli(
+            &server.url("/b"),
+            &["-o", unwritable.to_str().unwrap()],
+        ))
+        .await;
+        assert_eq!(code, ExitCode::from(CRAWLER_ERROR));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
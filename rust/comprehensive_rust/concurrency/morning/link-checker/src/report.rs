@@ -0,0 +1,262 @@
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// How the report is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// A list of broken URLs, for people.
+    Text,
+    Json,
+    Markdown,
+    /// JUnit XML, with a test case for each URL checked.
+    Junit,
+}
+
+/// A page linking to a URL.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Referrer {
+    pub page: String,
+
+    /// The text of the link, with its whitespace collapsed.
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Broken {
+    pub url: String,
+
+    /// The status the URL was answered with, if it was answered at all.
+    pub status: Option<u16>,
+
+    /// What went wrong: `status`, `timeout`, `connect`, `redirect`, `body` or `request`.
+    pub kind: &'static str,
+    pub error: String,
+    pub referrers: Vec<Referrer>,
+}
+
+/// What a crawl found.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The URLs checked and found fine.
+    pub ok: Vec<String>,
+
+    /// The URLs robots.txt kept the crawl from checking.
+    pub skipped: Vec<String>,
+    pub broken: Vec<Broken>,
+}
+
+/// The JSON report, which leaves out the URLs found fine.
+#[derive(Serialize)]
+struct Summary<'a> {
+    checked: usize,
+    skipped: &'a [String],
+    broken: &'a [Broken],
+}
+
+impl Report {
+    pub fn checked(&self) -> usize {
+        self.ok.len() + self.broken.len()
+    }
+
+    pub fn write(&self, format: Format, out: &mut dyn Write) -> io::Result<()> {
+        match format {
+            Format::Text => self.write_text(out),
+            Format::Json => {
+                let summary = Summary {
+                    checked: self.checked(),
+                    skipped: &self.skipped,
+                    broken: &self.broken,
+                };
+                serde_json::to_writer_pretty(&mut *out, &summary)?;
+                writeln!(out)
+            }
+            Format::Markdown => self.write_markdown(out),
+            Format::Junit => self.write_junit(out),
+        }
+    }
+
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "Checked {} URLs, {} broken, {} skipped",
+            self.checked(),
+            self.broken.len(),
+            self.skipped.len()
+        )?;
+        for broken in &self.broken {
+            writeln!(out, "{}: {}", broken.url, broken.error)?;
+            for referrer in &broken.referrers {
+                writeln!(out, "  linked from {} ({:?})", referrer.page, referrer.text)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_markdown(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "# Broken links\n")?;
+        writeln!(
+            out,
+            "Checked {} URLs: {} broken, {} skipped by robots.txt.",
+            self.checked(),
+            self.broken.len(),
+            self.skipped.len()
+        )?;
+        if self.broken.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "\n| URL | Error | Linked from |")?;
+        writeln!(out, "| --- | --- | --- |")?;
+        for broken in &self.broken {
+            let referrers: Vec<_> = broken
+                .referrers
+                .iter()
+                .map(|x| format!("[{}]({})", markdown_text(&x.text), x.page))
+                .collect();
+            writeln!(
+                out,
+                "| {} | {} | {} |",
+                broken.url,
+                markdown_text(&broken.error),
+                referrers.join("<br>")
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_junit(&self, out: &mut dyn Write) -> io::Result<()> {
+        let tests = self.checked() + self.skipped.len();
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<testsuites name="link-checker" tests="{tests}" failures="{}" skipped="{}">"#,
+            self.broken.len(),
+            self.skipped.len()
+        )?;
+        writeln!(
+            out,
+            r#"  <testsuite name="links" tests="{tests}" failures="{}" skipped="{}">"#,
+            self.broken.len(),
+            self.skipped.len()
+        )?;
+        for broken in &self.broken {
+            writeln!(
+                out,
+                r#"    <testcase classname="links" name="{}">"#,
+                xml(&broken.url)
+            )?;
+            writeln!(
+                out,
+                r#"      <failure type="{}" message="{}">"#,
+                broken.kind,
+                xml(&broken.error)
+            )?;
+            for referrer in &broken.referrers {
+                writeln!(
+                    out,
+                    "Linked from {} ({})",
+                    xml(&referrer.page),
+                    xml(&referrer.text)
+                )?;
+            }
+            writeln!(out, "      </failure>")?;
+            writeln!(out, "    </testcase>")?;
+        }
+        for url in &self.skipped {
+            writeln!(
+                out,
+                r#"    <testcase classname="links" name="{}"><skipped message="disallowed by robots.txt"/></testcase>"#,
+                xml(url)
+            )?;
+        }
+        for url in &self.ok {
+            writeln!(
+                out,
+                r#"    <testcase classname="links" name="{}"/>"#,
+                xml(url)
+            )?;
+        }
+        writeln!(out, "  </testsuite>")?;
+        writeln!(out, "</testsuites>")
+    }
+}
+
+/// Text that cannot break out of a Markdown table cell or link.
+fn markdown_text(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '|' | '[' | ']' | '\\' | '*' | '_' | '`' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Text safe in XML content and attribute values.
+fn xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report {
+            ok: vec!["https://example.com/".into()],
+            skipped: vec!["https://example.com/private".into()],
+            broken: vec![Broken {
+                url: "https://example.com/gone".into(),
+                status: Some(404),
+                kind: "status",
+                error: "404 Not Found".into(),
+                referrers: vec![Referrer {
+                    page: "https://example.com/".into(),
+                    text: "Old <stuff> | more".into(),
+                }],
+            }],
+        }
+    }
+
+    fn write(format: Format) -> String {
+        let mut out = vec![];
+        report().write(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_json() {
+        let json: serde_json::Value = serde_json::from_str(&write(Format::Json)).unwrap();
+        assert_eq!(json["checked"], 2);
+        assert_eq!(json["broken"][0]["status"], 404);
+        assert_eq!(
+            json["broken"][0]["referrers"][0]["page"],
+            "https://example.com/"
+        );
+        assert_eq!(json["skipped"][0], "https://example.com/private");
+    }
+
+    #[test]
+    fn writes_markdown() {
+        let markdown = write(Format::Markdown);
+        assert!(markdown.contains(
+            "| https://example.com/gone | 404 Not Found | [Old \\<stuff> \\| more](https://example.com/) |"
+        ), "{}", markdown);
+    }
+
+    #[test]
+    fn writes_junit() {
+        let xml = write(Format::Junit);
+        assert!(
+            xml.contains(r#"tests="3" failures="1" skipped="1""#),
+            "{}",
+            xml
+        );
+        assert!(xml.contains(r#"<failure type="status" message="404 Not Found">"#));
+        assert!(xml.contains("Old &lt;stuff&gt; | more"), "{}", xml);
+        assert!(xml.contains(r#"<testcase classname="links" name="https://example.com/"/>"#));
+    }
+}
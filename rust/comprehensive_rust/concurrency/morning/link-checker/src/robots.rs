@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+/// An `Allow` or `Disallow` line of a robots.txt group.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+/// The rules of a robots.txt file that apply to one user agent, as RFC 9309 reads them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Robots {
+    rules: Vec<Rule>,
+    pub crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Rules that allow everything, for a host without a robots.txt.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Rules that allow nothing, for a host whose robots.txt could not be read.
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![Rule {
+                allow: false,
+                pattern: "/".into(),
+            }],
+            crawl_delay: None,
+        }
+    }
+
+    /// Reads the groups of `text` for `user_agent`. Groups naming its product token, the part
+    /// before any `/`, are used if there are any. Otherwise the groups for `*` are.
+    pub fn parse(text: &str, user_agent: &str) -> Self {
+        let token = product_token(user_agent);
+
+        let mut named = Self::default();
+        let mut any = Self::default();
+        let mut found_named = false;
+
+        // The agents of the group being read, and whether a rule has ended its list of agents.
+        let mut agents: Vec<String> = vec![];
+        let mut in_rules = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    let agent = product_token(value);
+                    found_named |= agent == token;
+                    agents.push(agent);
+                }
+                key @ ("allow" | "disallow" | "crawl-delay") => {
+                    in_rules = true;
+                    if agents.contains(&token) {
+                        named.add(key, value);
+                    }
+                    if agents.iter().any(|x| x == "*") {
+                        any.add(key, value);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if found_named {
+            named
+        } else {
+            any
+        }
+    }
+
+    fn add(&mut self, key: &str, value: &str) {
+        match key {
+            // An empty `Disallow` allows everything, which needs no rule.
+            "allow" | "disallow" if !value.is_empty() => self.rules.push(Rule {
+                allow: key == "allow",
+                pattern: value.into(),
+            }),
+            "crawl-delay" => {
+                // A delay that is negative or too long for a `Duration` is ignored.
+                if let Some(delay) = value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                {
+                    self.crawl_delay = Some(delay);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `path`, with its query if any, may be fetched. The longest matching rule
+    /// decides, with `Allow` winning a tie. The robots.txt file itself is always allowed.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        self.rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+}
+
+/// The name a user agent goes by in robots.txt: `Link-Checker/1.0` is `link-checker`.
+fn product_token(user_agent: &str) -> String {
+    let token = user_agent.split(['/', ' ']).next().unwrap_or_default();
+    token.trim().to_ascii_lowercase()
+}
+
+/// Whether `path` starts with `pattern`, in which `*` matches any run of characters and a
+/// trailing `$` matches the end of the path.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<_> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return !anchored || rest.is_empty();
+    };
+    // Taking the first place each part fits leaves the most room for those after it.
+    for part in middle {
+        let Some(at) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[at + part.len()..];
+    }
+    if anchored {
+        rest.ends_with(last)
+    } else {
+        rest.contains(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "
+        # Everyone but us.
+        User-agent: *
+        Disallow: /private/
+        Crawl-delay: 1.5
+
+        User-agent: other-bot
+        User-agent: Link-Checker/2.0
+        Disallow: /drafts/
+        Allow: /drafts/published
+        Disallow: /*.pdf$
+        Crawl-delay: 2
+    ";
+
+    #[test]
+    fn picks_the_group_for_the_agent() {
+        let robots = Robots::parse(ROBOTS, "link-checker/0.1.0");
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(2)));
+        assert!(robots.is_allowed("/private/page"));
+        assert!(!robots.is_allowed("/drafts/x"));
+
+        let robots = Robots::parse(ROBOTS, "someone-else");
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(1500)));
+        assert!(!robots.is_allowed("/private/page"));
+        assert!(robots.is_allowed("/drafts/x"));
+    }
+
+    #[test]
+    fn ignores_crawl_delays_out_of_range() {
+        for delay in ["1e30", "-1", "NaN", "inf", "soon"] {
+            let robots = Robots::parse(&format!("User-agent: *\nCrawl-delay: {delay}\n"), "x");
+            assert_eq!(robots.crawl_delay, None, "{delay}");
+        }
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let robots = Robots::parse(ROBOTS, "link-checker");
+        assert!(robots.is_allowed("/drafts/published/today"));
+        assert!(!robots.is_allowed("/docs/manual.pdf"));
+        assert!(robots.is_allowed("/docs/manual.pdf?download=1"));
+        assert!(robots.is_allowed("/robots.txt"));
+    }
+
+    #[test]
+    fn empty_disallow_allows_everything() {
+        let robots = Robots::parse("User-agent: *\nDisallow:\n", "link-checker");
+        assert!(robots.is_allowed("/anything"));
+        assert!(Robots::parse("", "link-checker").is_allowed("/anything"));
+        assert!(!Robots::disallow_all().is_allowed("/anything"));
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(pattern_matches("/a/*/c", "/a/b/c/d"));
+        assert!(pattern_matches("/a*b*c$", "/axbyc"));
+        assert!(!pattern_matches("/a*b*c$", "/axbycd"));
+        assert!(pattern_matches("*.html$", "/index.html"));
+        assert!(pattern_matches("/$", "/"));
+        assert!(!pattern_matches("/$", "/a"));
+        assert!(!pattern_matches("/a", "/b/a"));
+    }
+}
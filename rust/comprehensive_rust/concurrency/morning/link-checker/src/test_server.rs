@@ -0,0 +1,143 @@
+use reqwest::Url;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Instant;
+
+/// What the server answers a path with.
+#[derive(Debug, Clone)]
+pub struct Page {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+    delay: Duration,
+}
+
+impl Page {
+    pub fn html(body: &str) -> Self {
+        Self {
+            status: 200,
+            content_type: "text/html",
+            body: body.into(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn text(body: &str) -> Self {
+        Self {
+            content_type: "text/plain",
+            ..Self::html(body)
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            ..Self::text("")
+        }
+    }
+
+    /// Answers only after `delay`.
+    pub fn with_delay(self, delay: Duration) -> Self {
+        Self { delay, ..self }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Log {
+    /// Each request by path, in the order they came in.
+    requests: Vec<(String, Instant)>,
+    in_flight: usize,
+    max_in_flight: usize,
+}
+
+/// A local HTTP server for the tests, answering each path with a canned response.
+pub struct TestServer {
+    addr: SocketAddr,
+    log: Arc<Mutex<Log>>,
+}
+
+impl TestServer {
+    /// Serves `pages` by path, and 404 for any other path, until the test ends.
+    pub async fn start<'a>(pages: impl IntoIterator<Item = (&'a str, Page)>) -> Self {
+        let pages: Arc<HashMap<String, Page>> = Arc::new(
+            pages
+                .into_iter()
+                .map(|(path, page)| (path.to_string(), page))
+                .collect(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let log = Arc::new(Mutex::new(Log::default()));
+        tokio::spawn({
+            let log = log.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, pages.clone(), log.clone()));
+                }
+            }
+        });
+        Self { addr, log }
+    }
+
+    pub fn url(&self, path: &str) -> Url {
+        Url::parse(&format!("http://{}{path}", self.addr)).unwrap()
+    }
+
+    /// The paths requested so far, in order.
+    pub fn paths(&self) -> Vec<String> {
+        let log = self.log.lock().unwrap();
+        log.requests.iter().map(|(path, _)| path.clone()).collect()
+    }
+
+    /// When each request for `path` came in.
+    pub fn times(&self, path: &str) -> Vec<Instant> {
+        let log = self.log.lock().unwrap();
+        log.requests
+            .iter()
+            .filter(|(x, _)| x == path)
+            .map(|(_, time)| *time)
+            .collect()
+    }
+
+    /// The most requests the server was answering at once.
+    pub fn max_in_flight(&self) -> usize {
+        self.log.lock().unwrap().max_in_flight
+    }
+}
+
+/// Answers one request on `stream`, and closes it.
+async fn serve(mut stream: TcpStream, pages: Arc<HashMap<String, Page>>, log: Arc<Mutex<Log>>) {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.ends_with(b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => head.extend_from_slice(&buf[..n]),
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    let path = head.split(' ').nth(1).unwrap_or("/").to_string();
+
+    {
+        let mut log = log.lock().unwrap();
+        log.requests.push((path.clone(), Instant::now()));
+        log.in_flight += 1;
+        log.max_in_flight = log.max_in_flight.max(log.in_flight);
+    }
+    let page = pages.get(&path).cloned().unwrap_or(Page::status(404));
+    tokio::time::sleep(page.delay).await;
+    let response = format!(
+        "HTTP/1.1 {} Canned\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        page.status,
+        page.content_type,
+        page.body.len(),
+        page.body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+    log.lock().unwrap().in_flight -= 1;
+}
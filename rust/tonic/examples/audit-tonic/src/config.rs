@@ -0,0 +1,41 @@
+use crate::layer::AuditLayer;
+
+use prost_reflect::DescriptorPool;
+use std::error::Error;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct AuditConfig {
+    /// Append a JSONL record of every call to this file.
+    #[arg(long = "audit-log")]
+    pub path: Option<PathBuf>,
+
+    /// Field to replace with "[REDACTED]" in the log, by full name such as
+    /// `helloworld.HelloRequest.name`. Can be repeated.
+    #[arg(long = "audit-redact")]
+    pub redact: Vec<String>,
+
+    /// Rotate the log once it would grow past this many bytes.
+    #[arg(long = "audit-max-bytes", default_value_t = 10 << 20)]
+    pub max_bytes: u64,
+
+    /// Rotated logs to keep, as FILE.1 (the newest) to FILE.N.
+    #[arg(long = "audit-keep", default_value_t = 5)]
+    pub keep: usize,
+
+    /// Messages of each call to include in its record. The rest are only counted.
+    #[arg(long = "audit-sample", default_value_t = 3)]
+    pub sample: usize,
+}
+
+impl AuditConfig {
+    /// The layer to audit a server with, `None` when no log is configured. `descriptors` is
+    /// the encoded file descriptor set of the services, used to decode their messages.
+    pub fn layer(&self, descriptors: &[u8]) -> Result<Option<AuditLayer>, Box<dyn Error>> {
+        if self.path.is_none() {
+            return Ok(None);
+        }
+        let pool = DescriptorPool::decode(descriptors)?;
+        Ok(Some(AuditLayer::new(self, pool)?))
+    }
+}
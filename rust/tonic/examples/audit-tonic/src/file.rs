@@ -0,0 +1,89 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// A log file that is moved aside to `path.1` once it would grow past `max_bytes`, shifting
+/// older logs up to `path.keep` and dropping the oldest.
+#[derive(Debug)]
+pub(crate) struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    /// Opens `path` for appending.
+    pub(crate) fn open(path: &Path, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            size: file.metadata()?.len(),
+            file,
+            max_bytes,
+            keep,
+        })
+    }
+
+    /// Appends `line` whole, rotating first if it doesn't fit.
+    pub(crate) fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep > 0 {
+            for i in (1..self.keep).rev() {
+                match fs::rename(self.rotated(i), self.rotated(i + 1)) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated(&self, i: usize) -> PathBuf {
+        let mut path = OsString::from(&self.path);
+        path.push(format!(".{}", i));
+        path.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_and_keeps_the_newest_logs() {
+        let dir = std::env::temp_dir().join(format!("audit-rotate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.jsonl");
+
+        let mut file = RotatingFile::open(&path, 6, 2).unwrap();
+        for line in ["one\n", "two\n", "three\n", "four\n", "five\n"] {
+            file.write_line(line.as_bytes()).unwrap();
+        }
+
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("audit.jsonl"), "five\n");
+        assert_eq!(read("audit.jsonl.1"), "four\n");
+        assert_eq!(read("audit.jsonl.2"), "three\n");
+        assert!(!dir.join("audit.jsonl.3").exists());
+
+        // Reopening appends to the current log.
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        file.write_line(b"six\n").unwrap();
+        assert_eq!(read("audit.jsonl"), "five\nsix\n");
+    }
+}
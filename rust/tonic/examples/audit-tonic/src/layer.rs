@@ -0,0 +1,261 @@
+use crate::config::AuditConfig;
+use crate::file::RotatingFile;
+use crate::summary::Summarizer;
+
+use bytes::Bytes;
+use futures_core::future::BoxFuture;
+use http::HeaderMap;
+use prost_reflect::{DescriptorPool, MessageDescriptor};
+use serde::Serialize;
+use serde_json::Value;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime};
+use tonic::body::Body as TonicBody;
+use tonic::transport::server::TcpConnectInfo;
+use tonic::Code;
+use tower::{Layer, Service};
+use tracing::error;
+use wire_tonic::tap::{Finish, Side, Tap, TapBody};
+
+/// One line of the audit log.
+#[derive(Debug, Serialize)]
+struct Record {
+    /// When the call started, in RFC 3339.
+    timestamp: String,
+
+    peer: Option<String>,
+
+    /// The gRPC method, as `/package.Service/Method`.
+    method: String,
+
+    requests: Messages,
+
+    responses: Messages,
+
+    /// The name of the status code. Calls that ended without one, because the client went
+    /// away, are logged as `Cancelled`.
+    status: String,
+
+    duration_ms: f64,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Messages {
+    count: usize,
+
+    /// The first few messages, with redacted fields replaced.
+    sample: Vec<Value>,
+}
+
+#[derive(Debug)]
+struct Sink {
+    file: Mutex<RotatingFile>,
+    pool: DescriptorPool,
+    summarizer: Summarizer,
+    sample: usize,
+}
+
+impl Sink {
+    fn write(&self, record: &Record) {
+        let mut line = serde_json::to_vec(record).unwrap();
+        line.push(b'\n');
+        if let Err(err) = self.file.lock().unwrap().write_line(&line) {
+            error!("failed to audit call to {}: {}", record.method, err);
+        }
+    }
+
+    /// The request and response types of the method at `path`.
+    fn types(&self, path: &str) -> Option<(MessageDescriptor, MessageDescriptor)> {
+        let (service, method) = path.trim_start_matches('/').split_once('/')?;
+        let method = self
+            .pool
+            .get_service_by_name(service)?
+            .methods()
+            .find(|x| x.name() == method)?;
+        Some((method.input(), method.output()))
+    }
+}
+
+/// Appends a JSONL record of every call to a rotating file: who made it, which method, a
+/// summary of the messages each way, the status and how long it took. A streaming call gets
+/// one record when it ends.
+#[derive(Debug, Clone)]
+pub struct AuditLayer {
+    sink: Arc<Sink>,
+}
+
+impl AuditLayer {
+    /// Audits calls to the services in `pool` to `config.path`.
+    pub fn new(config: &AuditConfig, pool: DescriptorPool) -> io::Result<Self> {
+        let path = config.path.as_deref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no audit log path configured")
+        })?;
+        Ok(Self {
+            sink: Arc::new(Sink {
+                file: Mutex::new(RotatingFile::open(path, config.max_bytes, config.keep)?),
+                pool,
+                summarizer: Summarizer {
+                    redact: config.redact.iter().cloned().collect(),
+                },
+                sample: config.sample,
+            }),
+        })
+    }
+}
+
+impl<S> Layer<S> for AuditLayer {
+    type Service = Audit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Audit {
+            inner,
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Audit<S> {
+    inner: S,
+    sink: Arc<Sink>,
+}
+
+/// One direction of a call: its messages as they arrive.
+#[derive(Debug, Default)]
+struct Direction {
+    desc: Option<MessageDescriptor>,
+    encoding: Option<String>,
+    messages: Messages,
+}
+
+impl Direction {
+    /// Counts the message, and summarizes it while the sample has room.
+    fn on_message(&mut self, compressed: bool, payload: &Bytes, sink: &Sink) {
+        self.messages.count += 1;
+        if self.messages.sample.len() < sink.sample {
+            let encoding = self.encoding.as_deref();
+            let summary =
+                sink.summarizer
+                    .summarize(self.desc.as_ref(), compressed, encoding, payload);
+            self.messages.sample.push(summary);
+        }
+    }
+}
+
+/// What is known of a call so far. The record is written when it finishes, so a call that
+/// never ends is never logged.
+#[derive(Debug)]
+struct CallState {
+    timestamp: SystemTime,
+    started: Instant,
+    peer: Option<String>,
+    method: String,
+    requests: Direction,
+    responses: Direction,
+    status: Option<Code>,
+    sink: Option<Arc<Sink>>,
+}
+
+impl CallState {
+    /// Picks up the status from the response headers or trailers, whichever carries it.
+    fn on_headers(&mut self, headers: &HeaderMap) {
+        if let Some(code) = headers.get("grpc-status") {
+            self.status = Some(Code::from_bytes(code.as_bytes()));
+        }
+    }
+}
+
+impl Tap for CallState {
+    fn on_message(&mut self, side: Side, compressed: bool, payload: Bytes) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        let direction = match side {
+            Side::Request => &mut self.requests,
+            Side::Response => &mut self.responses,
+        };
+        direction.on_message(compressed, &payload, sink);
+    }
+
+    fn on_trailers(&mut self, trailers: &HeaderMap) {
+        self.on_headers(trailers);
+    }
+
+    fn finish(&mut self) {
+        let Some(sink) = self.sink.take() else {
+            return;
+        };
+        sink.write(&Record {
+            timestamp: humantime::format_rfc3339_millis(self.timestamp).to_string(),
+            peer: self.peer.take(),
+            method: std::mem::take(&mut self.method),
+            requests: std::mem::take(&mut self.requests.messages),
+            responses: std::mem::take(&mut self.responses.messages),
+            status: format!("{:?}", self.status.unwrap_or(Code::Cancelled)),
+            duration_ms: self.started.elapsed().as_secs_f64() * 1000.0,
+        });
+    }
+}
+
+fn encoding(headers: &HeaderMap) -> Option<String> {
+    let encoding = headers.get("grpc-encoding")?.to_str().ok()?;
+    Some(encoding.to_string())
+}
+
+impl<S> Service<http::Request<TonicBody>> for Audit<S>
+where
+    S: Service<http::Request<TonicBody>, Response = http::Response<TonicBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<TonicBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<TonicBody>) -> Self::Future {
+        let method = req.uri().path().to_string();
+        let (input, output) = self.sink.types(&method).unzip();
+        let peer = req
+            .extensions()
+            .get::<TcpConnectInfo>()
+            .and_then(|x| x.remote_addr())
+            .map(|x| x.to_string());
+
+        let state = Arc::new(Mutex::new(CallState {
+            timestamp: SystemTime::now(),
+            started: Instant::now(),
+            peer,
+            method,
+            requests: Direction {
+                desc: input,
+                encoding: encoding(req.headers()),
+                ..Direction::default()
+            },
+            responses: Direction {
+                desc: output,
+                ..Direction::default()
+            },
+            status: None,
+            sink: Some(self.sink.clone()),
+        }));
+
+        let req = req.map(|inner| TapBody::request(inner, state.clone()));
+        let fut = self.inner.call(req);
+        let finish = Finish::new(state.clone());
+
+        Box::pin(async move {
+            let resp = fut.await?;
+            {
+                let mut state = state.lock().unwrap();
+                state.responses.encoding = encoding(resp.headers());
+                state.on_headers(resp.headers());
+            }
+            Ok(resp.map(|inner| TapBody::response(inner, finish)))
+        })
+    }
+}
@@ -0,0 +1,10 @@
+//! A tower layer for tonic servers that appends a JSONL record of every call to a rotating
+//! log file.
+
+mod config;
+mod file;
+mod layer;
+mod summary;
+
+pub use config::AuditConfig;
+pub use layer::{Audit, AuditLayer};
@@ -0,0 +1,196 @@
+use bytes::Bytes;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use prost_reflect::{DynamicMessage, Kind, MessageDescriptor};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::Read;
+
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// Turns messages into the JSON logged for them.
+#[derive(Debug, Default)]
+pub(crate) struct Summarizer {
+    /// Full names of the fields to redact.
+    pub(crate) redact: HashSet<String>,
+}
+
+impl Summarizer {
+    /// Summarizes a message of type `desc`, sent with `encoding` if `compressed`. Messages
+    /// that can't be decoded are summarized by their size.
+    pub(crate) fn summarize(
+        &self,
+        desc: Option<&MessageDescriptor>,
+        compressed: bool,
+        encoding: Option<&str>,
+        payload: &Bytes,
+    ) -> Value {
+        let payload = if compressed {
+            match decompress(encoding.unwrap_or("identity"), payload) {
+                Ok(payload) => Bytes::from(payload),
+                Err(err) => return json!({ "bytes": payload.len(), "error": err }),
+            }
+        } else {
+            payload.clone()
+        };
+
+        let Some(desc) = desc else {
+            return json!({ "bytes": payload.len() });
+        };
+        match DynamicMessage::decode(desc.clone(), payload.clone()) {
+            Ok(msg) => {
+                let mut value = serde_json::to_value(&msg).unwrap();
+                self.redact(desc, &mut value);
+                value
+            }
+            Err(err) => json!({ "bytes": payload.len(), "error": err.to_string() }),
+        }
+    }
+
+    fn redact(&self, desc: &MessageDescriptor, value: &mut Value) {
+        let Value::Object(object) = value else {
+            return;
+        };
+        for field in desc.fields() {
+            let Some(value) = object.get_mut(field.json_name()) else {
+                continue;
+            };
+            if self.redact.contains(field.full_name()) {
+                *value = Value::String(REDACTED.into());
+                continue;
+            }
+
+            let Kind::Message(inner) = field.kind() else {
+                continue;
+            };
+            if field.is_map() {
+                if let Kind::Message(inner) = inner.map_entry_value_field().kind() {
+                    for value in value
+                        .as_object_mut()
+                        .into_iter()
+                        .flat_map(|x| x.values_mut())
+                    {
+                        self.redact(&inner, value);
+                    }
+                }
+            } else if field.is_list() {
+                for value in value.as_array_mut().into_iter().flatten() {
+                    self.redact(&inner, value);
+                }
+            } else {
+                self.redact(&inner, value);
+            }
+        }
+    }
+}
+
+/// The most a message is decompressed to for its summary, tonic's default limit on the size
+/// of a message it decodes.
+const MAX_DECOMPRESSED_BYTES: u64 = 4 << 20;
+
+fn decompress(encoding: &str, payload: &[u8]) -> Result<Vec<u8>, String> {
+    let reader: Box<dyn Read + '_> = match encoding {
+        "gzip" => Box::new(GzDecoder::new(payload)),
+        "deflate" => Box::new(ZlibDecoder::new(payload)),
+        "zstd" => Box::new(
+            zstd::stream::read::Decoder::new(payload)
+                .map_err(|err| format!("failed to decompress {}: {}", encoding, err))?,
+        ),
+        other => return Err(format!("compressed with unknown encoding {:?}", other)),
+    };
+    // Read one byte past the limit to tell a message at the limit from a larger one.
+    let mut out = vec![];
+    reader
+        .take(MAX_DECOMPRESSED_BYTES + 1)
+        .read_to_end(&mut out)
+        .map_err(|err| format!("failed to decompress {}: {}", encoding, err))?;
+    if out.len() as u64 > MAX_DECOMPRESSED_BYTES {
+        return Err(format!(
+            "decompresses to more than {} bytes",
+            MAX_DECOMPRESSED_BYTES
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use prost_reflect::DescriptorPool;
+    use std::io::Write;
+
+    fn pool() -> DescriptorPool {
+        let mut compiler = protox::Compiler::new(["../routeguide-tonic/proto"]).unwrap();
+        compiler.open_file("route_guide.proto").unwrap();
+        compiler.descriptor_pool()
+    }
+
+    fn note(pool: &DescriptorPool) -> (MessageDescriptor, Bytes) {
+        let desc = pool.get_message_by_name("routeguide.RouteNote").unwrap();
+        let value = json!({ "location": { "latitude": 1, "longitude": 2 }, "message": "hi" });
+        let msg = DynamicMessage::deserialize(desc.clone(), value).unwrap();
+        (desc, msg.encode_to_vec().into())
+    }
+
+    #[test]
+    fn redacts_nested_fields() {
+        let pool = pool();
+        let (desc, payload) = note(&pool);
+        let summarizer = Summarizer {
+            redact: ["routeguide.Point.latitude".to_string()].into(),
+        };
+
+        let value = summarizer.summarize(Some(&desc), false, None, &payload);
+        assert_eq!(
+            value,
+            json!({ "location": { "latitude": REDACTED, "longitude": 2 }, "message": "hi" })
+        );
+    }
+
+    #[test]
+    fn decompresses_and_falls_back_to_sizes() {
+        let pool = pool();
+        let (desc, payload) = note(&pool);
+        let summarizer = Summarizer::default();
+
+        let mut gzip = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gzip.write_all(&payload).unwrap();
+        let gzip = Bytes::from(gzip.finish().unwrap());
+        let value = summarizer.summarize(Some(&desc), true, Some("gzip"), &gzip);
+        assert_eq!(value["message"], "hi");
+
+        let value = summarizer.summarize(None, false, None, &payload);
+        assert_eq!(value, json!({ "bytes": payload.len() }));
+
+        let value = summarizer.summarize(Some(&desc), true, Some("snappy"), &payload);
+        assert_eq!(
+            value["error"],
+            "compressed with unknown encoding \"snappy\""
+        );
+    }
+
+    #[test]
+    fn stops_decompressing_past_the_limit() {
+        let pool = pool();
+        let (desc, _) = note(&pool);
+        let summarizer = Summarizer::default();
+        let zeros = vec![0; MAX_DECOMPRESSED_BYTES as usize * 4];
+
+        let mut gzip = flate2::write::GzEncoder::new(vec![], flate2::Compression::best());
+        gzip.write_all(&zeros).unwrap();
+        let gzip = Bytes::from(gzip.finish().unwrap());
+        let zstd = Bytes::from(zstd::stream::encode_all(&zeros[..], 19).unwrap());
+
+        for (encoding, payload) in [("gzip", gzip), ("zstd", zstd)] {
+            assert!(payload.len() < 1 << 16);
+            let value = summarizer.summarize(Some(&desc), true, Some(encoding), &payload);
+            assert_eq!(
+                value,
+                json!({
+                    "bytes": payload.len(),
+                    "error": format!("decompresses to more than {} bytes", MAX_DECOMPRESSED_BYTES),
+                })
+            );
+        }
+    }
+}
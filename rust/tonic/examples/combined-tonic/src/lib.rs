@@ -0,0 +1,176 @@
+use helloworld_tonic::echo::EchoLayer;
+use helloworld_tonic::hello_world::greeter_server::GreeterServer;
+use helloworld_tonic::hello_world::FILE_DESCRIPTOR_SET as HELLOWORLD_DESCRIPTORS;
+use helloworld_tonic::server::MyGreeter;
+use routeguide_tonic::compression::{CompressLayer, CompressionConfig};
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::{Feature, FILE_DESCRIPTOR_SET as ROUTEGUIDE_DESCRIPTORS};
+use routeguide_tonic::service::RouteGuideService;
+use routeguide_tonic::shutdown::Drain;
+
+use audit_tonic::AuditConfig;
+use axum::http::StatusCode;
+use axum::routing::get;
+use prost::Message;
+use std::error::Error;
+use std::future::Future;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::server::NamedService;
+use tonic::service::Routes;
+use tonic::transport::Server;
+use tonic_health::ServingStatus;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower::util::option_layer;
+use tower_http::trace::TraceLayer;
+use tracing::{info, warn};
+
+/// The services of the combined server, by the names clients call them with.
+const SERVICES: [&str; 2] = [
+    <GreeterServer<MyGreeter> as NamedService>::NAME,
+    <RouteGuideServer<RouteGuideService> as NamedService>::NAME,
+];
+
+/// How the combined server is run. Its middleware is shared by every service on it.
+#[derive(Debug, Clone, clap::Args)]
+pub struct Config {
+    /// Also answer HTTP/1.1, for the health page at `/healthz`. gRPC still needs HTTP/2.
+    #[arg(long)]
+    pub http1: bool,
+
+    /// Calls handled at once, over all connections. The rest wait their turn.
+    #[arg(long, default_value_t = 1024)]
+    pub concurrency_limit: usize,
+
+    /// Seconds a call may take, unless the client asks for less with `grpc-timeout`.
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// Largest message, in bytes, a service accepts.
+    #[arg(long, default_value_t = 4 << 20)]
+    pub max_message_size: usize,
+
+    #[command(flatten)]
+    pub compression: CompressionConfig,
+
+    #[command(flatten)]
+    pub audit: AuditConfig,
+
+    /// Seconds to wait for open calls to finish once shutdown starts.
+    #[arg(long, default_value_t = 10)]
+    pub drain_timeout: u64,
+}
+
+/// Merges the descriptors of both services into one set, for the audit log to decode any
+/// call with.
+fn descriptors() -> Result<Vec<u8>, prost::DecodeError> {
+    let mut set = prost_types::FileDescriptorSet::decode(HELLOWORLD_DESCRIPTORS)?;
+    let routeguide = prost_types::FileDescriptorSet::decode(ROUTEGUIDE_DESCRIPTORS)?;
+    set.file.extend(routeguide.file);
+    Ok(set.encode_to_vec())
+}
+
+/// Serves the Greeter and RouteGuide services, with reflection and health checks, on
+/// `listener` until `signal` resolves. Then health checks report `NOT_SERVING`, streams are
+/// drained, and open calls get `drain_timeout` to finish.
+pub async fn serve(
+    config: Config,
+    features: Vec<Feature>,
+    listener: TcpListener,
+    signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn Error>> {
+    let route_guide = RouteGuideService::new(features);
+    let drain = route_guide.drain();
+
+    let mut greeter = GreeterServer::new(MyGreeter::new(true).with_drain(drain.clone()))
+        .max_decoding_message_size(config.max_message_size);
+    let mut route_guide =
+        RouteGuideServer::new(route_guide).max_decoding_message_size(config.max_message_size);
+    for encoding in config.compression.accepted() {
+        greeter = greeter.accept_compressed(encoding);
+        route_guide = route_guide.accept_compressed(encoding);
+    }
+
+    let (health, health_service) = tonic_health::server::health_reporter();
+    for service in SERVICES {
+        health
+            .set_service_status(service, ServingStatus::Serving)
+            .await;
+    }
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(HELLOWORLD_DESCRIPTORS)
+        .register_encoded_file_descriptor_set(ROUTEGUIDE_DESCRIPTORS)
+        .build_v1()?;
+
+    // Calls are routed by the service in their path. Anything else, such as the health page,
+    // falls through to axum.
+    let mut routes = Routes::builder();
+    routes
+        .add_service(greeter)
+        .add_service(route_guide)
+        .add_service(reflection)
+        .add_service(health_service);
+    let router = routes.routes().into_axum_router().route(
+        "/healthz",
+        get({
+            let drain = drain.clone();
+            move || health_page(drain)
+        }),
+    );
+
+    let audit = config.audit.layer(&descriptors()?)?;
+    let mut server = Server::builder().accept_http1(config.http1);
+    if let Some(timeout) = config.timeout {
+        server = server.timeout(Duration::from_secs(timeout));
+    }
+    // Echo and compression both wrap the body of each response, so come outside the audit log,
+    // which records the messages as the services sent them.
+    let serve = server
+        .layer(TraceLayer::new_for_grpc())
+        .layer(GlobalConcurrencyLimitLayer::new(config.concurrency_limit))
+        .layer(EchoLayer)
+        .layer(CompressLayer::server(config.compression))
+        .layer(option_layer(audit))
+        .add_routes(Routes::from(router))
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), {
+            let drain = drain.clone();
+            async move {
+                signal.await;
+                info!("shutting down, draining open calls");
+                health
+                    .set_service_status("", ServingStatus::NotServing)
+                    .await;
+                for service in SERVICES {
+                    health
+                        .set_service_status(service, ServingStatus::NotServing)
+                        .await;
+                }
+                drain.start();
+            }
+        });
+
+    tokio::select! {
+        res = serve => res?,
+        _ = async {
+            drain.started().await;
+            tokio::time::sleep(Duration::from_secs(config.drain_timeout)).await;
+        } => warn!("calls still open after {}s, exiting anyway", config.drain_timeout),
+    }
+    Ok(())
+}
+
+/// A plain text page for load balancers and people, listing each service and whether it
+/// serves.
+async fn health_page(drain: Drain) -> (StatusCode, String) {
+    let (code, status) = if drain.is_started() {
+        (StatusCode::SERVICE_UNAVAILABLE, "NOT_SERVING")
+    } else {
+        (StatusCode::OK, "SERVING")
+    };
+    let mut page = String::new();
+    for service in SERVICES {
+        page.push_str(&format!("{service} {status}\n"));
+    }
+    (code, page)
+}
@@ -0,0 +1,36 @@
+use combined_tonic::Config;
+use routeguide_tonic::data;
+use routeguide_tonic::shutdown;
+
+use clap::Parser;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Serves Greeter and RouteGuide together on one port.
+#[derive(Debug, Parser)]
+struct Args {
+    #[arg(long, default_value = "[::1]:50051")]
+    addr: SocketAddr,
+
+    /// Feature database for RouteGuide, instead of the one shipped with routeguide-tonic.
+    #[arg(long)]
+    data: Option<PathBuf>,
+
+    #[command(flatten)]
+    config: Config,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let features = data::load_from(&args.data.unwrap_or_else(data::default_path))?;
+    let listener = tokio::net::TcpListener::bind(args.addr).await?;
+    info!("serving {} features on {}", features.len(), args.addr);
+    combined_tonic::serve(args.config, features, listener, shutdown::signal()).await?;
+
+    info!("stopped");
+    Ok(())
+}
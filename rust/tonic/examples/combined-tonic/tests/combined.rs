@@ -0,0 +1,204 @@
+use combined_tonic::Config;
+use helloworld_tonic::hello_world::greeter_client::GreeterClient;
+use helloworld_tonic::hello_world::HelloRequest;
+use routeguide_tonic::compression::CompressionConfig;
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::{Feature, Point};
+
+use audit_tonic::AuditConfig;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Request;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
+
+fn config(audit_log: Option<PathBuf>) -> Config {
+    Config {
+        http1: true,
+        concurrency_limit: 16,
+        timeout: None,
+        max_message_size: 1024,
+        compression: CompressionConfig {
+            encodings: vec![routeguide_tonic::compression::Encoding::Identity],
+            min_size: 1024,
+        },
+        audit: AuditConfig {
+            path: audit_log,
+            redact: vec![],
+            max_bytes: 1 << 20,
+            keep: 1,
+            sample: 1,
+        },
+        drain_timeout: 1,
+    }
+}
+
+fn feature() -> Feature {
+    Feature {
+        name: "Berkshire Valley Management Area Trail".into(),
+        location: Some(Point {
+            latitude: 409_146_138,
+            longitude: -746_188_906,
+        }),
+        ..Feature::default()
+    }
+}
+
+struct Running {
+    addr: SocketAddr,
+    channel: Channel,
+    stop: oneshot::Sender<()>,
+    server: JoinHandle<Result<(), String>>,
+}
+
+/// Starts the combined server, which stops when `stop` is sent to.
+async fn start_server(config: Config) -> Running {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(async move {
+        let signal = async {
+            let _ = stopped.await;
+        };
+        let res = combined_tonic::serve(config, vec![feature()], listener, signal).await;
+        res.map_err(|err| err.to_string())
+    });
+    let channel = Endpoint::from_shared(format!("http://{addr}"))
+        .unwrap()
+        .connect()
+        .await
+        .unwrap();
+    Running {
+        addr,
+        channel,
+        stop,
+        server,
+    }
+}
+
+async fn http1_get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    // A server that only speaks HTTP/2 resets the connection, leaving the response empty.
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).await;
+    response
+}
+
+async fn health(channel: Channel, service: &str) -> ServingStatus {
+    let req = HealthCheckRequest {
+        service: service.into(),
+    };
+    let resp = HealthClient::new(channel).check(req).await.unwrap();
+    resp.into_inner().status()
+}
+
+#[tokio::test]
+async fn serves_both_services_on_one_port() {
+    let log = std::env::temp_dir().join(format!("combined-{}-audit.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&log);
+    let Running {
+        addr,
+        channel,
+        stop,
+        server,
+    } = start_server(config(Some(log.clone()))).await;
+
+    let mut greeter = GreeterClient::new(channel.clone());
+    let mut request = Request::new(HelloRequest {
+        name: "Tonic".into(),
+        locale: "fr".into(),
+    });
+    request
+        .metadata_mut()
+        .insert("x-request-id", "greet".parse().unwrap());
+    let resp = greeter.say_hello(request).await.unwrap();
+    assert_eq!(resp.get_ref().message, "Bonjour Tonic");
+    assert_eq!(resp.metadata().get("x-request-id").unwrap(), "greet");
+
+    // The middleware is shared: RouteGuide gets its request ID echoed too.
+    let mut route_guide = RouteGuideClient::new(channel.clone());
+    let mut request = Request::new(feature().location.unwrap());
+    request
+        .metadata_mut()
+        .insert("x-request-id", "route".parse().unwrap());
+    let resp = route_guide.get_feature(request).await.unwrap();
+    assert_eq!(resp.get_ref().name, feature().name);
+    assert_eq!(resp.metadata().get("x-request-id").unwrap(), "route");
+
+    // And so are the limits.
+    let err = greeter
+        .say_hello(HelloRequest {
+            name: "x".repeat(2048),
+            locale: String::new(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::OutOfRange);
+
+    let methods: Vec<_> = std::fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|x| serde_json::from_str::<Value>(x).unwrap()["method"].clone())
+        .collect();
+    assert!(
+        methods.contains(&"/helloworld.Greeter/SayHello".into()),
+        "{:?}",
+        methods
+    );
+    assert!(
+        methods.contains(&"/routeguide.RouteGuide/GetFeature".into()),
+        "{:?}",
+        methods
+    );
+
+    for service in ["helloworld.Greeter", "routeguide.RouteGuide"] {
+        assert_eq!(
+            health(channel.clone(), service).await,
+            ServingStatus::Serving
+        );
+    }
+    let page = http1_get(addr, "/healthz").await;
+    assert!(page.starts_with("HTTP/1.1 200"), "{}", page);
+    assert!(page.contains("helloworld.Greeter SERVING\n"), "{}", page);
+    assert!(page.contains("routeguide.RouteGuide SERVING\n"), "{}", page);
+
+    // Watchers see the server go down, and it stops once their stream times out.
+    let req = HealthCheckRequest {
+        service: "routeguide.RouteGuide".into(),
+    };
+    let mut watch = HealthClient::new(channel)
+        .watch(req)
+        .await
+        .unwrap()
+        .into_inner();
+    let status = watch.message().await.unwrap().unwrap().status();
+    assert_eq!(status, ServingStatus::Serving);
+    stop.send(()).unwrap();
+    let status = watch.message().await.unwrap().unwrap().status();
+    assert_eq!(status, ServingStatus::NotServing);
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn http1_is_off_by_default() {
+    let running = start_server(Config {
+        http1: false,
+        ..config(None)
+    })
+    .await;
+    let page = http1_get(running.addr, "/healthz").await;
+    assert!(!page.contains("SERVING"), "{}", page);
+}
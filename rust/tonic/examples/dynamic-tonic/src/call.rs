@@ -0,0 +1,70 @@
+use crate::codec::DynamicCodec;
+
+use prost_reflect::{DynamicMessage, MethodDescriptor};
+use tonic::client::Grpc;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::MetadataMap;
+use tonic::transport::Channel;
+use tonic::{Request, Status, Streaming};
+
+/// Calls `method` with `requests`, handing each response to `on_response` as it arrives, and
+/// returns the trailers. Methods that don't stream requests take exactly one.
+pub async fn call(
+    channel: Channel,
+    method: &MethodDescriptor,
+    requests: Vec<DynamicMessage>,
+    mut on_response: impl FnMut(DynamicMessage),
+) -> Result<MetadataMap, Status> {
+    let path = format!("/{}/{}", method.parent_service().full_name(), method.name());
+    let path = PathAndQuery::try_from(path).map_err(|err| Status::internal(err.to_string()))?;
+    let codec = DynamicCodec::new(method.output());
+
+    let mut grpc = Grpc::new(channel);
+    grpc.ready()
+        .await
+        .map_err(|err| Status::unavailable(err.to_string()))?;
+
+    if !method.is_client_streaming() {
+        let [request] = <[_; 1]>::try_from(requests).map_err(|requests| {
+            Status::invalid_argument(format!(
+                "{} takes one request message, not {}",
+                method.full_name(),
+                requests.len()
+            ))
+        })?;
+
+        if method.is_server_streaming() {
+            let stream = grpc
+                .server_streaming(Request::new(request), path, codec)
+                .await?;
+            drain(stream.into_inner(), on_response).await
+        } else {
+            let resp = grpc.unary(Request::new(request), path, codec).await?;
+            let (metadata, message, _) = resp.into_parts();
+            on_response(message);
+            // Tonic merges the trailers of a unary response into its metadata.
+            Ok(metadata)
+        }
+    } else {
+        let requests = Request::new(tokio_stream::iter(requests));
+        if method.is_server_streaming() {
+            let stream = grpc.streaming(requests, path, codec).await?;
+            drain(stream.into_inner(), on_response).await
+        } else {
+            let resp = grpc.client_streaming(requests, path, codec).await?;
+            let (metadata, message, _) = resp.into_parts();
+            on_response(message);
+            Ok(metadata)
+        }
+    }
+}
+
+async fn drain(
+    mut stream: Streaming<DynamicMessage>,
+    mut on_response: impl FnMut(DynamicMessage),
+) -> Result<MetadataMap, Status> {
+    while let Some(message) = stream.message().await? {
+        on_response(message);
+    }
+    Ok(stream.trailers().await?.unwrap_or_default())
+}
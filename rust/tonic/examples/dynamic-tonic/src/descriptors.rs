@@ -0,0 +1,165 @@
+use prost::Message;
+use prost_reflect::{DescriptorPool, MethodDescriptor};
+use prost_types::FileDescriptorProto;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
+use tonic::Streaming;
+use tonic_reflection::pb::v1::server_reflection_client::ServerReflectionClient;
+use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+use tonic_reflection::pb::v1::server_reflection_response::MessageResponse;
+use tonic_reflection::pb::v1::{ServerReflectionRequest, ServerReflectionResponse};
+
+/// Compiles `.proto` files. Imports are resolved against `includes`, or against the
+/// directories of the files when there are none.
+pub fn from_protos(
+    files: &[impl AsRef<Path>],
+    includes: &[PathBuf],
+) -> Result<DescriptorPool, Box<dyn Error>> {
+    let mut includes = includes.to_vec();
+    if includes.is_empty() {
+        for file in files {
+            let dir = file.as_ref().parent().unwrap_or(Path::new("."));
+            includes.push(dir.to_path_buf());
+        }
+    }
+
+    let mut compiler = protox::Compiler::new(includes)?;
+    compiler.include_imports(true).open_files(files)?;
+    Ok(compiler.descriptor_pool())
+}
+
+/// Downloads the descriptors of every service a server lists through gRPC server reflection,
+/// along with the files they import.
+pub async fn from_reflection(channel: Channel) -> Result<DescriptorPool, Box<dyn Error>> {
+    let mut reflection = Reflection::start(channel).await?;
+
+    let services = match reflection
+        .ask(MessageRequest::ListServices(String::new()))
+        .await?
+    {
+        MessageResponse::ListServicesResponse(resp) => resp.service,
+        other => return Err(format!("unexpected reflection response {:?}", other).into()),
+    };
+
+    let mut files = HashMap::new();
+    let mut missing = vec![];
+    for service in services {
+        let request = MessageRequest::FileContainingSymbol(service.name);
+        reflection
+            .add_files(request, &mut files, &mut missing)
+            .await?;
+    }
+    while let Some(name) = missing.pop() {
+        if !files.contains_key(&name) {
+            let request = MessageRequest::FileByFilename(name);
+            reflection
+                .add_files(request, &mut files, &mut missing)
+                .await?;
+        }
+    }
+
+    let mut pool = DescriptorPool::new();
+    pool.add_file_descriptor_protos(files.into_values())?;
+    Ok(pool)
+}
+
+/// Finds a method named either `package.Service/Method` or `package.Service.Method`.
+pub fn find_method(pool: &DescriptorPool, name: &str) -> Option<MethodDescriptor> {
+    let (service, method) = name.rsplit_once('/').or_else(|| name.rsplit_once('.'))?;
+    pool.get_service_by_name(service)?
+        .methods()
+        .find(|x| x.name() == method)
+}
+
+/// One server reflection stream, which answers requests in order.
+struct Reflection {
+    requests: mpsc::Sender<ServerReflectionRequest>,
+    responses: Streaming<ServerReflectionResponse>,
+}
+
+impl Reflection {
+    async fn start(channel: Channel) -> Result<Self, tonic::Status> {
+        let (requests, rx) = mpsc::channel(1);
+        let responses = ServerReflectionClient::new(channel)
+            .server_reflection_info(ReceiverStream::new(rx))
+            .await?
+            .into_inner();
+        Ok(Self {
+            requests,
+            responses,
+        })
+    }
+
+    async fn ask(&mut self, request: MessageRequest) -> Result<MessageResponse, Box<dyn Error>> {
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(request),
+        };
+        self.requests
+            .send(request)
+            .await
+            .map_err(|_| "reflection stream closed")?;
+
+        match self.responses.message().await? {
+            Some(ServerReflectionResponse {
+                message_response: Some(MessageResponse::ErrorResponse(err)),
+                ..
+            }) => Err(format!("reflection error {}: {}", err.error_code, err.error_message).into()),
+            Some(ServerReflectionResponse {
+                message_response: Some(resp),
+                ..
+            }) => Ok(resp),
+            _ => Err("reflection stream ended early".into()),
+        }
+    }
+
+    /// Adds the files answering `request` to `files`, and the imports not in it yet to
+    /// `missing`.
+    async fn add_files(
+        &mut self,
+        request: MessageRequest,
+        files: &mut HashMap<String, FileDescriptorProto>,
+        missing: &mut Vec<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let resp = match self.ask(request).await? {
+            MessageResponse::FileDescriptorResponse(resp) => resp,
+            other => return Err(format!("unexpected reflection response {:?}", other).into()),
+        };
+        for bytes in resp.file_descriptor_proto {
+            let file = FileDescriptorProto::decode(bytes.as_slice())?;
+            missing.extend(
+                file.dependency
+                    .iter()
+                    .filter(|x| !files.contains_key(*x))
+                    .cloned(),
+            );
+            files.insert(file.name().to_string(), file);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_methods_by_either_name() {
+        let pool = from_protos(&["../routeguide-tonic/proto/route_guide.proto"], &[]).unwrap();
+
+        for name in [
+            "routeguide.RouteGuide/RouteChat",
+            "routeguide.RouteGuide.RouteChat",
+        ] {
+            let method = find_method(&pool, name).unwrap();
+            assert_eq!(method.full_name(), "routeguide.RouteGuide.RouteChat");
+            assert!(method.is_client_streaming() && method.is_server_streaming());
+        }
+        assert!(find_method(&pool, "routeguide.RouteGuide/Missing").is_none());
+        assert!(find_method(&pool, "RouteChat").is_none());
+    }
+}
@@ -0,0 +1,54 @@
+use prost_reflect::{DynamicMessage, MessageDescriptor};
+use serde_json::Value;
+
+/// Parses request messages written in the protobuf JSON mapping. The input holds any number
+/// of JSON objects, one after another, or a single array of them.
+pub fn parse(
+    desc: &MessageDescriptor,
+    input: &str,
+) -> Result<Vec<DynamicMessage>, serde_json::Error> {
+    let mut values = vec![];
+    for value in serde_json::Deserializer::from_str(input).into_iter::<Value>() {
+        match value? {
+            Value::Array(items) => values.extend(items),
+            value => values.push(value),
+        }
+    }
+    values
+        .into_iter()
+        .map(|value| DynamicMessage::deserialize(desc.clone(), value))
+        .collect()
+}
+
+pub fn to_string_pretty(msg: &DynamicMessage) -> String {
+    serde_json::to_string_pretty(msg).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point() -> MessageDescriptor {
+        let pool =
+            crate::descriptors::from_protos(&["../routeguide-tonic/proto/route_guide.proto"], &[])
+                .unwrap();
+        pool.get_message_by_name("routeguide.Point").unwrap()
+    }
+
+    #[test]
+    fn parses_a_sequence_or_an_array_of_messages() {
+        let point = point();
+        let sequence = parse(&point, r#"{"latitude": 1} {"longitude": 2}"#).unwrap();
+        let array = parse(&point, r#"[{"latitude": 1}, {"longitude": 2}]"#).unwrap();
+
+        assert_eq!(sequence, array);
+        assert_eq!(to_string_pretty(&sequence[0]), "{\n  \"latitude\": 1\n}");
+        assert!(parse(&point, "").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let err = parse(&point(), r#"{"altitude": 1}"#).unwrap_err();
+        assert!(err.to_string().contains("altitude"), "{}", err);
+    }
+}
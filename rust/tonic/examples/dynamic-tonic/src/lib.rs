@@ -0,0 +1,4 @@
+pub mod call;
+pub mod codec;
+pub mod descriptors;
+pub mod json;
@@ -0,0 +1,123 @@
+use dynamic_tonic::call::call;
+use dynamic_tonic::descriptors::{self, find_method};
+use dynamic_tonic::json;
+
+use clap::{Parser, Subcommand};
+use prost_reflect::{DescriptorPool, MethodDescriptor};
+use std::io::Read;
+use std::path::PathBuf;
+use tonic::transport::{Channel, Endpoint};
+
+/// Calls any gRPC method, converting requests from JSON and responses to JSON.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Server to call.
+    #[arg(long, default_value = "http://[::1]:10000")]
+    addr: String,
+
+    /// Read the method descriptors from these files instead of through server reflection.
+    #[arg(long = "proto")]
+    protos: Vec<PathBuf>,
+
+    /// Directories to resolve imports in `--proto` files against. Defaults to the directories
+    /// of the files.
+    #[arg(long = "import-path", short = 'I')]
+    import_paths: Vec<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the services and their methods.
+    List,
+
+    /// Call a method, printing each response.
+    Call {
+        /// The method, as `package.Service/Method`.
+        method: String,
+
+        /// Request messages as JSON, one after another. `@` reads them from stdin, and
+        /// `@FILE` from a file.
+        #[arg(short, long, default_value = "{}")]
+        data: String,
+
+        /// Also print the response trailers.
+        #[arg(short, long)]
+        verbose: bool,
+    },
+}
+
+fn shape(method: &MethodDescriptor) -> &'static str {
+    match (method.is_client_streaming(), method.is_server_streaming()) {
+        (false, false) => "unary",
+        (false, true) => "server streaming",
+        (true, false) => "client streaming",
+        (true, true) => "bidirectional streaming",
+    }
+}
+
+fn read_data(data: &str) -> std::io::Result<String> {
+    match data.strip_prefix('@') {
+        Some("") => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            Ok(input)
+        }
+        Some(path) => std::fs::read_to_string(path),
+        None => Ok(data.to_string()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let channel: Channel = Endpoint::from_shared(args.addr)?.connect_lazy();
+    let pool: DescriptorPool = if args.protos.is_empty() {
+        descriptors::from_reflection(channel.clone()).await?
+    } else {
+        descriptors::from_protos(&args.protos, &args.import_paths)?
+    };
+
+    match args.command {
+        Command::List => {
+            for service in pool.services() {
+                println!("{}", service.full_name());
+                for method in service.methods() {
+                    println!(
+                        "  {}({}) returns ({}), {}",
+                        method.name(),
+                        method.input().full_name(),
+                        method.output().full_name(),
+                        shape(&method)
+                    );
+                }
+            }
+        }
+        Command::Call {
+            method,
+            data,
+            verbose,
+        } => {
+            let method = find_method(&pool, &method)
+                .ok_or_else(|| format!("no method {} on the server", method))?;
+            let requests = json::parse(&method.input(), &read_data(&data)?)?;
+
+            let trailers = call(channel, &method, requests, |resp| {
+                println!("{}", json::to_string_pretty(&resp));
+            })
+            .await
+            .map_err(|status| format!("{:?}: {}", status.code(), status.message()))?;
+
+            if verbose {
+                for (name, value) in trailers.into_headers().iter() {
+                    println!("{}: {}", name, value.to_str().unwrap_or("<binary>"));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use dynamic_tonic::call::call;
+use dynamic_tonic::descriptors::{self, find_method};
+use dynamic_tonic::json;
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::{Feature, Point, FILE_DESCRIPTOR_SET};
+use routeguide_tonic::service::RouteGuideService;
+
+use prost_reflect::DescriptorPool;
+use serde_json::Value;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Endpoint, Server};
+use tonic::Code;
+
+fn features() -> Vec<Feature> {
+    (0..3)
+        .map(|i| Feature {
+            name: format!("feature {i}"),
+            location: Some(Point {
+                latitude: 400_000_000 + i * 1_000_000,
+                longitude: -740_000_000,
+            }),
+            ..Feature::default()
+        })
+        .collect()
+}
+
+/// Starts a route guide server with reflection, returning a channel to it.
+async fn start_server() -> Channel {
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .build_v1()
+        .unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(RouteGuideServer::new(RouteGuideService::new(features())))
+            .add_service(reflection)
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+
+    Endpoint::from_shared(format!("http://{addr}"))
+        .unwrap()
+        .connect_lazy()
+}
+
+/// Calls `method` with JSON `input`, returning the responses as JSON values.
+async fn call_json(
+    channel: &Channel,
+    pool: &DescriptorPool,
+    method: &str,
+    input: &str,
+) -> Result<Vec<Value>, tonic::Status> {
+    let method = find_method(pool, method).unwrap();
+    let requests = json::parse(&method.input(), input).unwrap();
+    let mut responses = vec![];
+    call(channel.clone(), &method, requests, |resp| {
+        responses.push(serde_json::from_str(&json::to_string_pretty(&resp)).unwrap());
+    })
+    .await?;
+    Ok(responses)
+}
+
+#[tokio::test]
+async fn reflection_matches_the_proto_file() {
+    let channel = start_server().await;
+
+    let reflected = descriptors::from_reflection(channel).await.unwrap();
+    let local =
+        descriptors::from_protos(&["../routeguide-tonic/proto/route_guide.proto"], &[]).unwrap();
+
+    let methods = |pool: &DescriptorPool| -> Vec<String> {
+        let service = pool.get_service_by_name("routeguide.RouteGuide").unwrap();
+        service.methods().map(|x| x.full_name().into()).collect()
+    };
+    assert_eq!(methods(&reflected), methods(&local));
+    assert!(reflected
+        .get_service_by_name("grpc.reflection.v1.ServerReflection")
+        .is_some());
+}
+
+#[tokio::test]
+async fn calls_every_streaming_shape_from_json() {
+    let channel = start_server().await;
+    let pool = descriptors::from_reflection(channel.clone()).await.unwrap();
+
+    let unary = call_json(
+        &channel,
+        &pool,
+        "routeguide.RouteGuide/GetFeature",
+        r#"{"latitude": 401000000, "longitude": -740000000}"#,
+    )
+    .await
+    .unwrap();
+    assert_eq!(unary[0]["name"], "feature 1");
+
+    let server_streaming = call_json(
+        &channel,
+        &pool,
+        "routeguide.RouteGuide/ListFeatures",
+        r#"{"lo": {"latitude": 400000000, "longitude": -741000000},
+            "hi": {"latitude": 401500000, "longitude": -739000000}}"#,
+    )
+    .await
+    .unwrap();
+    let mut names: Vec<_> = server_streaming
+        .iter()
+        .map(|x| x["name"].as_str())
+        .collect();
+    names.sort();
+    assert_eq!(names, [Some("feature 0"), Some("feature 1")]);
+
+    let client_streaming = call_json(
+        &channel,
+        &pool,
+        "routeguide.RouteGuide/RecordRoute",
+        r#"{"latitude": 400000000, "longitude": -740000000}
+           {"latitude": 402000000, "longitude": -740000000}
+           {"latitude": 1, "longitude": 1}"#,
+    )
+    .await
+    .unwrap();
+    assert_eq!(client_streaming[0]["pointCount"], 3);
+    assert_eq!(client_streaming[0]["featureCount"], 2);
+
+    let bidi = call_json(
+        &channel,
+        &pool,
+        "routeguide.RouteGuide/RouteChat",
+        r#"[{"location": {"latitude": 5}, "message": "first"},
+            {"location": {"latitude": 5}, "message": "second"}]"#,
+    )
+    .await
+    .unwrap();
+    let messages: Vec<_> = bidi
+        .iter()
+        .map(|x| x["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(messages, ["first", "first", "second"]);
+}
+
+#[tokio::test]
+async fn unary_methods_take_exactly_one_request() {
+    let channel = start_server().await;
+    let pool = descriptors::from_reflection(channel.clone()).await.unwrap();
+
+    let err = call_json(&channel, &pool, "routeguide.RouteGuide/GetFeature", "{} {}")
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(
+        err.message(),
+        "routeguide.RouteGuide.GetFeature takes one request message, not 2"
+    );
+}
@@ -0,0 +1,85 @@
+use crate::client::hello_request;
+use crate::hello_world::greeter_client::GreeterClient;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use tonic::transport::Channel;
+use tonic::Status;
+
+/// Latencies of the `SayHello` calls made by a benchmark, fastest first.
+#[derive(Debug)]
+pub struct Report {
+    pub elapsed: Duration,
+    pub latencies: Vec<Duration>,
+}
+
+impl Report {
+    /// The latency `p` percent of the calls were at least as fast as.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let Some(last) = self.latencies.len().checked_sub(1) else {
+            return Duration::ZERO;
+        };
+        self.latencies[(last as f64 * p / 100.0).round() as usize]
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rate = self.latencies.len() as f64 / self.elapsed.as_secs_f64();
+        writeln!(
+            f,
+            "{} calls in {:.2?}, {:.0} calls/s",
+            self.latencies.len(),
+            self.elapsed,
+            rate
+        )?;
+        write!(
+            f,
+            "latency p50={:.2?} p90={:.2?} p99={:.2?} max={:.2?}",
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.percentile(100.0)
+        )
+    }
+}
+
+/// Makes `calls` unary calls, `concurrency` at a time, stopping at the first failure.
+pub async fn run(
+    client: GreeterClient<Channel>,
+    name: &str,
+    calls: usize,
+    concurrency: usize,
+) -> Result<Report, Status> {
+    let started = Instant::now();
+    let concurrency = concurrency.clamp(1, calls.max(1));
+
+    let mut workers = JoinSet::new();
+    for worker in 0..concurrency {
+        let mut client = client.clone();
+        let request = hello_request(name, "");
+        // Spread the remainder over the first workers.
+        let count = calls / concurrency + usize::from(worker < calls % concurrency);
+        workers.spawn(async move {
+            let mut latencies = Vec::with_capacity(count);
+            for _ in 0..count {
+                let call = Instant::now();
+                client.say_hello(request.clone()).await?;
+                latencies.push(call.elapsed());
+            }
+            Ok::<_, Status>(latencies)
+        });
+    }
+
+    let mut latencies = Vec::with_capacity(calls);
+    while let Some(result) = workers.join_next().await {
+        latencies.extend(result.map_err(|err| Status::internal(err.to_string()))??);
+    }
+    latencies.sort();
+
+    Ok(Report {
+        elapsed: started.elapsed(),
+        latencies,
+    })
+}
@@ -1,27 +1,36 @@
-use hello_world::greeter_client::GreeterClient;
-use hello_world::HelloRequest;
-use tonic::{codec::CompressionEncoding, Request};
+use crate::hello_world::greeter_client::GreeterClient;
+use crate::hello_world::HelloRequest;
+use crate::options::{ConnectOptions, Encoding};
 
-pub mod hello_world {
-    tonic::include_proto!("helloworld");
-}
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let response = {
-        let mut client = GreeterClient::connect("http://[::1]:50051")
-            .await?
-            .accept_compressed(CompressionEncoding::Zstd)
-            .send_compressed(CompressionEncoding::Zstd);
+use tonic::transport::{Channel, Endpoint};
+use tonic::Request;
 
-        let request = Request::new(HelloRequest {
-            name: "Tonic".into(),
-        });
+pub async fn connect(
+    options: &ConnectOptions,
+    compression: Encoding,
+) -> Result<GreeterClient<Channel>, tonic::transport::Error> {
+    let channel = Endpoint::from_shared(format!("http://{}", options.addr))?
+        .connect_timeout(options.connect_timeout())
+        .connect()
+        .await?;
 
-        client.say_hello(request).await?
-    };
+    let mut client = GreeterClient::new(channel);
+    if let Some(encoding) = compression.tonic() {
+        client = client.accept_compressed(encoding).send_compressed(encoding);
+    }
+    Ok(client)
+}
 
-    println!("RESPONSE={:?}", response);
+pub fn hello_request(name: &str, locale: &str) -> HelloRequest {
+    HelloRequest {
+        name: name.into(),
+        locale: locale.into(),
+    }
+}
 
-    Ok(())
+/// Wraps a message in a request carrying an id, which the server echoes back in its trailers.
+pub fn with_request_id<T>(message: T, id: &str) -> Result<Request<T>, Box<dyn std::error::Error>> {
+    let mut request = Request::new(message);
+    request.metadata_mut().insert("x-request-id", id.parse()?);
+    Ok(request)
 }
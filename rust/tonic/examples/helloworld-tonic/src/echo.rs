@@ -0,0 +1,95 @@
+use http::HeaderMap;
+use http_body::{Body, Frame, SizeHint};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tonic::body::Body as TonicBody;
+use tower::{Layer, Service};
+
+/// Request metadata copied into the trailers of the response, so clients can match the
+/// responses they log to the requests they sent.
+pub const ECHOED_METADATA: &[&str] = &["x-request-id", "x-correlation-id"];
+
+/// Copies the `ECHOED_METADATA` of each request into the trailers of its response.
+#[derive(Debug, Clone, Default)]
+pub struct EchoLayer;
+
+impl<S> Layer<S> for EchoLayer {
+    type Service = Echo<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Echo { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Echo<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for Echo<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<TonicBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<EchoBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let mut echo = HeaderMap::new();
+        for name in ECHOED_METADATA {
+            for value in req.headers().get_all(*name) {
+                echo.append(*name, value.clone());
+            }
+        }
+
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let mut resp = fut.await?;
+            // A response that failed before sending anything has its status in the headers,
+            // and no trailers.
+            if resp.headers().contains_key("grpc-status") {
+                resp.headers_mut().extend(echo.drain());
+            }
+            Ok(resp.map(|inner| EchoBody { inner, echo }))
+        })
+    }
+}
+
+pub struct EchoBody {
+    inner: TonicBody,
+    echo: HeaderMap,
+}
+
+impl Body for EchoBody {
+    type Data = <TonicBody as Body>::Data;
+    type Error = <TonicBody as Body>::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut frame = ready!(Pin::new(&mut self.inner).poll_frame(cx));
+        if let Some(trailers) = frame
+            .as_mut()
+            .and_then(|x| x.as_mut().ok())
+            .and_then(|x| x.trailers_mut())
+        {
+            trailers.extend(self.echo.drain());
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,13 @@
+pub mod bench;
+pub mod client;
+pub mod echo;
+pub mod options;
+pub mod server;
+
+pub mod hello_world {
+    tonic::include_proto!("helloworld");
+
+    /// Descriptors of `helloworld.proto`, for the reflection service.
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("helloworld_descriptor");
+}
@@ -1,3 +1,159 @@
-fn main() {
-    println!("Hello, world!");
+use helloworld_tonic::bench;
+use helloworld_tonic::client::{self, hello_request, with_request_id};
+use helloworld_tonic::options::{CompressionOptions, ConnectOptions};
+use helloworld_tonic::server::{self, MyGreeter};
+
+use audit_tonic::AuditConfig;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::time::Duration;
+
+#[derive(Debug, Parser)]
+#[command(about = "Greeter server, client and benchmark")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    #[command(flatten)]
+    connect: ConnectOptions,
+
+    #[command(flatten)]
+    compression: CompressionOptions,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the Greeter server.
+    Serve {
+        /// Don't print each request.
+        #[arg(long)]
+        quiet: bool,
+
+        #[command(flatten)]
+        audit: AuditConfig,
+
+        /// Seconds to wait for open calls to finish after SIGINT or SIGTERM.
+        #[arg(long, default_value_t = 10)]
+        drain_timeout: u64,
+    },
+
+    /// Greet someone and print the responses and trailers.
+    Call {
+        /// Who to greet. Repeat it to greet several people with the streaming RPCs.
+        #[arg(long, default_value = "Tonic")]
+        name: Vec<String>,
+
+        /// Language of the greeting, such as "fr" or "fr-CA".
+        #[arg(long, default_value = "")]
+        locale: String,
+
+        #[arg(long, value_enum, default_value = "unary")]
+        rpc: Rpc,
+
+        /// Sent as x-request-id, which the server echoes in its trailers.
+        #[arg(long, default_value = "helloworld")]
+        request_id: String,
+    },
+
+    /// Make many unary calls and report their throughput and latency.
+    Bench {
+        #[arg(long, default_value = "Tonic")]
+        name: String,
+
+        #[arg(long, default_value_t = 10_000)]
+        calls: usize,
+
+        /// Calls in flight at once.
+        #[arg(long, default_value_t = 16)]
+        concurrency: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Rpc {
+    /// SayHello, once for the first name.
+    Unary,
+    /// SayHelloStream, for the first name.
+    Stream,
+    /// SayHelloGroup.
+    Group,
+    /// SayHelloChat.
+    Chat,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let compression = cli.compression.compression;
+
+    match cli.command {
+        Command::Serve {
+            quiet,
+            audit,
+            drain_timeout,
+        } => {
+            println!("Listening on {}", cli.connect.addr);
+            let greeter = MyGreeter::new(quiet);
+            let drain_timeout = Duration::from_secs(drain_timeout);
+            server::serve(
+                cli.connect.addr,
+                greeter,
+                compression,
+                &audit,
+                drain_timeout,
+            )
+            .await?;
+        }
+        Command::Call {
+            name,
+            locale,
+            rpc,
+            request_id,
+        } => {
+            let mut client = client::connect(&cli.connect, compression).await?;
+            let requests: Vec<_> = name.iter().map(|x| hello_request(x, &locale)).collect();
+            let first = requests[0].clone();
+
+            match rpc {
+                Rpc::Unary => {
+                    // Tonic merges the trailers of a unary response into its metadata.
+                    let response = client
+                        .say_hello(with_request_id(first, &request_id)?)
+                        .await?;
+                    println!("RESPONSE={:?}", response);
+                }
+                Rpc::Stream => {
+                    let request = with_request_id(first, &request_id)?;
+                    let mut stream = client.say_hello_stream(request).await?.into_inner();
+                    while let Some(response) = stream.message().await? {
+                        println!("RESPONSE={:?}", response);
+                    }
+                    println!("TRAILERS={:?}", stream.trailers().await?);
+                }
+                Rpc::Group => {
+                    let request = with_request_id(tokio_stream::iter(requests), &request_id)?;
+                    let response = client.say_hello_group(request).await?;
+                    println!("RESPONSE={:?}", response);
+                }
+                Rpc::Chat => {
+                    let request = with_request_id(tokio_stream::iter(requests), &request_id)?;
+                    let mut stream = client.say_hello_chat(request).await?.into_inner();
+                    while let Some(response) = stream.message().await? {
+                        println!("RESPONSE={:?}", response);
+                    }
+                    println!("TRAILERS={:?}", stream.trailers().await?);
+                }
+            }
+        }
+        Command::Bench {
+            name,
+            calls,
+            concurrency,
+        } => {
+            let client = client::connect(&cli.connect, compression).await?;
+            let report = bench::run(client, &name, calls, concurrency).await?;
+            println!("{}", report);
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,29 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+pub use wire_tonic::encoding::Encoding;
+
+/// Options shared by every subcommand that listens or connects.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ConnectOptions {
+    /// Address the server listens on, and the client connects to.
+    #[arg(long, global = true, default_value = "[::1]:50051")]
+    pub addr: SocketAddr,
+
+    /// Seconds the client waits for a connection.
+    #[arg(long, global = true, default_value_t = 5)]
+    pub connect_timeout: u64,
+}
+
+impl ConnectOptions {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout)
+    }
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct CompressionOptions {
+    /// Encoding messages are sent with, which the other side has to accept.
+    #[arg(long, global = true, value_enum, default_value = "zstd")]
+    pub compression: Encoding,
+}
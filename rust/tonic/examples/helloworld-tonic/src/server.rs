@@ -1,13 +1,107 @@
-use hello_world::greeter_server::{Greeter, GreeterServer};
-use hello_world::{HelloRequest, HelloResponse};
-use tonic::{codec::CompressionEncoding, transport::Server, Request, Response, Status};
+// Tonic streams and handlers all return `Result<_, Status>`, however large `Status` is.
+#![allow(clippy::result_large_err)]
 
-pub mod hello_world {
-    tonic::include_proto!("helloworld");
-}
+use crate::echo::EchoLayer;
+use crate::hello_world::greeter_server::{Greeter, GreeterServer};
+use crate::hello_world::{HelloRequest, HelloResponse, FILE_DESCRIPTOR_SET};
+use crate::options::Encoding;
+
+use audit_tonic::AuditConfig;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+use tower::util::option_layer;
+use wire_tonic::shutdown::{self, Drain};
+
+/// Greetings by language. The first one is used when a request has no locale.
+const GREETINGS: &[(&str, &str)] = &[
+    ("en", "Hello"),
+    ("de", "Hallo"),
+    ("es", "Hola"),
+    ("fr", "Bonjour"),
+    ("it", "Ciao"),
+    ("ja", "こんにちは"),
+    ("pt", "Olá"),
+];
 
 #[derive(Debug, Default)]
-pub struct MyGreeter {}
+pub struct MyGreeter {
+    /// Don't print the requests, which slows a benchmarked server down.
+    pub quiet: bool,
+    drain: Drain,
+}
+
+impl MyGreeter {
+    pub fn new(quiet: bool) -> Self {
+        Self {
+            quiet,
+            drain: Drain::default(),
+        }
+    }
+
+    /// Ends the open streams once `drain` starts, for a server that drains other services
+    /// along with this one.
+    pub fn with_drain(self, drain: Drain) -> Self {
+        Self { drain, ..self }
+    }
+
+    /// Ends the open streams of this greeter once started.
+    pub fn drain(&self) -> Drain {
+        self.drain.clone()
+    }
+}
+
+type ResponseStream = Pin<Box<dyn Stream<Item = Result<HelloResponse, Status>> + Send>>;
+
+/// Passes `responses` on until the server starts draining, then ends with
+/// `shutdown::status()` instead of being cut off.
+fn until_drained<S>(mut responses: S, drain: Drain) -> ResponseStream
+where
+    S: Stream<Item = Result<HelloResponse, Status>> + Send + Unpin + 'static,
+{
+    Box::pin(async_stream::try_stream! {
+        loop {
+            let response = tokio::select! {
+                // Checked first, so nothing more is sent once draining has started.
+                biased;
+                _ = drain.started() => Some(Err(shutdown::status())),
+                response = responses.next() => response,
+            };
+            let Some(response) = response else {
+                break;
+            };
+            let response = response?;
+            yield response;
+        }
+    })
+}
+
+/// Looks up the greeting for a locale, returning its language and the greeting.
+fn greeting(locale: &str) -> Result<(&'static str, &'static str), Status> {
+    if locale.is_empty() {
+        return Ok(GREETINGS[0]);
+    }
+    let language = locale
+        .split(['-', '_'])
+        .next()
+        .unwrap()
+        .to_ascii_lowercase();
+    GREETINGS
+        .iter()
+        .find(|(x, _)| *x == language)
+        .copied()
+        .ok_or_else(|| Status::invalid_argument(format!("unsupported locale {:?}", locale)))
+}
+
+fn hello(request: &HelloRequest) -> Result<HelloResponse, Status> {
+    let (locale, greeting) = greeting(&request.locale)?;
+    Ok(HelloResponse {
+        message: format!("{} {}", greeting, request.name),
+        locale: locale.into(),
+    })
+}
 
 #[tonic::async_trait]
 impl Greeter for MyGreeter {
@@ -15,29 +109,131 @@ impl Greeter for MyGreeter {
         &self,
         request: Request<HelloRequest>,
     ) -> Result<Response<HelloResponse>, Status> {
-        println!("Got a request: {:?}", request);
+        if !self.quiet {
+            println!("Got a request: {:?}", request);
+        }
+
+        Ok(Response::new(hello(request.get_ref())?))
+    }
+
+    type SayHelloStreamStream = ResponseStream;
+
+    async fn say_hello_stream(
+        &self,
+        request: Request<HelloRequest>,
+    ) -> Result<Response<Self::SayHelloStreamStream>, Status> {
+        if !self.quiet {
+            println!("Got a stream request: {:?}", request);
+        }
+
+        let name = request.into_inner().name;
+        let responses = GREETINGS.iter().map(move |(locale, greeting)| {
+            Ok(HelloResponse {
+                message: format!("{} {}", greeting, name),
+                locale: locale.to_string(),
+            })
+        });
+
+        let responses = tokio_stream::iter(responses);
+        Ok(Response::new(until_drained(responses, self.drain())))
+    }
+
+    async fn say_hello_group(
+        &self,
+        request: Request<Streaming<HelloRequest>>,
+    ) -> Result<Response<HelloResponse>, Status> {
+        if !self.quiet {
+            println!("Got a group request: {:?}", request);
+        }
 
-        let resp = HelloResponse {
-            message: format!("Hello {}", request.into_inner().name),
+        let mut stream = request.into_inner();
+        let mut locale = None;
+        let mut names = vec![];
+        loop {
+            let req = tokio::select! {
+                req = stream.message() => req?,
+                _ = self.drain.started() => return Err(shutdown::status()),
+            };
+            let Some(req) = req else {
+                break;
+            };
+            locale.get_or_insert(req.locale);
+            names.push(req.name);
+        }
+        let Some(locale) = locale else {
+            return Err(Status::invalid_argument("no names were sent"));
         };
 
-        Ok(Response::new(resp))
+        let (locale, greeting) = greeting(&locale)?;
+        Ok(Response::new(HelloResponse {
+            message: format!("{} {}", greeting, names.join(", ")),
+            locale: locale.into(),
+        }))
+    }
+
+    type SayHelloChatStream = ResponseStream;
+
+    async fn say_hello_chat(
+        &self,
+        request: Request<Streaming<HelloRequest>>,
+    ) -> Result<Response<Self::SayHelloChatStream>, Status> {
+        if !self.quiet {
+            println!("Got a chat request: {:?}", request);
+        }
+
+        let responses = request.into_inner().map(|req| hello(&req?));
+
+        Ok(Response::new(until_drained(responses, self.drain())))
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let addr = "[::1]:50051".parse()?;
-    let greeter = MyGreeter::default();
+/// Wraps a greeter in a server that accepts and sends `compression`.
+pub fn service(greeter: MyGreeter, compression: Encoding) -> GreeterServer<MyGreeter> {
+    let mut service = GreeterServer::new(greeter);
+    if let Some(encoding) = compression.tonic() {
+        service = service
+            .accept_compressed(encoding)
+            .send_compressed(encoding);
+    }
+    service
+}
+
+/// Serves `greeter` and server reflection on `addr` until SIGINT or SIGTERM. Then its streams
+/// are drained, and open calls get `drain_timeout` to finish.
+pub async fn serve(
+    addr: SocketAddr,
+    greeter: MyGreeter,
+    compression: Encoding,
+    audit: &AuditConfig,
+    drain_timeout: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let audit = audit.layer(FILE_DESCRIPTOR_SET)?;
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .build_v1()?;
 
-    Server::builder()
-        .add_service(
-            GreeterServer::new(greeter)
-                .accept_compressed(CompressionEncoding::Zstd)
-                .send_compressed(CompressionEncoding::Zstd),
-        )
-        .serve(addr)
-        .await?;
+    let drain = greeter.drain();
+    let serve = Server::builder()
+        .layer(EchoLayer)
+        .layer(option_layer(audit))
+        .add_service(service(greeter, compression))
+        .add_service(reflection)
+        .serve_with_shutdown(addr, {
+            let drain = drain.clone();
+            async move {
+                shutdown::signal().await;
+                println!("Shutting down, draining open calls");
+                drain.start();
+            }
+        });
+
+    tokio::select! {
+        res = serve => res?,
+        _ = async {
+            drain.started().await;
+            tokio::time::sleep(drain_timeout).await;
+        } => println!("Calls still open after {:?}, exiting anyway", drain_timeout),
+    }
 
     Ok(())
 }
@@ -0,0 +1,112 @@
+use audit_tonic::AuditConfig;
+use helloworld_tonic::client::{self, hello_request};
+use helloworld_tonic::hello_world::FILE_DESCRIPTOR_SET;
+use helloworld_tonic::options::{ConnectOptions, Encoding};
+use helloworld_tonic::server::{self, MyGreeter};
+
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tonic::Code;
+
+fn log_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "helloworld-audit-{}-{name}.jsonl",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn read_log(path: &Path) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn records_every_call_with_redacted_fields() {
+    let path = log_path("calls");
+    let config = AuditConfig {
+        path: Some(path.clone()),
+        redact: vec!["helloworld.HelloRequest.name".into()],
+        max_bytes: 1 << 20,
+        keep: 1,
+        sample: 2,
+    };
+    let audit = config.layer(FILE_DESCRIPTOR_SET).unwrap().unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .layer(audit)
+            .add_service(server::service(MyGreeter::new(true), Encoding::Gzip))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    let options = ConnectOptions {
+        addr,
+        connect_timeout: 5,
+    };
+    let mut greeter = client::connect(&options, Encoding::Gzip).await.unwrap();
+
+    greeter
+        .say_hello(hello_request("Alice", "fr"))
+        .await
+        .unwrap();
+
+    let requests = ["Bob", "Carol", "Dave"].map(|name| hello_request(name, "de"));
+    let mut stream = greeter
+        .say_hello_chat(tokio_stream::iter(requests))
+        .await
+        .unwrap()
+        .into_inner();
+    while stream.message().await.unwrap().is_some() {}
+
+    let err = greeter
+        .say_hello(hello_request("Eve", "xx"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    let records = read_log(&path);
+    assert_eq!(records.len(), 3);
+    for record in &records {
+        assert_eq!(
+            record["peer"].as_str().unwrap().split(':').next(),
+            Some("127.0.0.1")
+        );
+        assert!(record["duration_ms"].as_f64().unwrap() >= 0.0);
+        assert!(record["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+
+    assert_eq!(records[0]["method"], "/helloworld.Greeter/SayHello");
+    assert_eq!(records[0]["status"], "Ok");
+    assert_eq!(
+        records[0]["requests"],
+        json!({ "count": 1, "sample": [{ "name": "[REDACTED]", "locale": "fr" }] })
+    );
+    assert_eq!(
+        records[0]["responses"],
+        json!({ "count": 1, "sample": [{ "message": "Bonjour Alice", "locale": "fr" }] })
+    );
+
+    // A stream gets one record, with every message counted but only the first two sampled.
+    assert_eq!(records[1]["method"], "/helloworld.Greeter/SayHelloChat");
+    assert_eq!(records[1]["status"], "Ok");
+    assert_eq!(records[1]["requests"]["count"], 3);
+    assert_eq!(records[1]["responses"]["count"], 3);
+    assert_eq!(
+        records[1]["responses"]["sample"],
+        json!([
+            { "message": "Hallo Bob", "locale": "de" },
+            { "message": "Hallo Carol", "locale": "de" },
+        ])
+    );
+
+    assert_eq!(records[2]["status"], "InvalidArgument");
+    assert_eq!(records[2]["responses"], json!({ "count": 0, "sample": [] }));
+}
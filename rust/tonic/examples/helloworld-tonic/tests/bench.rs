@@ -0,0 +1,99 @@
+use helloworld_tonic::bench;
+use helloworld_tonic::options::Encoding;
+use helloworld_tonic::server::MyGreeter;
+
+use std::process::{Command, Output};
+use std::time::Duration;
+
+mod common;
+
+use common::start_server;
+
+/// Runs the `helloworld` binary to completion.
+async fn helloworld(args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_helloworld"));
+    command.args(args);
+    tokio::task::spawn_blocking(move || command.output().unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn bench_makes_every_call_and_reports_sorted_latencies() {
+    let (_, client) = start_server(MyGreeter::new(true), Encoding::Gzip).await;
+
+    let report = bench::run(client.clone(), "Ana", 50, 8).await.unwrap();
+    assert_eq!(report.latencies.len(), 50);
+    assert!(report.latencies.is_sorted());
+    assert_eq!(report.percentile(0.0), report.latencies[0]);
+    assert_eq!(report.percentile(100.0), report.latencies[49]);
+    assert!(report.elapsed >= report.percentile(100.0));
+    assert!(report.to_string().starts_with("50 calls in "));
+
+    // More workers than calls, and no calls at all.
+    let report = bench::run(client.clone(), "Ana", 3, 16).await.unwrap();
+    assert_eq!(report.latencies.len(), 3);
+    let report = bench::run(client, "Ana", 0, 16).await.unwrap();
+    assert!(report.latencies.is_empty());
+    assert_eq!(report.percentile(99.0), Duration::ZERO);
+}
+
+#[tokio::test]
+async fn cli_benches_and_calls_with_shared_options() {
+    let (addr, _) = start_server(MyGreeter::new(true), Encoding::Gzip).await;
+    let addr = addr.to_string();
+
+    // The shared options go before or after the subcommand.
+    let output = helloworld(&[
+        "--addr",
+        &addr,
+        "bench",
+        "--calls",
+        "20",
+        "--concurrency",
+        "4",
+        "--compression",
+        "gzip",
+    ])
+    .await;
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("20 calls in "), "{}", stdout);
+    assert!(stdout.contains("latency p50="), "{}", stdout);
+
+    let output = helloworld(&[
+        "--compression",
+        "gzip",
+        "call",
+        "--rpc",
+        "group",
+        "--addr",
+        &addr,
+        "--name",
+        "Ana",
+        "--name",
+        "Ben",
+        "--locale",
+        "es",
+    ])
+    .await;
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"Hola Ana, Ben\""), "{}", stdout);
+}
+
+#[tokio::test]
+async fn cli_rejects_bad_arguments() {
+    for (args, mentions) in [
+        (&["bench", "--calls", "many"][..], "--calls"),
+        (&["call", "--rpc", "broadcast"], "--rpc"),
+        (&["--compression", "brotli", "call"], "--compression"),
+        (&["--addr", "localhost", "serve"], "--addr"),
+        (&["greet"], "greet"),
+    ] {
+        let output = helloworld(args).await;
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(mentions), "{:?}: {}", args, stderr);
+    }
+}
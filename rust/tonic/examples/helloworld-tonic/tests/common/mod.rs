@@ -0,0 +1,32 @@
+#![allow(dead_code)]
+
+use helloworld_tonic::client;
+use helloworld_tonic::echo::EchoLayer;
+use helloworld_tonic::hello_world::greeter_client::GreeterClient;
+use helloworld_tonic::options::{ConnectOptions, Encoding};
+use helloworld_tonic::server::{self, MyGreeter};
+
+use std::net::SocketAddr;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Server};
+
+/// Serves a greeter the way `serve` does, echoing request ids, and connects to it. Both sides
+/// send messages with `compression`.
+pub async fn start_server(
+    greeter: MyGreeter,
+    compression: Encoding,
+) -> (SocketAddr, GreeterClient<Channel>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .layer(EchoLayer)
+            .add_service(server::service(greeter, compression))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    let options = ConnectOptions {
+        addr,
+        connect_timeout: 5,
+    };
+    (addr, client::connect(&options, compression).await.unwrap())
+}
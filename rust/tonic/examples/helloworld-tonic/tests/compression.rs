@@ -0,0 +1,167 @@
+use helloworld_tonic::client::{self, hello_request};
+use helloworld_tonic::echo::EchoLayer;
+use helloworld_tonic::hello_world::greeter_client::GreeterClient;
+use helloworld_tonic::options::{ConnectOptions, Encoding};
+use helloworld_tonic::server::{self, MyGreeter};
+
+use http::HeaderMap;
+use std::net::SocketAddr;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::body::Body as TonicBody;
+use tonic::transport::{Channel, Server};
+use tonic::{Code, Status};
+use tower::util::MapRequestLayer;
+
+use Encoding::{Deflate, Gzip, Identity, Zstd};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Ok,
+    Unimplemented,
+}
+
+/// What a client configured with the second encoding sees calling a server configured with
+/// the first. A server always accepts identity, and only compresses its responses with an
+/// encoding the client accepts.
+const MATRIX: &[(Encoding, Encoding, Outcome)] = &[
+    (Identity, Identity, Outcome::Ok),
+    (Identity, Gzip, Outcome::Unimplemented),
+    (Identity, Deflate, Outcome::Unimplemented),
+    (Identity, Zstd, Outcome::Unimplemented),
+    (Gzip, Identity, Outcome::Ok),
+    (Gzip, Gzip, Outcome::Ok),
+    (Gzip, Deflate, Outcome::Unimplemented),
+    (Gzip, Zstd, Outcome::Unimplemented),
+    (Deflate, Identity, Outcome::Ok),
+    (Deflate, Gzip, Outcome::Unimplemented),
+    (Deflate, Deflate, Outcome::Ok),
+    (Deflate, Zstd, Outcome::Unimplemented),
+    (Zstd, Identity, Outcome::Ok),
+    (Zstd, Gzip, Outcome::Unimplemented),
+    (Zstd, Deflate, Outcome::Unimplemented),
+    (Zstd, Zstd, Outcome::Ok),
+];
+
+/// Starts a server, with `rewrite` standing in for an intermediary that changes the request
+/// headers on their way to it.
+async fn start_server(compression: Encoding, rewrite: fn(&mut HeaderMap)) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .layer(MapRequestLayer::new(
+                move |mut req: http::Request<TonicBody>| {
+                    rewrite(req.headers_mut());
+                    req
+                },
+            ))
+            .layer(EchoLayer)
+            .add_service(server::service(MyGreeter::new(true), compression))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    addr
+}
+
+async fn connect(addr: SocketAddr, compression: Encoding) -> GreeterClient<Channel> {
+    let options = ConnectOptions {
+        addr,
+        connect_timeout: 5,
+    };
+    client::connect(&options, compression).await.unwrap()
+}
+
+/// Calls SayHello, returning the encoding of the response.
+async fn unary(client: &mut GreeterClient<Channel>) -> Result<String, Status> {
+    let resp = client.say_hello(hello_request("Tonic", "")).await?;
+    assert_eq!(resp.get_ref().message, "Hello Tonic");
+    let encoding = resp.metadata().get("grpc-encoding");
+    Ok(encoding.map_or("identity", |x| x.to_str().unwrap()).into())
+}
+
+async fn chat(client: &mut GreeterClient<Channel>) -> Result<(), Status> {
+    let requests = ["Alice", "Bob"].map(|name| hello_request(name, "fr"));
+    let mut stream = client
+        .say_hello_chat(tokio_stream::iter(requests))
+        .await?
+        .into_inner();
+    let mut messages = vec![];
+    while let Some(resp) = stream.message().await? {
+        messages.push(resp.message);
+    }
+    assert_eq!(messages, ["Bonjour Alice", "Bonjour Bob"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn every_client_and_server_encoding() {
+    for server in [Identity, Gzip, Deflate, Zstd] {
+        let addr = start_server(server, |_| {}).await;
+
+        for &(_, client, expected) in MATRIX.iter().filter(|(x, ..)| *x == server) {
+            let case = format!("{:?} server, {:?} client", server, client);
+            let mut greeter = connect(addr, client).await;
+            let unary = unary(&mut greeter).await;
+            let chat = chat(&mut greeter).await;
+
+            match expected {
+                Outcome::Ok => {
+                    // The response is only compressed when both sides use the same encoding.
+                    let encoding = if client == server { server } else { Identity };
+                    assert_eq!(unary.expect(&case), encoding.name(), "{}", case);
+                    chat.expect(&case);
+                }
+                Outcome::Unimplemented => {
+                    let message = format!(
+                        "Content is compressed with `{}` which isn't supported",
+                        client.name()
+                    );
+                    // Clients can retry with an encoding the server lists.
+                    let accepted = match server.tonic() {
+                        Some(_) => format!("{},identity", server.name()),
+                        None => "identity".into(),
+                    };
+                    for status in [unary.unwrap_err(), chat.unwrap_err()] {
+                        assert_eq!(status.code(), Code::Unimplemented, "{}", case);
+                        assert_eq!(status.message(), message, "{}", case);
+                        let accept_encoding = status.metadata().get("grpc-accept-encoding");
+                        assert_eq!(accept_encoding.unwrap(), accepted.as_str(), "{}", case);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn dropped_encoding_header_is_internal() {
+    let addr = start_server(Gzip, |headers| {
+        headers.remove("grpc-encoding");
+    })
+    .await;
+    let mut greeter = connect(addr, Gzip).await;
+
+    let status = unary(&mut greeter).await.unwrap_err();
+    assert_eq!(status.code(), Code::Internal);
+    assert_eq!(
+        status.message(),
+        "protocol error: received message with compressed-flag but no grpc-encoding was specified"
+    );
+}
+
+#[tokio::test]
+async fn mislabelled_encoding_is_internal() {
+    let addr = start_server(Zstd, |headers| {
+        headers.insert("grpc-encoding", "zstd".parse().unwrap());
+    })
+    .await;
+    let mut greeter = connect(addr, Gzip).await;
+
+    let status = unary(&mut greeter).await.unwrap_err();
+    assert_eq!(status.code(), Code::Internal);
+    assert!(
+        status.message().starts_with("Error decompressing: ")
+            && status.message().ends_with(", while sending request"),
+        "{}",
+        status.message()
+    );
+}
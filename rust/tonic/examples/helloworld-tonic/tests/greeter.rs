@@ -0,0 +1,180 @@
+use helloworld_tonic::client::{hello_request, with_request_id};
+use helloworld_tonic::hello_world::HelloResponse;
+use helloworld_tonic::options::Encoding;
+use helloworld_tonic::server::MyGreeter;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Code, Streaming};
+use wire_tonic::shutdown::Drain;
+
+mod common;
+
+use common::start_server;
+
+fn response(message: &str, locale: &str) -> HelloResponse {
+    HelloResponse {
+        message: message.into(),
+        locale: locale.into(),
+    }
+}
+
+async fn collect(stream: &mut Streaming<HelloResponse>) -> Vec<HelloResponse> {
+    let mut responses = vec![];
+    while let Some(response) = stream.message().await.unwrap() {
+        responses.push(response);
+    }
+    responses
+}
+
+#[tokio::test]
+async fn say_hello_picks_the_language_of_the_locale() {
+    let (_, mut client) = start_server(MyGreeter::new(true), Encoding::Identity).await;
+
+    for (locale, expected) in [
+        ("", response("Hello Ana", "en")),
+        ("fr", response("Bonjour Ana", "fr")),
+        ("fr-CA", response("Bonjour Ana", "fr")),
+        ("PT_br", response("Olá Ana", "pt")),
+    ] {
+        let got = client
+            .say_hello(hello_request("Ana", locale))
+            .await
+            .unwrap();
+        assert_eq!(got.into_inner(), expected, "locale {:?}", locale);
+    }
+
+    let err = client
+        .say_hello(hello_request("Ana", "xx-YY"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(err.message(), "unsupported locale \"xx-YY\"");
+}
+
+#[tokio::test]
+async fn say_hello_echoes_the_request_id() {
+    let (_, mut client) = start_server(MyGreeter::new(true), Encoding::Identity).await;
+
+    let request = with_request_id(hello_request("Ana", ""), "unary-1").unwrap();
+    let response = client.say_hello(request).await.unwrap();
+    // Tonic merges the trailers of a unary response into its metadata.
+    assert_eq!(response.metadata().get("x-request-id").unwrap(), "unary-1");
+
+    let request = with_request_id(hello_request("Ana", "xx"), "unary-2").unwrap();
+    let err = client.say_hello(request).await.unwrap_err();
+    assert_eq!(err.metadata().get("x-request-id").unwrap(), "unary-2");
+}
+
+#[tokio::test]
+async fn say_hello_stream_greets_in_every_language() {
+    let (_, mut client) = start_server(MyGreeter::new(true), Encoding::Identity).await;
+
+    let request = with_request_id(hello_request("Ana", "ja"), "stream-1").unwrap();
+    let mut stream = client.say_hello_stream(request).await.unwrap().into_inner();
+    let responses = collect(&mut stream).await;
+
+    let locales: Vec<_> = responses.iter().map(|x| x.locale.as_str()).collect();
+    assert_eq!(locales, ["en", "de", "es", "fr", "it", "ja", "pt"]);
+    assert_eq!(responses[3], response("Bonjour Ana", "fr"));
+
+    let trailers = stream.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers.get("x-request-id").unwrap(), "stream-1");
+}
+
+#[tokio::test]
+async fn say_hello_group_greets_everyone_in_the_first_locale() {
+    let (_, mut client) = start_server(MyGreeter::new(true), Encoding::Identity).await;
+
+    let requests = vec![
+        hello_request("Ana", "es"),
+        hello_request("Ben", "de"),
+        hello_request("Cy", ""),
+    ];
+    let request = with_request_id(tokio_stream::iter(requests), "group-1").unwrap();
+    let got = client.say_hello_group(request).await.unwrap();
+    assert_eq!(got.metadata().get("x-request-id").unwrap(), "group-1");
+    assert_eq!(got.into_inner(), response("Hola Ana, Ben, Cy", "es"));
+
+    let err = client
+        .say_hello_group(tokio_stream::iter(vec![]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(err.message(), "no names were sent");
+
+    let requests = vec![hello_request("Ana", "xx")];
+    let err = client
+        .say_hello_group(tokio_stream::iter(requests))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn say_hello_chat_answers_each_request_in_its_locale() {
+    let (_, mut client) = start_server(MyGreeter::new(true), Encoding::Identity).await;
+
+    let requests = vec![hello_request("Ana", "it"), hello_request("Ben", "")];
+    let request = with_request_id(tokio_stream::iter(requests), "chat-1").unwrap();
+    let mut stream = client.say_hello_chat(request).await.unwrap().into_inner();
+    assert_eq!(
+        collect(&mut stream).await,
+        [response("Ciao Ana", "it"), response("Hello Ben", "en")]
+    );
+    let trailers = stream.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers.get("x-request-id").unwrap(), "chat-1");
+
+    // A bad locale ends the chat, after the responses before it.
+    let requests = vec![hello_request("Ana", ""), hello_request("Ben", "xx")];
+    let request = with_request_id(tokio_stream::iter(requests), "chat-2").unwrap();
+    let mut stream = client.say_hello_chat(request).await.unwrap().into_inner();
+    assert_eq!(
+        stream.message().await.unwrap(),
+        Some(response("Hello Ana", "en"))
+    );
+    let err = stream.message().await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(err.metadata().get("x-request-id").unwrap(), "chat-2");
+}
+
+#[tokio::test]
+async fn draining_ends_open_streams_with_a_status() {
+    let drain = Drain::default();
+    let greeter = MyGreeter::new(true).with_drain(drain.clone());
+    let (_, mut client) = start_server(greeter, Encoding::Identity).await;
+
+    let (chat_tx, rx) = mpsc::channel(4);
+    let mut chat = client
+        .say_hello_chat(ReceiverStream::new(rx))
+        .await
+        .unwrap()
+        .into_inner();
+    chat_tx.send(hello_request("Ana", "")).await.unwrap();
+    assert!(chat.message().await.unwrap().is_some());
+
+    let (group_tx, rx) = mpsc::channel(4);
+    let group = tokio::spawn({
+        let mut client = client.clone();
+        async move { client.say_hello_group(ReceiverStream::new(rx)).await }
+    });
+    group_tx.send(hello_request("Ana", "")).await.unwrap();
+
+    drain.start();
+
+    let err = chat.message().await.unwrap_err();
+    assert_eq!(err.code(), Code::Unavailable);
+    assert_eq!(err.message(), "server is shutting down");
+
+    let err = group.await.unwrap().unwrap_err();
+    assert_eq!(err.code(), Code::Unavailable);
+
+    // A stream started while draining ends before its first message.
+    let mut stream = client
+        .say_hello_stream(hello_request("Ana", ""))
+        .await
+        .unwrap()
+        .into_inner();
+    let err = stream.message().await.unwrap_err();
+    assert_eq!(err.code(), Code::Unavailable);
+}
@@ -0,0 +1,84 @@
+use helloworld_tonic::client::{self, hello_request};
+use helloworld_tonic::hello_world::greeter_client::GreeterClient;
+use helloworld_tonic::options::{ConnectOptions, Encoding};
+
+use std::net::TcpListener;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
+use tonic::Code;
+
+/// Kills the server process when the test is done with it, even if the test panics.
+struct ServerProcess(Child);
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Starts `helloworld serve` on a free port and connects to it.
+async fn spawn_server(args: &[&str]) -> (ServerProcess, GreeterClient<Channel>) {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_helloworld"))
+        .args(["--addr", &addr.to_string(), "serve", "--quiet"])
+        .args(args)
+        .spawn()
+        .unwrap();
+    let server = ServerProcess(child);
+
+    let options = ConnectOptions {
+        addr,
+        connect_timeout: 5,
+    };
+    for _ in 0..100 {
+        if let Ok(client) = client::connect(&options, Encoding::Zstd).await {
+            return (server, client);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("server at {addr} did not come up");
+}
+
+fn terminate(pid: u32) {
+    assert_eq!(unsafe { libc::kill(pid as i32, libc::SIGTERM) }, 0);
+}
+
+async fn wait(mut server: ServerProcess) -> std::process::ExitStatus {
+    tokio::task::spawn_blocking(move || server.0.wait().unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn sigterm_ends_open_streams_with_a_status_and_exits() {
+    let (server, mut client) = spawn_server(&["--drain-timeout", "5"]).await;
+
+    let (tx, rx) = mpsc::channel(4);
+    let mut chat = client
+        .say_hello_chat(ReceiverStream::new(rx))
+        .await
+        .unwrap()
+        .into_inner();
+    tx.send(hello_request("Before", "")).await.unwrap();
+    assert_eq!(
+        chat.message().await.unwrap().unwrap().message,
+        "Hello Before"
+    );
+
+    let started = Instant::now();
+    terminate(server.0.id());
+
+    let err = chat.message().await.unwrap_err();
+    assert_eq!(err.code(), Code::Unavailable);
+    assert_eq!(err.message(), "server is shutting down");
+
+    assert!(wait(server).await.success());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,143 @@
+//! Compares the encodings on large `ListFeatures` streams: bytes on the wire, and CPU time
+//! spent by client and server together. Run with `cargo bench --bench compression`.
+
+use routeguide_tonic::compression::{CompressLayer, CompressionConfig, Encoding};
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::{Feature, Point, Rectangle};
+use routeguide_tonic::service::RouteGuideService;
+
+use http_body_util::BodyExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::body::Body;
+use tonic::transport::{Endpoint, Server};
+use tonic::Request;
+use tower::ServiceExt;
+
+const FEATURE_COUNT: i32 = 20_000;
+const RUNS: u32 = 5;
+
+fn features() -> Vec<Feature> {
+    (0..FEATURE_COUNT)
+        .map(|i| Feature {
+            name: format!("{} Main Street, Springfield, NJ 07081, USA", i),
+            location: Some(Point {
+                latitude: 400_000_000 + i * 100,
+                longitude: -740_000_000 - i * 100,
+            }),
+            ..Feature::default()
+        })
+        .collect()
+}
+
+fn cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let micros = |t: libc::timeval| t.tv_sec as u64 * 1_000_000 + t.tv_usec as u64;
+    Duration::from_micros(micros(usage.ru_utime) + micros(usage.ru_stime))
+}
+
+async fn bench(encoding: Encoding, min_size: usize) {
+    let config = CompressionConfig {
+        encodings: vec![encoding],
+        min_size,
+    };
+
+    let mut route_guide = RouteGuideServer::new(RouteGuideService::new(features()));
+    for encoding in config.accepted() {
+        route_guide = route_guide.accept_compressed(encoding);
+    }
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .layer(CompressLayer::server(config.clone()))
+            .add_service(route_guide)
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+
+    let wire_bytes = Arc::new(AtomicU64::new(0));
+    let channel = Endpoint::from_shared(format!("http://{addr}"))
+        .unwrap()
+        .connect()
+        .await
+        .unwrap();
+    let counted = {
+        let wire_bytes = wire_bytes.clone();
+        channel.map_response(move |resp: http::Response<Body>| {
+            let wire_bytes = wire_bytes.clone();
+            resp.map(|body| {
+                Body::new(body.map_frame(move |frame| {
+                    if let Some(data) = frame.data_ref() {
+                        wire_bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+                    }
+                    frame
+                }))
+            })
+        })
+    };
+    let mut client = RouteGuideClient::new(counted).max_decoding_message_size(usize::MAX);
+    for encoding in config.accepted() {
+        client = client.accept_compressed(encoding);
+    }
+
+    let rect = Rectangle {
+        lo: Some(Point {
+            latitude: -900_000_000,
+            longitude: -1_800_000_000,
+        }),
+        hi: Some(Point {
+            latitude: 900_000_000,
+            longitude: 1_800_000_000,
+        }),
+    };
+
+    let mut count = 0;
+    let (wall_start, cpu_start) = (Instant::now(), cpu_time());
+    for _ in 0..RUNS {
+        let mut stream = client
+            .list_features(Request::new(rect))
+            .await
+            .unwrap()
+            .into_inner();
+        while stream.message().await.unwrap().is_some() {
+            count += 1;
+        }
+    }
+    let (wall, cpu) = (wall_start.elapsed() / RUNS, (cpu_time() - cpu_start) / RUNS);
+    assert_eq!(count, FEATURE_COUNT as u32 * RUNS);
+
+    println!(
+        "{:>8} {:>8} {:>12} {:>10.1?} {:>10.1?}",
+        encoding.name(),
+        min_size,
+        wire_bytes.load(Ordering::Relaxed) / RUNS as u64,
+        wall,
+        cpu,
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    println!(
+        "{} features per stream, averaged over {} streams",
+        FEATURE_COUNT, RUNS
+    );
+    println!(
+        "{:>8} {:>8} {:>12} {:>10} {:>10}",
+        "encoding", "min size", "wire bytes", "wall", "cpu"
+    );
+    for min_size in [0, 1024] {
+        for encoding in [
+            Encoding::Identity,
+            Encoding::Gzip,
+            Encoding::Deflate,
+            Encoding::Zstd,
+        ] {
+            bench(encoding, min_size).await;
+        }
+    }
+}
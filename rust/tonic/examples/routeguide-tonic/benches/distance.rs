@@ -0,0 +1,59 @@
+//! Compares the distance models against the reference geodesics in `data/geodesics.txt`:
+//! their error, and the time each takes per distance. Run with `cargo bench --bench distance`.
+
+use routeguide_tonic::geo::DistanceModel;
+use routeguide_tonic::route_guide::Point;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+const RUNS: u32 = 20_000;
+
+fn geodesics() -> Vec<(Point, Point, f64)> {
+    include_str!("../data/geodesics.txt")
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| {
+            let v: Vec<f64> = line.split(' ').map(|x| x.parse().unwrap()).collect();
+            let point = |lat: f64, lng: f64| Point {
+                latitude: (lat * 1e7).round() as i32,
+                longitude: (lng * 1e7).round() as i32,
+            };
+            (point(v[0], v[1]), point(v[2], v[3]), v[4])
+        })
+        .collect()
+}
+
+fn main() {
+    let geodesics = geodesics();
+
+    println!(
+        "{:<10} {:>14} {:>14} {:>12}",
+        "model", "max error m", "max error %", "ns/distance"
+    );
+    for model in [DistanceModel::Haversine, DistanceModel::Karney] {
+        let mut max_error = 0f64;
+        let mut max_relative = 0f64;
+        for (p1, p2, meters) in &geodesics {
+            let error = (model.distance(p1, p2) - meters).abs();
+            max_error = max_error.max(error);
+            max_relative = max_relative.max(error / meters);
+        }
+
+        let started = Instant::now();
+        for _ in 0..RUNS {
+            for (p1, p2, _) in &geodesics {
+                black_box(model.distance(black_box(p1), black_box(p2)));
+            }
+        }
+        let per_distance = started.elapsed().as_nanos() / (RUNS as u128 * geodesics.len() as u128);
+
+        println!(
+            "{:<10} {:>14.6} {:>14.6} {:>12}",
+            format!("{:?}", model),
+            max_error,
+            max_relative * 100f64,
+            per_distance
+        );
+    }
+}
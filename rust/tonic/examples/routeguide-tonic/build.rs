@@ -1,4 +1,12 @@
+use std::path::PathBuf;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("proto/route_guide.proto")?;
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("route_guide_descriptor.bin"))
+        .compile_protos(&["proto/route_guide.proto"], &["proto"])?;
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("admin_descriptor.bin"))
+        .compile_protos(&["proto/admin.proto"], &["proto"])?;
     Ok(())
 }
@@ -0,0 +1,156 @@
+use crate::features::{Features, Reloader};
+use crate::geo;
+use crate::route_guide::admin::admin_server::Admin;
+use crate::route_guide::admin::{
+    CancelStreamRequest, CancelStreamResponse, ListStreamsRequest, ReloadDataRequest,
+    SetLogLevelRequest, SetLogLevelResponse, Stats, StatsRequest, Stream, StreamList,
+};
+use crate::streams::ActiveStreams;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use tonic::{Request, Response, Status};
+use tracing::info;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Changes the log filter of the subscriber set up by `init_logging`.
+pub type LogFilter = reload::Handle<EnvFilter, Registry>;
+
+/// Logs to stderr like `tracing_subscriber::fmt::init`, filtered by `RUST_LOG` or else at
+/// `info`, returning a handle to change the filter while running.
+pub fn init_logging() -> LogFilter {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let (filter, handle) = reload::Layer::new(filter);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+    handle
+}
+
+/// Lets operators look inside a running server and control it. Serve it on its own port,
+/// away from clients.
+#[derive(Debug)]
+pub struct AdminService {
+    features: Features,
+    streams: ActiveStreams,
+    reloader: Option<Reloader>,
+    log_filter: Option<LogFilter>,
+}
+
+impl AdminService {
+    pub fn new(features: Features, streams: ActiveStreams) -> Self {
+        Self {
+            features,
+            streams,
+            reloader: None,
+            log_filter: None,
+        }
+    }
+
+    /// Reloads the features with `reloader` when asked to.
+    pub fn with_reloader(self, reloader: Reloader) -> Self {
+        Self {
+            reloader: Some(reloader),
+            ..self
+        }
+    }
+
+    /// Changes the log level through `handle` when asked to.
+    pub fn with_log_filter(self, handle: LogFilter) -> Self {
+        Self {
+            log_filter: Some(handle),
+            ..self
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        let features = self.features.snapshot();
+        Stats {
+            feature_count: features.len() as u64,
+            bounds: geo::bounds(features.keys()),
+            loaded_at: unix_seconds(self.features.loaded_at()),
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs() as i64)
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn get_stats(&self, _: Request<StatsRequest>) -> Result<Response<Stats>, Status> {
+        Ok(Response::new(self.stats()))
+    }
+
+    async fn list_streams(
+        &self,
+        _: Request<ListStreamsRequest>,
+    ) -> Result<Response<StreamList>, Status> {
+        let streams = self
+            .streams
+            .list()
+            .into_iter()
+            .map(|x| Stream {
+                id: x.id,
+                duration: x.elapsed().as_secs_f64(),
+                method: x.method,
+                peer: x.peer.unwrap_or_default(),
+                started_at: unix_seconds(x.started_at),
+            })
+            .collect();
+        Ok(Response::new(StreamList { streams }))
+    }
+
+    async fn cancel_stream(
+        &self,
+        req: Request<CancelStreamRequest>,
+    ) -> Result<Response<CancelStreamResponse>, Status> {
+        let id = req.into_inner().id;
+        if !self.streams.cancel(id) {
+            return Err(Status::not_found(format!("no open stream {id}")));
+        }
+        info!("admin: cancelled stream {}", id);
+        Ok(Response::new(CancelStreamResponse {}))
+    }
+
+    async fn reload_data(&self, _: Request<ReloadDataRequest>) -> Result<Response<Stats>, Status> {
+        let Some(reloader) = &self.reloader else {
+            return Err(Status::unimplemented(
+                "this server has no database to reload",
+            ));
+        };
+        // The file is what is wrong, so the same request can succeed once it is fixed.
+        let count = reloader
+            .reload()
+            .map_err(|err| Status::failed_precondition(err.to_string()))?;
+        info!("admin: loaded {} features", count);
+        Ok(Response::new(self.stats()))
+    }
+
+    async fn set_log_level(
+        &self,
+        req: Request<SetLogLevelRequest>,
+    ) -> Result<Response<SetLogLevelResponse>, Status> {
+        let Some(handle) = &self.log_filter else {
+            return Err(Status::unimplemented("this server's log level is fixed"));
+        };
+        let filter = req.into_inner().filter;
+        let parsed = EnvFilter::try_new(&filter)
+            .map_err(|err| Status::invalid_argument(format!("bad filter {filter:?}: {err}")))?;
+        let previous = handle
+            .with_current(|x| x.to_string())
+            .map_err(|err| Status::internal(err.to_string()))?;
+        handle
+            .reload(parsed)
+            .map_err(|err| Status::internal(err.to_string()))?;
+        info!(
+            "admin: log filter changed from {:?} to {:?}",
+            previous, filter
+        );
+        Ok(Response::new(SetLogLevelResponse { previous }))
+    }
+}
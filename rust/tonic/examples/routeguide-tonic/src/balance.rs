@@ -0,0 +1,378 @@
+use crate::descriptors;
+use crate::retry::{unavailable, Buffered};
+
+use bytes::Bytes;
+use futures_core::future::BoxFuture;
+use http_body::{Body, Frame, SizeHint};
+use std::collections::HashSet;
+use std::error::Error;
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinSet;
+use tonic::body::Body as TonicBody;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Status;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
+use tower::{Service, ServiceExt};
+use tracing::{info, warn};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Policy {
+    /// Each call goes to the next server in turn.
+    #[default]
+    RoundRobin,
+
+    /// Each call goes to the server with the fewest calls open.
+    LeastLoaded,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct BalanceConfig {
+    /// Server to send calls to. Repeat to spread calls over several.
+    #[arg(long = "addr", default_value = "http://[::1]:10000")]
+    pub addrs: Vec<String>,
+
+    /// Read the servers from this file instead, one per line, and follow changes to it.
+    #[arg(long, conflicts_with = "addrs")]
+    pub endpoints_file: Option<PathBuf>,
+
+    #[arg(long = "balance", value_enum, default_value_t)]
+    pub policy: Policy,
+
+    /// Milliseconds between health checks of each server, and between looks at the
+    /// endpoints file.
+    #[arg(long, default_value = "1000")]
+    pub health_interval: NonZeroU64,
+}
+
+/// How one server is doing, as seen by a `Balancer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointStatus {
+    pub uri: String,
+
+    /// Whether calls are sent to it: it passed its last health check, and no call to it
+    /// has failed to connect since.
+    pub healthy: bool,
+
+    pub in_flight: usize,
+
+    /// Calls sent to it so far, including those that failed over to another server.
+    pub calls: u64,
+}
+
+#[derive(Debug)]
+struct Backend {
+    uri: String,
+    channel: Channel,
+    healthy: AtomicBool,
+    in_flight: AtomicUsize,
+    calls: AtomicU64,
+}
+
+impl Backend {
+    fn new(uri: String) -> Result<Arc<Self>, tonic::transport::Error> {
+        let channel = Endpoint::from_shared(uri.clone())?.connect_lazy();
+        Ok(Arc::new(Self {
+            uri,
+            channel,
+            healthy: AtomicBool::new(true),
+            in_flight: AtomicUsize::new(0),
+            calls: AtomicU64::new(0),
+        }))
+    }
+
+    fn set_healthy(&self, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            if healthy {
+                info!("{} is healthy again", self.uri);
+            } else {
+                warn!("ejecting {}", self.uri);
+            }
+        }
+    }
+
+    async fn check(&self, timeout: Duration) -> bool {
+        let mut client = HealthClient::new(self.channel.clone());
+        let check = client.check(HealthCheckRequest {
+            service: String::new(),
+        });
+        match tokio::time::timeout(timeout, check).await {
+            Ok(Ok(resp)) => resp.into_inner().status() == ServingStatus::Serving,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Pool {
+    backends: RwLock<Vec<Arc<Backend>>>,
+    policy: Policy,
+    next: AtomicUsize,
+
+    /// Paths of the methods taking a single request, which can be sent again elsewhere.
+    retryable: HashSet<String>,
+}
+
+impl Pool {
+    /// Picks a healthy backend that hasn't been tried yet.
+    fn pick(&self, tried: &[Arc<Backend>]) -> Option<Arc<Backend>> {
+        let backends = self.backends.read().unwrap();
+        let candidates: Vec<_> = backends
+            .iter()
+            .filter(|x| x.healthy.load(Ordering::Relaxed))
+            .filter(|x| !tried.iter().any(|y| Arc::ptr_eq(x, y)))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let picked = match self.policy {
+            Policy::RoundRobin => candidates[start % candidates.len()],
+            // Start from a different backend each time, so ties are spread too.
+            Policy::LeastLoaded => (0..candidates.len())
+                .map(|i| candidates[(start + i) % candidates.len()])
+                .min_by_key(|x| x.in_flight.load(Ordering::Relaxed))
+                .unwrap(),
+        };
+        Some(picked.clone())
+    }
+
+    /// Replaces the backends with those for `uris`, keeping the ones already known.
+    fn set_uris(&self, uris: &[String]) {
+        let mut backends = self.backends.write().unwrap();
+        let current: Vec<_> = backends.iter().map(|x| x.uri.as_str()).collect();
+        if current == uris {
+            return;
+        }
+
+        let mut updated = vec![];
+        for uri in uris {
+            if let Some(backend) = backends.iter().find(|x| &x.uri == uri) {
+                updated.push(backend.clone());
+                continue;
+            }
+            match Backend::new(uri.clone()) {
+                Ok(backend) => updated.push(backend),
+                Err(err) => warn!("skipping endpoint {}: {}", uri, err),
+            }
+        }
+        info!("balancing over {:?}", uris);
+        *backends = updated;
+    }
+
+    async fn check_health(&self, timeout: Duration) {
+        let backends = self.backends.read().unwrap().clone();
+        let mut checks = JoinSet::new();
+        for backend in backends {
+            checks.spawn(async move {
+                let healthy = backend.check(timeout).await;
+                backend.set_healthy(healthy);
+            });
+        }
+        while checks.join_next().await.is_some() {}
+    }
+}
+
+fn read_endpoints(path: &Path) -> std::io::Result<Vec<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Health checks the backends, and follows the endpoints file, for as long as the pool is
+/// in use.
+async fn maintain(pool: Weak<Pool>, file: Option<PathBuf>, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    let mut modified: Option<SystemTime> = None;
+    loop {
+        interval.tick().await;
+        let Some(pool) = pool.upgrade() else {
+            return;
+        };
+
+        if let Some(path) = &file {
+            let mtime = std::fs::metadata(path).and_then(|x| x.modified()).ok();
+            if mtime != modified {
+                modified = mtime;
+                match read_endpoints(path) {
+                    Ok(uris) => pool.set_uris(&uris),
+                    Err(err) => warn!("failed to read {}: {}", path.display(), err),
+                }
+            }
+        }
+
+        pool.check_health(period).await;
+    }
+}
+
+/// Spreads the calls of a client over several servers, as a transport for any generated
+/// client. Servers are health checked with `grpc.health.v1`, and ejected while they fail
+/// their checks or refuse connections.
+///
+/// Calls taking a single request are failed over to another server when theirs can't be
+/// reached or answers `Unavailable` straight away. Streaming calls go to one server, and
+/// fail with it.
+#[derive(Debug, Clone)]
+pub struct Balancer {
+    pool: Arc<Pool>,
+}
+
+impl Balancer {
+    /// Balances calls to the services in `descriptors`, an encoded file descriptor set, over
+    /// the servers in `config`. Must be called within a tokio runtime.
+    pub fn new(config: &BalanceConfig, descriptors: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let uris = match &config.endpoints_file {
+            Some(path) => read_endpoints(path)?,
+            None => config.addrs.clone(),
+        };
+        let backends = uris
+            .into_iter()
+            .map(Backend::new)
+            .collect::<Result<_, _>>()?;
+
+        let retryable = descriptors::methods(descriptors)?
+            .into_iter()
+            .filter(|x| !x.client_streaming)
+            .map(|x| x.path)
+            .collect();
+
+        let pool = Arc::new(Pool {
+            backends: RwLock::new(backends),
+            policy: config.policy,
+            next: AtomicUsize::new(0),
+            retryable,
+        });
+        tokio::spawn(maintain(
+            Arc::downgrade(&pool),
+            config.endpoints_file.clone(),
+            Duration::from_millis(config.health_interval.get()),
+        ));
+        Ok(Self { pool })
+    }
+
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        let backends = self.pool.backends.read().unwrap();
+        backends
+            .iter()
+            .map(|x| EndpointStatus {
+                uri: x.uri.clone(),
+                healthy: x.healthy.load(Ordering::Relaxed),
+                in_flight: x.in_flight.load(Ordering::Relaxed),
+                calls: x.calls.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// Sends `req` to `backend`, ejecting it if it can't be reached.
+async fn send(
+    backend: Arc<Backend>,
+    req: http::Request<TonicBody>,
+) -> Result<http::Response<TonicBody>, BoxError> {
+    backend.calls.fetch_add(1, Ordering::Relaxed);
+    backend.in_flight.fetch_add(1, Ordering::Relaxed);
+    let guard = InFlight(backend.clone());
+
+    let mut channel = backend.channel.clone();
+    let resp = match channel.ready().await {
+        Ok(channel) => channel.call(req).await,
+        Err(err) => Err(err),
+    };
+    match resp {
+        Ok(resp) => Ok(resp.map(|inner| {
+            TonicBody::new(TrackedBody {
+                inner,
+                _guard: guard,
+            })
+        })),
+        Err(err) => {
+            backend.set_healthy(false);
+            Err(err.into())
+        }
+    }
+}
+
+impl Service<http::Request<TonicBody>> for Balancer {
+    type Response = http::Response<TonicBody>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<TonicBody>) -> Self::Future {
+        let pool = self.pool.clone();
+        Box::pin(async move {
+            let no_servers = || Status::unavailable("no healthy servers").into_http();
+
+            if !pool.retryable.contains(req.uri().path()) {
+                return match pool.pick(&[]) {
+                    Some(backend) => send(backend, req).await,
+                    None => Ok(no_servers()),
+                };
+            }
+
+            // Keep the request, to send again if the first server fails.
+            let buffered = Buffered::new(req).await?;
+
+            let mut tried = vec![];
+            let mut last = None;
+            while let Some(backend) = pool.pick(&tried) {
+                tried.push(backend.clone());
+                match send(backend, buffered.request()).await {
+                    Ok(resp) if !unavailable(&resp) => return Ok(resp),
+                    other => last = Some(other),
+                }
+            }
+            last.unwrap_or_else(|| Ok(no_servers()))
+        })
+    }
+}
+
+/// Counts a call as open until its response is done with.
+struct InFlight(Arc<Backend>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+struct TrackedBody {
+    inner: TonicBody,
+    _guard: InFlight,
+}
+
+impl Body for TrackedBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
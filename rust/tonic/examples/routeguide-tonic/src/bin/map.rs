@@ -0,0 +1,62 @@
+use routeguide_tonic::data;
+use routeguide_tonic::map::{self, Map, Projection};
+use routeguide_tonic::recording;
+use routeguide_tonic::route_guide::Rectangle;
+
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Draws the features of a database, and optionally recorded routes, as an SVG map.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Feature database to draw instead of the one shipped with the crate.
+    #[arg(long)]
+    data: Option<PathBuf>,
+
+    /// Also draw the points sent by each RecordRoute call of a session recorded with
+    /// `routeguid-client --record`.
+    #[arg(long)]
+    session: Option<PathBuf>,
+
+    /// Only draw this area, written as LAT,LNG,LAT,LNG in degrees. Defaults to the area
+    /// holding everything drawn.
+    #[arg(long, value_parser = map::parse_bounds)]
+    bounds: Option<Rectangle>,
+
+    #[arg(long, value_enum, default_value_t)]
+    projection: Projection,
+
+    /// Length in pixels of the longer side of the map.
+    #[arg(long, default_value_t = 1024)]
+    size: u32,
+
+    /// Leave feature names to the tooltips.
+    #[arg(long)]
+    no_labels: bool,
+
+    /// Write the map here instead of to standard output.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let features = data::load_from(&args.data.unwrap_or_else(data::default_path))?;
+    let routes = match &args.session {
+        Some(path) => map::recorded_routes(&recording::load(path)?)?,
+        None => vec![],
+    };
+    let bounds = args.bounds.unwrap_or_else(|| map::fit(&features, &routes));
+
+    let svg = Map::new(bounds)
+        .with_projection(args.projection)
+        .with_size(args.size)
+        .with_labels(!args.no_labels)
+        .render(&features, &routes);
+    match &args.output {
+        Some(path) => std::fs::write(path, svg)?,
+        None => print!("{svg}"),
+    }
+    Ok(())
+}
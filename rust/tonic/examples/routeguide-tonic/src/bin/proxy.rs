@@ -0,0 +1,74 @@
+use routeguide_tonic::geo::DistanceModel;
+use routeguide_tonic::proxy::RouteGuideProxy;
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::FILE_DESCRIPTOR_SET;
+use routeguide_tonic::shutdown;
+
+use clap::Parser;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tonic::transport::{Endpoint, Server};
+use tracing::{info, warn};
+
+#[derive(Debug, Parser)]
+struct Args {
+    #[arg(long, default_value = "[::1]:10000")]
+    addr: SocketAddr,
+
+    /// Shard endpoints in shard order, e.g. `--shard http://[::1]:10001 --shard ...`.
+    #[arg(long = "shard", required = true)]
+    shards: Vec<String>,
+
+    /// How to measure the length of recorded routes.
+    #[arg(long, value_enum, default_value_t)]
+    distance: DistanceModel,
+
+    /// Seconds to wait for open calls to finish after SIGINT or SIGTERM.
+    #[arg(long, default_value_t = 10)]
+    drain_timeout: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let shards = args
+        .shards
+        .into_iter()
+        .map(|uri| Ok(Endpoint::from_shared(uri)?.connect_lazy()))
+        .collect::<Result<Vec<_>, tonic::transport::Error>>()?;
+
+    info!("proxying {} shards on {}", shards.len(), args.addr);
+
+    let proxy = RouteGuideProxy::new(shards).with_distance(args.distance);
+    let drain = proxy.drain();
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .build_v1()?;
+
+    // Once signalled, the proxy stops accepting connections and waits for the open calls,
+    // which the drain asks to finish.
+    let serve = Server::builder()
+        .add_service(RouteGuideServer::new(proxy))
+        .add_service(reflection)
+        .serve_with_shutdown(args.addr, {
+            let drain = drain.clone();
+            async move {
+                shutdown::signal().await;
+                info!("shutting down, draining open calls");
+                drain.start();
+            }
+        });
+
+    tokio::select! {
+        res = serve => res?,
+        _ = async {
+            drain.started().await;
+            tokio::time::sleep(Duration::from_secs(args.drain_timeout)).await;
+        } => warn!("calls still open after {}s, exiting anyway", args.drain_timeout),
+    }
+
+    info!("stopped");
+    Ok(())
+}
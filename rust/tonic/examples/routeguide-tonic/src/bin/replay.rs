@@ -0,0 +1,56 @@
+use routeguide_tonic::replay::Replay;
+use routeguide_tonic::shutdown::{self, Drain};
+
+use clap::Parser;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use tonic::service::Routes;
+use tonic::transport::Server;
+use tracing::{info, warn};
+
+#[derive(Debug, Parser)]
+struct Args {
+    #[arg(long, default_value = "[::1]:10000")]
+    addr: SocketAddr,
+
+    /// Session recorded with `routeguid-client --record`.
+    session: PathBuf,
+
+    /// Seconds to wait for open calls to finish after SIGINT or SIGTERM.
+    #[arg(long, default_value_t = 10)]
+    drain_timeout: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let replay = Replay::load(&args.session)?;
+    info!("replaying {} on {}", args.session.display(), args.addr);
+
+    // Replayed calls end by themselves, so once signalled this only waits for them.
+    let drain = Drain::default();
+    let serve = Server::builder()
+        .add_routes(Routes::from(axum::Router::new().fallback_service(replay)))
+        .serve_with_shutdown(args.addr, {
+            let drain = drain.clone();
+            async move {
+                shutdown::signal().await;
+                info!("shutting down, waiting for open calls");
+                drain.start();
+            }
+        });
+
+    tokio::select! {
+        res = serve => res?,
+        _ = async {
+            drain.started().await;
+            tokio::time::sleep(Duration::from_secs(args.drain_timeout)).await;
+        } => warn!("calls still open after {}s, exiting anyway", args.drain_timeout),
+    }
+
+    info!("stopped");
+    Ok(())
+}
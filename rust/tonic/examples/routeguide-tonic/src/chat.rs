@@ -0,0 +1,231 @@
+use crate::route_guide::{Point, RouteNote};
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+/// What each RouteChat stream remembers of the notes sent on it, and what it sends back.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ChatConfig {
+    /// Notes to remember at each location of a chat. The oldest are forgotten first.
+    #[arg(long = "chat-notes-per-location", default_value = "100")]
+    pub per_location: NonZeroUsize,
+
+    /// Notes to remember over all the locations of a chat.
+    #[arg(long = "chat-notes-per-stream", default_value = "10000")]
+    pub per_stream: NonZeroUsize,
+
+    /// Seconds to remember a note for. Without it, notes are only forgotten to make room.
+    #[arg(long = "chat-note-ttl")]
+    pub ttl: Option<u64>,
+
+    /// Answer each note with itself alone, instead of every note remembered at its location.
+    #[arg(long = "chat-only-new")]
+    pub only_new: bool,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            per_location: NonZeroUsize::new(100).unwrap(),
+            per_stream: NonZeroUsize::new(10_000).unwrap(),
+            ttl: None,
+            only_new: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    seq: u64,
+    note: RouteNote,
+}
+
+/// The notes remembered by one chat. A note repeating one already remembered, at the same
+/// location with the same message, replaces it as the newest rather than being kept twice.
+#[derive(Debug)]
+pub struct ChatHistory {
+    config: ChatConfig,
+    by_location: HashMap<Point, VecDeque<Entry>>,
+
+    /// Every remembered note by sequence number, so oldest first, with when it was added.
+    order: BTreeMap<u64, (Point, Instant)>,
+
+    next_seq: u64,
+}
+
+impl ChatHistory {
+    pub fn new(config: ChatConfig) -> Self {
+        Self {
+            config,
+            by_location: HashMap::new(),
+            order: BTreeMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Remembers `note`, returning the notes to send back for it.
+    pub fn add(&mut self, note: RouteNote) -> Vec<RouteNote> {
+        self.add_at(note, Instant::now())
+    }
+
+    fn add_at(&mut self, note: RouteNote, now: Instant) -> Vec<RouteNote> {
+        self.expire(now);
+
+        let location = note.location.unwrap_or_default();
+        let entries = self.by_location.entry(location).or_default();
+        let repeated = match entries.iter().position(|x| x.note == note) {
+            Some(index) => {
+                let entry = entries.remove(index).unwrap();
+                self.order.remove(&entry.seq);
+                true
+            }
+            None => false,
+        };
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let reply = if self.config.only_new && !repeated {
+            vec![note.clone()]
+        } else {
+            vec![]
+        };
+        entries.push_back(Entry { seq, note });
+        self.order.insert(seq, (location, now));
+
+        if entries.len() > self.config.per_location.get() {
+            let oldest = entries.pop_front().unwrap();
+            self.order.remove(&oldest.seq);
+        }
+        while self.order.len() > self.config.per_stream.get() {
+            self.forget_oldest();
+        }
+
+        if self.config.only_new {
+            return reply;
+        }
+        self.by_location[&location]
+            .iter()
+            .map(|x| x.note.clone())
+            .collect()
+    }
+
+    /// How many notes are remembered.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let Some(ttl) = self.config.ttl.map(Duration::from_secs) else {
+            return;
+        };
+        while let Some((_, (_, added))) = self.order.first_key_value() {
+            if now.duration_since(*added) < ttl {
+                break;
+            }
+            self.forget_oldest();
+        }
+    }
+
+    fn forget_oldest(&mut self) {
+        let Some((_, (location, _))) = self.order.pop_first() else {
+            return;
+        };
+        // Each location holds its notes oldest first, so the oldest overall is at the front.
+        let entries = self.by_location.get_mut(&location).unwrap();
+        entries.pop_front();
+        if entries.is_empty() {
+            self.by_location.remove(&location);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(latitude: i32, message: &str) -> RouteNote {
+        RouteNote {
+            location: Some(Point {
+                latitude,
+                longitude: 0,
+            }),
+            message: message.into(),
+        }
+    }
+
+    fn messages(notes: &[RouteNote]) -> Vec<&str> {
+        notes.iter().map(|x| x.message.as_str()).collect()
+    }
+
+    fn config(per_location: usize, per_stream: usize) -> ChatConfig {
+        ChatConfig {
+            per_location: NonZeroUsize::new(per_location).unwrap(),
+            per_stream: NonZeroUsize::new(per_stream).unwrap(),
+            ..ChatConfig::default()
+        }
+    }
+
+    #[test]
+    fn caps_each_location_and_the_stream() {
+        let mut history = ChatHistory::new(config(2, 3));
+        assert_eq!(messages(&history.add(note(1, "a"))), ["a"]);
+        assert_eq!(messages(&history.add(note(1, "b"))), ["a", "b"]);
+        assert_eq!(messages(&history.add(note(1, "c"))), ["b", "c"]);
+
+        // Making room at another location forgets the oldest note anywhere.
+        assert_eq!(messages(&history.add(note(2, "x"))), ["x"]);
+        assert_eq!(messages(&history.add(note(2, "y"))), ["x", "y"]);
+        assert_eq!(history.len(), 3);
+        assert_eq!(messages(&history.add(note(1, "d"))), ["d"]);
+        assert_eq!(history.len(), 3);
+    }
+
+    #[test]
+    fn repeated_notes_move_to_the_end() {
+        let mut history = ChatHistory::new(config(10, 10));
+        history.add(note(1, "a"));
+        history.add(note(1, "b"));
+        assert_eq!(messages(&history.add(note(1, "a"))), ["b", "a"]);
+        assert_eq!(history.len(), 2);
+
+        // The same message elsewhere is a different note.
+        history.add(note(2, "a"));
+        assert_eq!(history.len(), 3);
+    }
+
+    #[test]
+    fn forgets_notes_after_their_ttl() {
+        let mut history = ChatHistory::new(ChatConfig {
+            ttl: Some(60),
+            ..ChatConfig::default()
+        });
+        let start = Instant::now();
+        history.add_at(note(1, "a"), start);
+        history.add_at(note(2, "b"), start + Duration::from_secs(30));
+        let notes = history.add_at(note(1, "c"), start + Duration::from_secs(60));
+        assert_eq!(messages(&notes), ["c"]);
+        assert_eq!(history.len(), 2);
+
+        // Repeating a note keeps it for another TTL.
+        history.add_at(note(2, "b"), start + Duration::from_secs(80));
+        let notes = history.add_at(note(2, "d"), start + Duration::from_secs(120));
+        assert_eq!(messages(&notes), ["b", "d"]);
+    }
+
+    #[test]
+    fn only_new_notes_are_sent() {
+        let mut history = ChatHistory::new(ChatConfig {
+            only_new: true,
+            ..ChatConfig::default()
+        });
+        assert_eq!(messages(&history.add(note(1, "a"))), ["a"]);
+        assert_eq!(messages(&history.add(note(1, "b"))), ["b"]);
+        assert!(history.add(note(1, "a")).is_empty());
+        assert_eq!(history.len(), 2);
+    }
+}
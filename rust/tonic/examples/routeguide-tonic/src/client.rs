@@ -1,21 +1,46 @@
-pub mod route_guide {
-    tonic::include_proto!("routeguide");
-}
-
 use time::Duration;
 
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 
-use route_guide::route_guide_client::RouteGuideClient;
-use route_guide::{Point, Rectangle, RouteNote};
+use routeguide_tonic::balance::{BalanceConfig, Balancer};
+use routeguide_tonic::compression::{Compress, CompressLayer, CompressionConfig};
+use routeguide_tonic::recording::{RecordLayer, Recorder};
+use routeguide_tonic::retry::{Retry, RetryConfig, RetryLayer};
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::{
+    NearestFeatureRequest, Point, Rectangle, RouteNote, RouteRequest, SimplifyRequest,
+    FILE_DESCRIPTOR_SET,
+};
+
+use clap::Parser;
 use std::error::Error;
+use std::path::PathBuf;
 use tokio::time;
-use tonic::transport::Channel;
 use tonic::Request;
+use tower::util::Either;
+use tower::Layer;
 use tracing::{error, info};
 
-async fn print_features(client: &mut RouteGuideClient<Channel>) -> Result<(), Box<dyn Error>> {
+#[derive(Debug, Parser)]
+struct Args {
+    #[command(flatten)]
+    balance: BalanceConfig,
+
+    /// Record the session to this file, for `routeguide-replay` to serve back.
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    #[command(flatten)]
+    compression: CompressionConfig,
+
+    #[command(flatten)]
+    retry: RetryConfig,
+}
+
+type Transport = Either<Recorder<Compress<Retry<Balancer>>>, Compress<Retry<Balancer>>>;
+
+async fn print_features(client: &mut RouteGuideClient<Transport>) -> Result<(), Box<dyn Error>> {
     let rectangle = Rectangle {
         lo: Some(Point {
             latitude: 400_000_000,
@@ -39,7 +64,7 @@ async fn print_features(client: &mut RouteGuideClient<Channel>) -> Result<(), Bo
     Ok(())
 }
 
-async fn run_record_route(client: &mut RouteGuideClient<Channel>) -> Result<(), Box<dyn Error>> {
+async fn run_record_route(client: &mut RouteGuideClient<Transport>) -> Result<(), Box<dyn Error>> {
     let req = {
         let (outbound, point_cnt) = {
             let mut rng = SmallRng::from_rng(rand::thread_rng())?;
@@ -56,15 +81,40 @@ async fn run_record_route(client: &mut RouteGuideClient<Channel>) -> Result<(),
         Request::new(outbound)
     };
 
-    match client.record_route(req).await {
-        Ok(resp) => info!("SUMMARY {:?}", resp.into_inner()),
-        Err(err) => error!("RecordRoute failed: {}", err),
-    }
+    let summary = match client.record_route(req).await {
+        Ok(resp) => resp.into_inner(),
+        Err(err) => {
+            error!("RecordRoute failed: {}", err);
+            return Ok(());
+        }
+    };
+    info!("SUMMARY {:?}", summary);
+
+    let route = client
+        .get_route(Request::new(RouteRequest {
+            id: summary.route_id,
+        }))
+        .await?
+        .into_inner();
+    info!("ROUTE {} has {} points", route.id, route.points.len());
+
+    let simplified = client
+        .get_simplified_route(Request::new(SimplifyRequest {
+            id: summary.route_id,
+            tolerance: 100_000f64,
+        }))
+        .await?
+        .into_inner();
+    info!(
+        "SIMPLIFIED ROUTE {} has {} points",
+        simplified.id,
+        simplified.points.len()
+    );
 
     Ok(())
 }
 
-async fn run_route_chat(client: &mut RouteGuideClient<Channel>) -> Result<(), Box<dyn Error>> {
+async fn run_route_chat(client: &mut RouteGuideClient<Transport>) -> Result<(), Box<dyn Error>> {
     let start = time::Instant::now();
 
     let mut inbound = {
@@ -102,8 +152,19 @@ async fn run_route_chat(client: &mut RouteGuideClient<Channel>) -> Result<(), Bo
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     tracing_subscriber::fmt::init();
-
-    let mut client = RouteGuideClient::connect("http://[::1]:10000").await?;
+    let args = Args::parse();
+
+    let balancer = Balancer::new(&args.balance, FILE_DESCRIPTOR_SET)?;
+    let retry = RetryLayer::new(args.retry.clone(), FILE_DESCRIPTOR_SET)?.layer(balancer);
+    let channel = CompressLayer::client(args.compression.clone()).layer(retry);
+    let transport = match &args.record {
+        Some(path) => Either::Left(RecordLayer::new(path)?.layer(channel)),
+        None => Either::Right(channel),
+    };
+    let mut client = RouteGuideClient::new(transport);
+    for encoding in args.compression.accepted() {
+        client = client.accept_compressed(encoding);
+    }
 
     info!("*** SIMPLE RPC ***");
     let resp = client
@@ -114,6 +175,18 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .await?;
     info!("RESPONSE = {:?}", resp);
 
+    // A GPS reading a few meters off still finds the feature.
+    let resp = client
+        .get_nearest_feature(Request::new(NearestFeatureRequest {
+            location: Some(Point {
+                latitude: 409_146_200,
+                longitude: -746_188_850,
+            }),
+            tolerance_meters: 25f64,
+        }))
+        .await?;
+    info!("NEAREST = {:?}", resp);
+
     info!("\n*** SERVER STREAMING ***");
     print_features(&mut client).await?;
 
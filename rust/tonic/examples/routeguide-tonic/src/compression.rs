@@ -0,0 +1,221 @@
+use bytes::{Bytes, BytesMut};
+use futures_core::future::BoxFuture;
+use http::HeaderValue;
+use http_body::{Body, Frame, SizeHint};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tonic::body::Body as TonicBody;
+use tonic::codec::CompressionEncoding;
+use tonic::Status;
+use tower::{Layer, Service};
+use wire_tonic::frame;
+
+pub use wire_tonic::encoding::Encoding;
+
+const GRPC_ENCODING: &str = "grpc-encoding";
+const GRPC_ACCEPT_ENCODING: &str = "grpc-accept-encoding";
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct CompressionConfig {
+    /// Encodings to use, most preferred first.
+    #[arg(
+        long = "compression",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "identity"
+    )]
+    pub encodings: Vec<Encoding>,
+
+    /// Messages smaller than this many bytes are sent uncompressed.
+    #[arg(long = "min-compress-size", default_value_t = 1024)]
+    pub min_size: usize,
+}
+
+impl CompressionConfig {
+    /// The encodings tonic should accept and decode.
+    pub fn accepted(&self) -> impl Iterator<Item = CompressionEncoding> + '_ {
+        self.encodings.iter().filter_map(|x| x.tonic())
+    }
+}
+
+/// Compresses the messages a service sends, leaving out those below `min_size`. Tonic can only
+/// compress all of a stream or none of it, so compression is done here instead and tonic is
+/// only told about the encodings it should accept.
+///
+/// On the server, responses use the first configured encoding the client accepts, where
+/// identity is always accepted. On the client, requests use the first configured encoding,
+/// which the server has to accept.
+#[derive(Debug, Clone)]
+pub struct CompressLayer {
+    config: Arc<CompressionConfig>,
+    side: Side,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Client,
+    Server,
+}
+
+impl CompressLayer {
+    pub fn server(config: CompressionConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            side: Side::Server,
+        }
+    }
+
+    pub fn client(config: CompressionConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            side: Side::Client,
+        }
+    }
+}
+
+impl<S> Layer<S> for CompressLayer {
+    type Service = Compress<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Compress {
+            inner,
+            config: self.config.clone(),
+            side: self.side,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Compress<S> {
+    inner: S,
+    config: Arc<CompressionConfig>,
+    side: Side,
+}
+
+impl<S> Compress<S> {
+    fn negotiate(&self, accept_encoding: Option<&HeaderValue>) -> Encoding {
+        let accepted: Vec<&str> = accept_encoding
+            .and_then(|x| x.to_str().ok())
+            .map(|x| x.split(',').map(str::trim).collect())
+            .unwrap_or_default();
+        self.config
+            .encodings
+            .iter()
+            .copied()
+            // Every client takes uncompressed messages, whether it says so or not.
+            .find(|x| *x == Encoding::Identity || accepted.contains(&x.name()))
+            .unwrap_or(Encoding::Identity)
+    }
+}
+
+impl<S> Service<http::Request<TonicBody>> for Compress<S>
+where
+    S: Service<http::Request<TonicBody>, Response = http::Response<TonicBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<TonicBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<TonicBody>) -> Self::Future {
+        let min_size = self.config.min_size;
+        match self.side {
+            Side::Client => {
+                let encoding = self.config.encodings.first().copied();
+                if let Some(encoding) = encoding.filter(|x| *x != Encoding::Identity) {
+                    req.headers_mut()
+                        .insert(GRPC_ENCODING, HeaderValue::from_static(encoding.name()));
+                    req = req.map(|inner| CompressBody::boxed(inner, encoding, min_size));
+                }
+                Box::pin(self.inner.call(req))
+            }
+            Side::Server => {
+                let encoding = self.negotiate(req.headers().get(GRPC_ACCEPT_ENCODING));
+                let fut = self.inner.call(req);
+                Box::pin(async move {
+                    let mut resp = fut.await?;
+                    // A response with the status in its headers has no messages to compress.
+                    if encoding == Encoding::Identity || resp.headers().contains_key("grpc-status")
+                    {
+                        return Ok(resp);
+                    }
+                    resp.headers_mut()
+                        .insert(GRPC_ENCODING, HeaderValue::from_static(encoding.name()));
+                    Ok(resp.map(|inner| CompressBody::boxed(inner, encoding, min_size)))
+                })
+            }
+        }
+    }
+}
+
+/// Rewrites the uncompressed messages in a body, compressing those of at least `min_size`.
+struct CompressBody {
+    inner: TonicBody,
+    parser: frame::Parser,
+    encoding: Encoding,
+    min_size: usize,
+}
+
+impl CompressBody {
+    fn boxed(inner: TonicBody, encoding: Encoding, min_size: usize) -> TonicBody {
+        TonicBody::new(Self {
+            inner,
+            parser: frame::Parser::default(),
+            encoding,
+            min_size,
+        })
+    }
+
+    fn rewrite(&mut self, data: &[u8]) -> std::io::Result<Bytes> {
+        let mut out = BytesMut::new();
+        for (compressed, payload) in self.parser.push(data) {
+            let (compressed, payload) = if compressed || payload.len() < self.min_size {
+                (compressed, payload)
+            } else {
+                (true, self.encoding.compress(&payload)?.into())
+            };
+            frame::put(&mut out, compressed, &payload);
+        }
+        Ok(out.freeze())
+    }
+}
+
+impl Body for CompressBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        loop {
+            let frame = match ready!(Pin::new(&mut self.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => frame,
+                other => return Poll::Ready(other),
+            };
+            let frame = match frame.into_data() {
+                Ok(data) => self
+                    .rewrite(&data)
+                    .map_err(|err| Status::internal(format!("compression failed: {err}")))?,
+                Err(frame) => return Poll::Ready(Some(Ok(frame))),
+            };
+            // Wait for the rest of a message split across frames.
+            if !frame.is_empty() {
+                return Poll::Ready(Some(Ok(Frame::data(frame))));
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
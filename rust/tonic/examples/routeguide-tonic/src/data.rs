@@ -1,5 +1,7 @@
 use serde::Deserialize;
+use std::collections::HashSet;
 use std::fs::File;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Deserialize)]
 struct Point {
@@ -11,14 +13,58 @@ struct Point {
 struct Feature {
     name: String,
     location: Point,
+
+    #[serde(default)]
+    tags: Vec<String>,
+
+    #[serde(default)]
+    description: String,
+
+    /// In meters above sea level.
+    #[serde(default)]
+    elevation: Option<f64>,
+
+    /// In seconds since the unix epoch.
+    #[serde(default)]
+    updated_at: i64,
+}
+
+/// The database shipped with the crate.
+pub fn default_path() -> PathBuf {
+    PathBuf::from_iter([
+        std::env!("CARGO_MANIFEST_DIR"),
+        "data",
+        "route_guide_db.json",
+    ])
 }
 
 pub fn load() -> Result<Vec<crate::route_guide::Feature>, Box<dyn std::error::Error>> {
+    load_from(&default_path())
+}
+
+/// Loads the features in `path`, rejecting locations that are out of range or repeated, and
+/// empty tags. Each feature is an object such as
+///
+/// ```json
+/// {
+///   "name": "Patriots Path, Mendham, NJ 07945, USA",
+///   "location": { "latitude": 407838351, "longitude": -746143763 },
+///   "tags": ["trail"],
+///   "description": "Runs along the Whippany River.",
+///   "elevation": 120.5,
+///   "updated_at": 1700000000
+/// }
+/// ```
+///
+/// where only `name` and `location` are required, as in databases written before the others.
+pub fn load_from(
+    path: &Path,
+) -> Result<Vec<crate::route_guide::Feature>, Box<dyn std::error::Error>> {
     let contents: Vec<Feature> = {
-        let data_dir = std::path::PathBuf::from_iter([std::env!("CARGO_MANIFEST_DIR"), "data"]);
-        let file = File::open(data_dir.join("route_guide_db.json"))?;
+        let file = File::open(path)?;
         serde_json::from_reader(&file)?
     };
+    validate(&contents)?;
 
     Ok(contents
         .into_iter()
@@ -28,6 +74,41 @@ pub fn load() -> Result<Vec<crate::route_guide::Feature>, Box<dyn std::error::Er
                 latitude: feature.location.latitude,
                 longitude: feature.location.longitude,
             }),
+            tags: feature.tags,
+            description: feature.description,
+            elevation: feature.elevation,
+            updated_at: feature.updated_at,
         })
         .collect())
 }
+
+fn validate(features: &[Feature]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for (i, feature) in features.iter().enumerate() {
+        let Point {
+            latitude,
+            longitude,
+        } = feature.location;
+        if !(-900_000_000..=900_000_000).contains(&latitude)
+            || !(-1_800_000_000..=1_800_000_000).contains(&longitude)
+        {
+            return Err(format!(
+                "feature {} ({:?}) is out of range at {}, {}",
+                i, feature.name, latitude, longitude
+            ));
+        }
+        if feature.tags.iter().any(|x| x.is_empty()) {
+            return Err(format!(
+                "feature {} ({:?}) has an empty tag",
+                i, feature.name
+            ));
+        }
+        if !seen.insert((latitude, longitude)) {
+            return Err(format!(
+                "feature {} ({:?}) repeats the location {}, {}",
+                i, feature.name, latitude, longitude
+            ));
+        }
+    }
+    Ok(())
+}
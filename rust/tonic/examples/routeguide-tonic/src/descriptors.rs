@@ -0,0 +1,31 @@
+use prost::Message;
+
+/// A method of a service in a file descriptor set.
+#[derive(Debug, Clone)]
+pub(crate) struct Method {
+    /// As `/package.Service/Method`, the path of its calls.
+    pub(crate) path: String,
+    pub(crate) client_streaming: bool,
+    pub(crate) server_streaming: bool,
+}
+
+/// Lists the methods of every service in `descriptors`, an encoded file descriptor set.
+pub(crate) fn methods(descriptors: &[u8]) -> Result<Vec<Method>, prost::DecodeError> {
+    let mut methods = vec![];
+    for file in prost_types::FileDescriptorSet::decode(descriptors)?.file {
+        for service in &file.service {
+            let name = match file.package() {
+                "" => service.name().to_string(),
+                package => format!("{}.{}", package, service.name()),
+            };
+            for method in &service.method {
+                methods.push(Method {
+                    path: format!("/{}/{}", name, method.name()),
+                    client_streaming: method.client_streaming(),
+                    server_streaming: method.server_streaming(),
+                });
+            }
+        }
+    }
+    Ok(methods)
+}
@@ -0,0 +1,287 @@
+use bytes::{Bytes, BytesMut};
+use futures_core::future::BoxFuture;
+use http_body::{Body, Frame, SizeHint};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Deserializer};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tonic::body::Body as TonicBody;
+use tonic::{Code, Status};
+use tower::{Layer, Service};
+use wire_tonic::frame;
+
+/// Faults to inject, read from JSON such as
+///
+/// ```json
+/// {
+///   "seed": 7,
+///   "faults": [
+///     { "method": "ListFeatures", "drop_rate": 0.5, "abort_after": 10 },
+///     { "method": "*", "latency_ms": 200, "error_rate": 0.1, "error_code": "unavailable" }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaultConfig {
+    /// Seeds the random choices, so a run can be repeated. Random when unset.
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    /// Each call gets the faults of the first entry matching its method.
+    #[serde(default)]
+    pub faults: Vec<Fault>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fault {
+    /// A method name such as `ListFeatures`, or `*` for every method.
+    pub method: String,
+
+    /// Delay before the call is handled.
+    #[serde(default)]
+    pub latency_ms: u64,
+
+    /// Chance of failing a call with `error_code` instead of handling it.
+    #[serde(default)]
+    pub error_rate: f64,
+
+    #[serde(default = "unavailable", deserialize_with = "deserialize_code")]
+    pub error_code: Code,
+
+    /// Chance of leaving out each response message.
+    #[serde(default)]
+    pub drop_rate: f64,
+
+    /// Reset the stream after sending this many response messages. Messages still in flight
+    /// when it resets can be lost.
+    #[serde(default)]
+    pub abort_after: Option<usize>,
+}
+
+impl Fault {
+    /// A fault for `method` that does nothing until its fields are set.
+    pub fn new(method: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            latency_ms: 0,
+            error_rate: 0.0,
+            error_code: Code::Unavailable,
+            drop_rate: 0.0,
+            abort_after: None,
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        self.method == "*" || path.rsplit('/').next() == Some(self.method.as_str())
+    }
+}
+
+fn unavailable() -> Code {
+    Code::Unavailable
+}
+
+/// Reads a status code by name, in any case, such as `unavailable` or `DeadlineExceeded`.
+fn deserialize_code<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Code, D::Error> {
+    let name = String::deserialize(deserializer)?.replace('_', "");
+    (0..=16)
+        .map(Code::from_i32)
+        .find(|code| format!("{:?}", code).eq_ignore_ascii_case(&name))
+        .ok_or_else(|| serde::de::Error::custom(format!("unknown status code {:?}", name)))
+}
+
+impl FaultConfig {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    faults: Vec<Fault>,
+    rng: Arc<Mutex<SmallRng>>,
+}
+
+impl State {
+    fn new(config: FaultConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_entropy(),
+        };
+        Self {
+            faults: config.faults,
+            rng: Arc::new(Mutex::new(rng)),
+        }
+    }
+}
+
+/// Injects latency, error statuses, dropped response messages and stream resets into the
+/// methods of a server, to check how clients cope. The faults can be changed while serving
+/// through a `FaultHandle`.
+#[derive(Debug, Clone)]
+pub struct FaultLayer {
+    state: Arc<RwLock<State>>,
+}
+
+/// Replaces the faults of a running `FaultLayer`.
+#[derive(Debug, Clone)]
+pub struct FaultHandle {
+    state: Arc<RwLock<State>>,
+}
+
+impl FaultLayer {
+    pub fn new(config: FaultConfig) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(State::new(config))),
+        }
+    }
+
+    pub fn handle(&self) -> FaultHandle {
+        FaultHandle {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl FaultHandle {
+    /// Applies to the calls that start from now on.
+    pub fn set(&self, config: FaultConfig) {
+        *self.state.write().unwrap() = State::new(config);
+    }
+}
+
+impl<S> Layer<S> for FaultLayer {
+    type Service = Faults<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Faults {
+            inner,
+            state: self.state.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Faults<S> {
+    inner: S,
+    state: Arc<RwLock<State>>,
+}
+
+impl<S> Service<http::Request<TonicBody>> for Faults<S>
+where
+    S: Service<http::Request<TonicBody>, Response = http::Response<TonicBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<TonicBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<TonicBody>) -> Self::Future {
+        let (fault, rng) = {
+            let state = self.state.read().unwrap();
+            let path = req.uri().path();
+            let fault = state.faults.iter().find(|x| x.matches(path)).cloned();
+            (fault, state.rng.clone())
+        };
+        let Some(fault) = fault else {
+            return Box::pin(self.inner.call(req));
+        };
+
+        let fail = rng
+            .lock()
+            .unwrap()
+            .gen_bool(fault.error_rate.clamp(0.0, 1.0));
+        // Only call the service after the delay, as a slow server would.
+        let fut = (!fail).then(|| self.inner.call(req));
+
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(fault.latency_ms)).await;
+            let Some(fut) = fut else {
+                let status = Status::new(fault.error_code, "injected fault");
+                return Ok(status.into_http());
+            };
+
+            let resp = fut.await?;
+            if fault.drop_rate <= 0.0 && fault.abort_after.is_none() {
+                return Ok(resp);
+            }
+            Ok(resp.map(|inner| {
+                TonicBody::new(FaultBody {
+                    inner,
+                    parser: frame::Parser::default(),
+                    drop_rate: fault.drop_rate.clamp(0.0, 1.0),
+                    abort_after: fault.abort_after,
+                    sent: 0,
+                    rng,
+                })
+            }))
+        })
+    }
+}
+
+/// Drops response messages at random, and resets the stream after `abort_after` of them.
+struct FaultBody {
+    inner: TonicBody,
+    parser: frame::Parser,
+    drop_rate: f64,
+    abort_after: Option<usize>,
+    sent: usize,
+    rng: Arc<Mutex<SmallRng>>,
+}
+
+impl Body for FaultBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        loop {
+            if self.abort_after.is_some_and(|n| self.sent >= n) {
+                return Poll::Ready(Some(Err(Status::aborted("injected stream reset"))));
+            }
+
+            let frame = match std::task::ready!(Pin::new(&mut self.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => frame,
+                other => return Poll::Ready(other),
+            };
+            let data = match frame.into_data() {
+                Ok(data) => data,
+                Err(frame) => return Poll::Ready(Some(Ok(frame))),
+            };
+
+            let mut out = BytesMut::new();
+            for (compressed, payload) in self.parser.push(&data) {
+                if self.abort_after.is_some_and(|n| self.sent >= n) {
+                    break;
+                }
+                if self.rng.lock().unwrap().gen_bool(self.drop_rate) {
+                    continue;
+                }
+                frame::put(&mut out, compressed, &payload);
+                self.sent += 1;
+            }
+            if !out.is_empty() {
+                return Poll::Ready(Some(Ok(Frame::data(out.freeze()))));
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
@@ -0,0 +1,163 @@
+use crate::data;
+use crate::route_guide::{Feature, Point};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+pub type FeatureMap = HashMap<Point, Feature>;
+
+/// The features a service answers from, which can be replaced while serving. Calls keep
+/// the snapshot they started with, so a replacement only affects the calls after it.
+#[derive(Debug, Clone, Default)]
+pub struct Features {
+    current: Arc<RwLock<Loaded>>,
+}
+
+#[derive(Debug)]
+struct Loaded {
+    features: Arc<FeatureMap>,
+    at: SystemTime,
+}
+
+impl Loaded {
+    fn new(features: Vec<Feature>) -> Self {
+        let features = features
+            .into_iter()
+            .map(|x| (x.location.unwrap(), x))
+            .collect();
+        Self {
+            features: Arc::new(features),
+            at: SystemTime::now(),
+        }
+    }
+}
+
+impl Default for Loaded {
+    fn default() -> Self {
+        Self::new(vec![])
+    }
+}
+
+impl Features {
+    pub fn new(features: Vec<Feature>) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Loaded::new(features))),
+        }
+    }
+
+    pub fn snapshot(&self) -> Arc<FeatureMap> {
+        self.current.read().unwrap().features.clone()
+    }
+
+    /// When the current features were created or last replaced.
+    pub fn loaded_at(&self) -> SystemTime {
+        self.current.read().unwrap().at
+    }
+
+    pub fn replace(&self, features: Vec<Feature>) {
+        *self.current.write().unwrap() = Loaded::new(features);
+    }
+}
+
+type Filter = Arc<dyn Fn(&Feature) -> bool + Send + Sync>;
+
+/// Reloads `Features` from a database file, when asked to, when the file changes, or on
+/// SIGHUP. A file that fails to load or validate is rejected, and the features already
+/// loaded stay live.
+#[derive(Clone)]
+pub struct Reloader {
+    path: PathBuf,
+    features: Features,
+    filter: Filter,
+
+    /// The modification time of the file last loaded, or tried.
+    modified: Arc<Mutex<Option<SystemTime>>>,
+}
+
+impl std::fmt::Debug for Reloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reloader")
+            .field("path", &self.path)
+            .field("features", &self.features)
+            .field("modified", &self.modified)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Reloader {
+    pub fn new(path: PathBuf, features: Features) -> Self {
+        Self {
+            path,
+            features,
+            filter: Arc::new(|_| true),
+            modified: Arc::default(),
+        }
+    }
+
+    /// Only keeps the features of each load that `filter` accepts.
+    pub fn filter(self, filter: impl Fn(&Feature) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            filter: Arc::new(filter),
+            ..self
+        }
+    }
+
+    /// Loads the file and swaps in its features, returning how many were kept.
+    pub fn reload(&self) -> Result<usize, Box<dyn Error>> {
+        *self.modified.lock().unwrap() = self.file_modified();
+        let mut features = data::load_from(&self.path)?;
+        features.retain(|x| (self.filter)(x));
+        let count = features.len();
+        self.features.replace(features);
+        Ok(count)
+    }
+
+    fn file_modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path)
+            .and_then(|x| x.modified())
+            .ok()
+    }
+
+    /// Reloads, logging the outcome.
+    fn reload_logged(&self, why: &str) {
+        match self.reload() {
+            Ok(count) => info!("{}: loaded {} features", why, count),
+            Err(err) => warn!(
+                "{}: rejected {}, keeping the current features: {}",
+                why,
+                self.path.display(),
+                err
+            ),
+        }
+    }
+
+    /// Reloads whenever the file's modification time changes, checking every `period`, or
+    /// the process gets SIGHUP. Runs until dropped.
+    pub async fn watch(self, period: Duration) {
+        let mut interval = tokio::time::interval(period);
+
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .expect("failed to install SIGHUP handler");
+
+        loop {
+            #[cfg(unix)]
+            let hangup = hangup.recv();
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                _ = interval.tick() => {
+                    if self.file_modified() != *self.modified.lock().unwrap() {
+                        self.reload_logged(&format!("{} changed", self.path.display()));
+                    }
+                }
+                _ = hangup => self.reload_logged("SIGHUP"),
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+pub mod route_guide {
+    tonic::include_proto!("routeguide");
+
+    /// Descriptors of `route_guide.proto`, for the reflection service and `Balancer`.
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("route_guide_descriptor");
+
+    pub mod admin {
+        tonic::include_proto!("routeguide.admin");
+
+        /// Descriptors of `admin.proto`, for the reflection service of the admin port.
+        pub const FILE_DESCRIPTOR_SET: &[u8] =
+            tonic::include_file_descriptor_set!("admin_descriptor");
+    }
+}
+
+pub mod admin;
+pub mod balance;
+pub mod chat;
+pub mod compression;
+pub mod data;
+pub mod fault;
+pub mod features;
+pub mod geo;
+pub mod map;
+pub mod proxy;
+pub mod recording;
+pub mod replay;
+pub mod retry;
+pub mod service;
+pub mod shard;
+pub mod store;
+pub mod streams;
+
+pub use wire_tonic::shutdown;
+
+mod descriptors;
+mod simplify;
@@ -0,0 +1,419 @@
+use crate::features::Features;
+use crate::geo::{self, in_range};
+use crate::recording::Call;
+use crate::route_guide::{Feature, Point, Rectangle};
+use crate::store::RouteStore;
+
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::f64::consts::FRAC_PI_4;
+use std::fmt::Write;
+use std::sync::Arc;
+
+const CORD_FACTOR: f64 = 1e7;
+
+/// Mercator stretches towards the poles without bound, so it stops at the latitude where
+/// the map becomes square, as web maps do.
+const MERCATOR_MAX_LATITUDE: f64 = 85.051_128_78;
+
+/// Maps narrower than this many radians, about 6 meters, are drawn at that width.
+const MIN_SPAN: f64 = 1e-6;
+
+/// Pixels left around the drawn area, so that points on its edge stay whole.
+const MARGIN: f64 = 16.0;
+
+const ROUTE_COLORS: [&str; 6] = [
+    "#d62728", "#1f77b4", "#2ca02c", "#9467bd", "#ff7f0e", "#17becf",
+];
+
+/// How a map lays the earth out flat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Projection {
+    /// Keeps shapes true, as web maps do, but stretches areas away from the equator.
+    #[default]
+    Mercator,
+
+    /// Longitude and latitude drawn evenly, scaled to be true at the middle of the map.
+    /// Close to Mercator over small areas, and cheaper to reason about.
+    Equirectangular,
+}
+
+impl Projection {
+    /// Returns `p` in radians east and north, before scaling. `center` is the latitude of the
+    /// middle of the map, in degrees.
+    fn project(self, p: &Point, center: f64) -> (f64, f64) {
+        let lat = p.latitude as f64 / CORD_FACTOR;
+        let lng = (p.longitude as f64 / CORD_FACTOR).to_radians();
+        match self {
+            Self::Mercator => {
+                let lat = lat
+                    .clamp(-MERCATOR_MAX_LATITUDE, MERCATOR_MAX_LATITUDE)
+                    .to_radians();
+                (lng, (FRAC_PI_4 + lat / 2.0).tan().ln())
+            }
+            Self::Equirectangular => (lng * center.to_radians().cos(), lat.to_radians()),
+        }
+    }
+}
+
+/// Draws features and routes as an SVG map of an area.
+#[derive(Debug, Clone)]
+pub struct Map {
+    bounds: Rectangle,
+    projection: Projection,
+    size: u32,
+    labels: bool,
+}
+
+impl Map {
+    pub fn new(bounds: Rectangle) -> Self {
+        Self {
+            bounds,
+            projection: Projection::default(),
+            size: 1024,
+            labels: true,
+        }
+    }
+
+    pub fn with_projection(self, projection: Projection) -> Self {
+        Self { projection, ..self }
+    }
+
+    /// Sets the length in pixels of the longer side of the drawn area.
+    pub fn with_size(self, size: u32) -> Self {
+        Self { size, ..self }
+    }
+
+    /// Writes the name of each feature next to it, as well as in its tooltip.
+    pub fn with_labels(self, labels: bool) -> Self {
+        Self { labels, ..self }
+    }
+
+    /// Renders the features inside the map's bounds, and the routes, clipped to the bounds.
+    /// Routes are told apart by color, in the order given.
+    pub fn render(&self, features: &[Feature], routes: &[Vec<Point>]) -> String {
+        let lo = self.bounds.lo.unwrap_or_default();
+        let hi = self.bounds.hi.unwrap_or_default();
+        let center = (lo.latitude as f64 + hi.latitude as f64) / 2.0 / CORD_FACTOR;
+        let (x0, y0) = self.projection.project(&lo, center);
+        let (x1, y1) = self.projection.project(&hi, center);
+        let (left, right) = (x0.min(x1), x0.max(x1));
+        let (bottom, top) = (y0.min(y1), y0.max(y1));
+
+        let scale = self.size as f64 / (right - left).max(top - bottom).max(MIN_SPAN);
+        let width = (right - left) * scale + 2.0 * MARGIN;
+        let height = (top - bottom) * scale + 2.0 * MARGIN;
+        let pixel = |p: &Point| {
+            let (x, y) = self.projection.project(p, center);
+            (MARGIN + (x - left) * scale, MARGIN + (top - y) * scale)
+        };
+
+        let mut svg = String::new();
+        // Writing to a String cannot fail.
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.0}" height="{height:.0}" viewBox="0 0 {width:.1} {height:.1}" font-family="sans-serif" font-size="11">"#
+        );
+        let _ = writeln!(
+            svg,
+            r##"<rect width="100%" height="100%" fill="#f7f6f2"/>"##
+        );
+        let _ = writeln!(
+            svg,
+            r##"<clipPath id="bounds"><rect x="{MARGIN}" y="{MARGIN}" width="{:.1}" height="{:.1}"/></clipPath>"##,
+            width - 2.0 * MARGIN,
+            height - 2.0 * MARGIN,
+        );
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{MARGIN}" y="{MARGIN}" width="{:.1}" height="{:.1}" fill="none" stroke="#bbb"/>"##,
+            width - 2.0 * MARGIN,
+            height - 2.0 * MARGIN,
+        );
+
+        let _ = writeln!(
+            svg,
+            r#"<g clip-path="url(#bounds)" fill="none" stroke-width="2">"#
+        );
+        for (index, route) in routes.iter().enumerate() {
+            let points: Vec<_> = route
+                .iter()
+                .map(|p| {
+                    let (x, y) = pixel(p);
+                    format!("{x:.1},{y:.1}")
+                })
+                .collect();
+            let _ = writeln!(
+                svg,
+                r#"<polyline stroke="{}" points="{}"><title>route {} ({} points)</title></polyline>"#,
+                ROUTE_COLORS[index % ROUTE_COLORS.len()],
+                points.join(" "),
+                index + 1,
+                route.len(),
+            );
+        }
+        let _ = writeln!(svg, "</g>");
+
+        let _ = writeln!(svg, r##"<g fill="#333">"##);
+        for feature in features {
+            let Some(location) = &feature.location else {
+                continue;
+            };
+            if !in_range(location, &self.bounds) {
+                continue;
+            }
+            let (x, y) = pixel(location);
+            let name = escape(&feature.name);
+            let title = if name.is_empty() {
+                format!(
+                    "{:.7}, {:.7}",
+                    location.latitude as f64 / CORD_FACTOR,
+                    location.longitude as f64 / CORD_FACTOR
+                )
+            } else {
+                name.clone()
+            };
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{x:.1}" cy="{y:.1}" r="3"><title>{title}</title></circle>"#
+            );
+            if self.labels && !name.is_empty() {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{:.1}" y="{:.1}">{name}</text>"#,
+                    x + 5.0,
+                    y + 4.0
+                );
+            }
+        }
+        let _ = writeln!(svg, "</g>");
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Parses an area written as `LAT,LNG,LAT,LNG` in degrees, giving two opposite corners.
+pub fn parse_bounds(s: &str) -> Result<Rectangle, String> {
+    let degrees = s
+        .split(',')
+        .map(|x| {
+            x.trim()
+                .parse::<f64>()
+                .map_err(|err| format!("bad coordinate {x:?}: {err}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let [lat1, lng1, lat2, lng2] = degrees[..] else {
+        return Err("expected LAT,LNG,LAT,LNG".into());
+    };
+    let point = |lat: f64, lng: f64| {
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lng) {
+            return Err(format!("{lat},{lng} is out of range"));
+        }
+        Ok(Point {
+            latitude: (lat * CORD_FACTOR).round() as i32,
+            longitude: (lng * CORD_FACTOR).round() as i32,
+        })
+    };
+    Ok(Rectangle {
+        lo: Some(point(lat1, lng1)?),
+        hi: Some(point(lat2, lng2)?),
+    })
+}
+
+/// The smallest area holding every feature and route point, or the whole earth when there
+/// is nothing to draw.
+pub fn fit(features: &[Feature], routes: &[Vec<Point>]) -> Rectangle {
+    let points = features
+        .iter()
+        .filter_map(|x| x.location.as_ref())
+        .chain(routes.iter().flatten());
+    geo::bounds(points).unwrap_or(Rectangle {
+        lo: Some(Point {
+            latitude: -900_000_000,
+            longitude: -1_800_000_000,
+        }),
+        hi: Some(Point {
+            latitude: 900_000_000,
+            longitude: 1_800_000_000,
+        }),
+    })
+}
+
+/// Returns the points the client sent in each `RecordRoute` call of a recorded session.
+pub fn recorded_routes(calls: &[Call]) -> Result<Vec<Vec<Point>>, Box<dyn std::error::Error>> {
+    calls
+        .iter()
+        .filter(|call| call.path == "/routeguide.RouteGuide/RecordRoute")
+        .map(|call| call.requests.iter().map(|x| x.decode::<Point>()).collect())
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct MapState {
+    features: Features,
+    routes: Arc<RouteStore>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MapQuery {
+    /// As `LAT,LNG,LAT,LNG` in degrees.
+    bbox: Option<String>,
+
+    /// Route IDs, separated by commas.
+    routes: Option<String>,
+
+    projection: Option<Projection>,
+
+    size: Option<u32>,
+
+    labels: Option<bool>,
+}
+
+/// Serves `GET /map.svg`, drawing the current features and any of the routes recorded on the
+/// server. The query takes `bbox=LAT,LNG,LAT,LNG` in degrees, `routes=ID,ID`,
+/// `projection=mercator|equirectangular`, `size` in pixels, and `labels=false`. Without a
+/// `bbox`, the map fits everything it draws.
+pub fn router(features: Features, routes: Arc<RouteStore>) -> Router {
+    Router::new()
+        .route("/map.svg", get(map_svg))
+        .with_state(MapState { features, routes })
+}
+
+async fn map_svg(State(state): State<MapState>, Query(query): Query<MapQuery>) -> Response {
+    let bad_request = |message: String| (StatusCode::BAD_REQUEST, message).into_response();
+
+    let mut routes = vec![];
+    for id in query.routes.iter().flat_map(|x| x.split(',')) {
+        let Ok(id) = id.trim().parse() else {
+            return bad_request(format!("bad route ID {id:?}"));
+        };
+        let Some(route) = state.routes.get(id) else {
+            return (StatusCode::NOT_FOUND, format!("no route {id}")).into_response();
+        };
+        routes.push(route.points);
+    }
+
+    let size = query.size.unwrap_or(1024);
+    if !(1..=8192).contains(&size) {
+        return bad_request(format!("size {size} is out of range"));
+    }
+
+    let features: Vec<Feature> = state.features.snapshot().values().cloned().collect();
+    let bounds = match &query.bbox {
+        Some(bbox) => match parse_bounds(bbox) {
+            Ok(bounds) => bounds,
+            Err(err) => return bad_request(err),
+        },
+        None => fit(&features, &routes),
+    };
+
+    let svg = Map::new(bounds)
+        .with_projection(query.projection.unwrap_or_default())
+        .with_size(size)
+        .with_labels(query.labels.unwrap_or(true))
+        .render(&features, &routes);
+    ([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(latitude: i32, longitude: i32) -> Point {
+        Point {
+            latitude,
+            longitude,
+        }
+    }
+
+    fn feature(name: &str, latitude: i32, longitude: i32) -> Feature {
+        Feature {
+            name: name.into(),
+            location: Some(point(latitude, longitude)),
+            ..Feature::default()
+        }
+    }
+
+    #[test]
+    fn parses_bounds_in_degrees() {
+        let bounds = parse_bounds("40.5, -74.25,41,-73").unwrap();
+        assert_eq!(bounds.lo, Some(point(405_000_000, -742_500_000)));
+        assert_eq!(bounds.hi, Some(point(410_000_000, -730_000_000)));
+
+        assert!(parse_bounds("40,-74,41").is_err());
+        assert!(parse_bounds("40,-74,41,east").is_err());
+        assert!(parse_bounds("95,-74,41,-73").is_err());
+    }
+
+    #[test]
+    fn places_features_within_the_margin() {
+        let bounds = Rectangle {
+            lo: Some(point(0, 0)),
+            hi: Some(point(10_000_000, 20_000_000)),
+        };
+        let features = [
+            feature("south west", 0, 0),
+            feature("north east", 10_000_000, 20_000_000),
+            feature("outside", 20_000_000, 0),
+        ];
+        let svg = Map::new(bounds)
+            .with_projection(Projection::Equirectangular)
+            .with_size(200)
+            .render(&features, &[]);
+
+        // Two degrees east span the 200 pixels, so one north spans 100.
+        assert!(svg.contains(r#"width="232" height="132""#), "{}", svg);
+        assert!(svg.contains(r#"<circle cx="16.0" cy="116.0""#), "{}", svg);
+        assert!(svg.contains(r#"<circle cx="216.0" cy="16.0""#), "{}", svg);
+        assert!(svg.contains(">north east</text>"));
+        assert!(!svg.contains("outside"));
+    }
+
+    #[test]
+    fn mercator_stretches_away_from_the_equator() {
+        let center = 0.0;
+        let (_, y1) = Projection::Mercator.project(&point(100_000_000, 0), center);
+        let (_, y2) = Projection::Mercator.project(&point(600_000_000, 0), center);
+        let (_, y3) = Projection::Mercator.project(&point(700_000_000, 0), center);
+        assert!(y3 - y2 > 2.0 * y1);
+
+        // Poles are pinned to the edge of the square map.
+        let (_, pole) = Projection::Mercator.project(&point(900_000_000, 0), center);
+        assert!((pole - std::f64::consts::PI).abs() < 1e-6, "{}", pole);
+    }
+
+    #[test]
+    fn draws_routes_and_escapes_names() {
+        let features = [feature("Fish & <Chips>", 10, 10)];
+        let routes = vec![vec![point(0, 0), point(10, 10), point(20, 0)]];
+        let svg = Map::new(fit(&features, &routes))
+            .with_labels(false)
+            .render(&features, &routes);
+
+        assert!(svg.contains("<title>Fish &amp; &lt;Chips&gt;</title>"));
+        assert!(!svg.contains("<text"));
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert!(svg.contains("route 1 (3 points)"));
+    }
+}
@@ -0,0 +1,358 @@
+use crate::geo::{bounding_box, DistanceModel};
+use crate::route_guide::route_guide_client::RouteGuideClient;
+use crate::route_guide::route_guide_server::RouteGuide;
+use crate::route_guide::{
+    Feature, FeatureQuery, NearestFeature, NearestFeatureRequest, Point, Rectangle, Route,
+    RouteNote, RouteQuery, RouteRequest, RouteSummary, SimplifyRequest,
+};
+use crate::service::{
+    feature_query_args, get_route, get_simplified_route, list_routes, nearest_feature_args,
+};
+use crate::shard::ShardMap;
+use crate::shutdown::{self, Drain};
+use crate::store::RouteStore;
+
+use futures_core::stream::BoxStream;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Response, Status, Streaming};
+use tracing::info;
+
+/// Exposes the `RouteGuide` API on top of a set of shards, each serving the features of the
+/// grid cells `ShardMap` assigns to it. Recorded routes are kept by the proxy itself.
+#[derive(Debug)]
+pub struct RouteGuideProxy {
+    shards: Vec<RouteGuideClient<Channel>>,
+    map: ShardMap,
+    routes: Arc<RouteStore>,
+    next_chat_shard: AtomicUsize,
+    drain: Drain,
+    distance: DistanceModel,
+}
+
+impl RouteGuideProxy {
+    /// `shards[i]` must be serving shard `i` of `shards.len()`.
+    pub fn new(shards: Vec<Channel>) -> Self {
+        let map = ShardMap::new(shards.len());
+        Self {
+            shards: shards.into_iter().map(RouteGuideClient::new).collect(),
+            map,
+            routes: Arc::new(RouteStore::default()),
+            next_chat_shard: AtomicUsize::new(0),
+            drain: Drain::default(),
+            distance: DistanceModel::default(),
+        }
+    }
+
+    /// Measures route distances with `model`. The shards measure nearness to features with
+    /// their own.
+    pub fn with_distance(self, model: DistanceModel) -> Self {
+        Self {
+            distance: model,
+            ..self
+        }
+    }
+
+    /// Ends the open streams of this proxy once started.
+    pub fn drain(&self) -> Drain {
+        self.drain.clone()
+    }
+
+    fn shard(&self, index: usize) -> RouteGuideClient<Channel> {
+        self.shards[index].clone()
+    }
+
+    /// Streams the features that `call` streams from each of the shards `indices`, as they
+    /// come. A shard's error is passed on, and ends only its part.
+    fn merge_features<F, Fut>(
+        &self,
+        indices: Vec<usize>,
+        call: F,
+    ) -> ReceiverStream<Result<Feature, Status>>
+    where
+        F: Fn(RouteGuideClient<Channel>) -> Fut,
+        Fut: Future<Output = Result<Response<Streaming<Feature>>, Status>> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(4);
+        for index in indices {
+            let call = call(self.shard(index));
+            let tx = tx.clone();
+            let drain = self.drain.clone();
+            tokio::spawn(async move {
+                let mut stream = match call.await {
+                    Ok(resp) => resp.into_inner(),
+                    Err(status) => {
+                        let _ = tx.send(Err(status)).await;
+                        return;
+                    }
+                };
+
+                loop {
+                    let message = tokio::select! {
+                        message = stream.message() => message,
+                        _ = drain.started() => Err(shutdown::status()),
+                    };
+                    let item = match message {
+                        Ok(Some(f)) => Ok(f),
+                        Ok(None) => break,
+                        Err(status) => Err(status),
+                    };
+                    let failed = item.is_err();
+                    if tx.send(item).await.is_err() || failed {
+                        break;
+                    }
+                }
+            });
+        }
+        ReceiverStream::new(rx)
+    }
+
+    /// Counts the points that are features, asking each shard once about the bounding box of
+    /// the points it owns.
+    async fn count_features(&self, points: &[Point]) -> Result<i32, Status> {
+        let mut by_shard: HashMap<usize, Vec<Point>> = HashMap::new();
+        for p in points {
+            by_shard.entry(self.map.shard_of(p)).or_default().push(*p);
+        }
+
+        let mut feature_count = 0;
+        for (index, points) in by_shard {
+            let bounds = Rectangle {
+                lo: Some(Point {
+                    latitude: points.iter().map(|p| p.latitude).min().unwrap(),
+                    longitude: points.iter().map(|p| p.longitude).min().unwrap(),
+                }),
+                hi: Some(Point {
+                    latitude: points.iter().map(|p| p.latitude).max().unwrap(),
+                    longitude: points.iter().map(|p| p.longitude).max().unwrap(),
+                }),
+            };
+
+            let mut features = HashSet::new();
+            let mut stream = self
+                .shard(index)
+                .list_features(Request::new(bounds))
+                .await?
+                .into_inner();
+            while let Some(f) = stream.message().await? {
+                features.extend(f.location);
+            }
+
+            feature_count += points.iter().filter(|p| features.contains(p)).count() as i32;
+        }
+
+        Ok(feature_count)
+    }
+}
+
+#[tonic::async_trait]
+impl RouteGuide for RouteGuideProxy {
+    async fn get_feature(&self, req: Request<Point>) -> Result<Response<Feature>, Status> {
+        info!("GetFeature: {:?}", req.get_ref());
+        let index = self.map.shard_of(req.get_ref());
+        let feature = self
+            .shard(index)
+            .get_feature(Request::new(req.into_inner()))
+            .await?;
+        Ok(Response::new(feature.into_inner()))
+    }
+
+    type ListFeaturesStream = ReceiverStream<Result<Feature, Status>>;
+
+    async fn list_features(
+        &self,
+        req: Request<Rectangle>,
+    ) -> Result<Response<Self::ListFeaturesStream>, Status> {
+        info!("ListFeatures: {:?}", req.get_ref());
+        let rect = req.into_inner();
+        if rect.lo.is_none() || rect.hi.is_none() {
+            return Err(Status::invalid_argument("rectangle must have both corners"));
+        }
+
+        let stream = self.merge_features(self.map.shards_in(&rect), move |mut client| async move {
+            client.list_features(Request::new(rect)).await
+        });
+        Ok(Response::new(stream))
+    }
+
+    type SearchFeaturesStream = ReceiverStream<Result<Feature, Status>>;
+
+    async fn search_features(
+        &self,
+        req: Request<FeatureQuery>,
+    ) -> Result<Response<Self::SearchFeaturesStream>, Status> {
+        info!("SearchFeatures: {:?}", req.get_ref());
+        let query = req.into_inner();
+        feature_query_args(&query)?;
+
+        let indices = match &query.area {
+            Some(area) => self.map.shards_in(area),
+            None => (0..self.shards.len()).collect(),
+        };
+        let stream = self.merge_features(indices, move |mut client| {
+            let query = query.clone();
+            async move { client.search_features(Request::new(query)).await }
+        });
+        Ok(Response::new(stream))
+    }
+
+    async fn record_route(
+        &self,
+        req: Request<Streaming<Point>>,
+    ) -> Result<Response<RouteSummary>, Status> {
+        use tokio_stream::StreamExt;
+        info!("RecordRoute");
+
+        let mut stream = req.into_inner();
+        let mut summary = RouteSummary::default();
+        let mut points: Vec<Point> = vec![];
+        let mut distance = 0f64;
+        let now = Instant::now();
+
+        loop {
+            let point = tokio::select! {
+                point = stream.next() => point,
+                _ = self.drain.started() => return Err(shutdown::status()),
+            };
+            let Some(point) = point else {
+                break;
+            };
+            let point = point?;
+            summary.point_count += 1;
+
+            if let Some(last_point) = points.last() {
+                distance += self.distance.distance(last_point, &point);
+            }
+
+            points.push(point);
+        }
+
+        summary.distance = distance.round() as i64;
+        summary.feature_count = self.count_features(&points).await?;
+        summary.elapsed_time = now.elapsed().as_secs() as i32;
+
+        let route = self.routes.insert(summary, points);
+
+        Ok(Response::new(route.summary.unwrap()))
+    }
+
+    type RouteChatStream = BoxStream<'static, Result<RouteNote, Status>>;
+
+    async fn route_chat(
+        &self,
+        req: Request<Streaming<RouteNote>>,
+    ) -> Result<Response<Self::RouteChatStream>, Status> {
+        use tokio_stream::StreamExt;
+        info!("RouteChat");
+
+        // A chat only remembers the notes of its own stream, so any shard can host it.
+        let index = self.next_chat_shard.fetch_add(1, Ordering::Relaxed) % self.shards.len();
+
+        // Forward the notes until the client's stream fails, then fail the chat with its
+        // error rather than letting the shard see a clean end. Draining stops forwarding too,
+        // letting go of the client's stream so its connection can close.
+        let (error_tx, mut error_rx) = mpsc::channel(1);
+        let mut notes = req.into_inner();
+        let drain = self.drain.clone();
+        let outbound = async_stream::stream! {
+            loop {
+                let note = tokio::select! {
+                    note = notes.next() => note,
+                    _ = drain.started() => None,
+                };
+                match note {
+                    Some(Ok(note)) => yield note,
+                    Some(Err(status)) => {
+                        let _ = error_tx.send(status).await;
+                        break;
+                    }
+                    None => break,
+                }
+            }
+        };
+        let mut inbound = self
+            .shard(index)
+            .route_chat(Request::new(outbound))
+            .await?
+            .into_inner();
+
+        let drain = self.drain.clone();
+        let output = async_stream::try_stream! {
+            loop {
+                let note = tokio::select! {
+                    note = inbound.next() => note,
+                    Some(status) = error_rx.recv() => Some(Err(status)),
+                    _ = drain.started() => Some(Err(shutdown::status())),
+                };
+                let Some(note) = note else {
+                    break;
+                };
+                let note = note?;
+                yield note;
+            }
+        };
+
+        Ok(Response::new(Box::pin(output) as Self::RouteChatStream))
+    }
+
+    async fn get_route(&self, req: Request<RouteRequest>) -> Result<Response<Route>, Status> {
+        get_route(&self.routes, req)
+    }
+
+    type ListRoutesStream = BoxStream<'static, Result<Route, Status>>;
+
+    async fn list_routes(
+        &self,
+        req: Request<RouteQuery>,
+    ) -> Result<Response<Self::ListRoutesStream>, Status> {
+        list_routes(&self.routes, req)
+    }
+
+    async fn get_simplified_route(
+        &self,
+        req: Request<SimplifyRequest>,
+    ) -> Result<Response<Route>, Status> {
+        get_simplified_route(&self.routes, req)
+    }
+
+    async fn get_nearest_feature(
+        &self,
+        req: Request<NearestFeatureRequest>,
+    ) -> Result<Response<NearestFeature>, Status> {
+        info!("GetNearestFeature: {:?}", req.get_ref());
+        let req = req.into_inner();
+        let (location, tolerance) = nearest_feature_args(&req)?;
+
+        // The nearest feature may be over a cell border, so ask every shard near enough.
+        let indices = match bounding_box(&location, tolerance) {
+            Some(area) => self.map.shards_in(&area),
+            None => (0..self.shards.len()).collect(),
+        };
+
+        let mut nearest: Option<NearestFeature> = None;
+        for index in indices {
+            let found = match self
+                .shard(index)
+                .get_nearest_feature(Request::new(req))
+                .await
+            {
+                Ok(resp) => resp.into_inner(),
+                Err(status) if status.code() == Code::NotFound => continue,
+                Err(status) => return Err(status),
+            };
+            if nearest.as_ref().is_none_or(|x| found.distance < x.distance) {
+                nearest = Some(found);
+            }
+        }
+
+        nearest
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(""))
+    }
+}
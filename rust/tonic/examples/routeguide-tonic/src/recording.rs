@@ -0,0 +1,220 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use futures_core::future::BoxFuture;
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::time::Instant;
+use tonic::body::Body as TonicBody;
+use tower::{Layer, Service};
+use tracing::error;
+use wire_tonic::frame;
+use wire_tonic::tap::{Finish, Side, Tap, TapBody};
+
+/// One gRPC call of a recorded session. A session file holds one call per line, as JSON, in
+/// the order the calls finished.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Call {
+    pub path: String,
+
+    /// In milliseconds since the session started.
+    pub started_at: u64,
+
+    pub requests: Vec<Message>,
+
+    pub headers: BTreeMap<String, String>,
+
+    pub responses: Vec<Message>,
+
+    pub trailers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Message {
+    /// In milliseconds since the call started.
+    pub at: u64,
+
+    /// How many request messages the client had sent when this message arrived.
+    pub after_requests: usize,
+
+    pub compressed: bool,
+
+    /// The message as it was on the wire, base64 encoded.
+    pub data: String,
+}
+
+impl Message {
+    pub fn encode(at: u64, after_requests: usize, msg: &impl prost::Message) -> Self {
+        Self {
+            at,
+            after_requests,
+            compressed: false,
+            data: BASE64.encode(msg.encode_to_vec()),
+        }
+    }
+
+    /// Decodes an uncompressed message.
+    pub fn decode<M: prost::Message + Default>(&self) -> Result<M, Box<dyn std::error::Error>> {
+        if self.compressed {
+            return Err("message is compressed".into());
+        }
+        Ok(M::decode(BASE64.decode(&self.data)?.as_slice())?)
+    }
+
+    /// Returns the length-prefixed gRPC frame carrying this message.
+    pub fn frame(&self) -> Result<Bytes, base64::DecodeError> {
+        Ok(frame::encode(self.compressed, &BASE64.decode(&self.data)?))
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<Call>, Box<dyn std::error::Error>> {
+    let mut calls = vec![];
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            calls.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(calls)
+}
+
+pub(crate) fn header_map(headers: &BTreeMap<String, String>) -> HeaderMap {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.parse().ok()?, value.parse().ok()?)))
+        .collect()
+}
+
+fn header_strings(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+#[derive(Debug)]
+struct Sink {
+    started: Instant,
+    file: Mutex<File>,
+}
+
+impl Sink {
+    fn write(&self, call: &Call) {
+        let mut line = serde_json::to_vec(call).unwrap();
+        line.push(b'\n');
+        if let Err(err) = self.file.lock().unwrap().write_all(&line) {
+            error!("failed to record call to {}: {}", call.path, err);
+        }
+    }
+}
+
+/// The call being recorded, written out when it finishes.
+#[derive(Debug)]
+struct CallState {
+    call: Call,
+    started: Instant,
+    sink: Option<Arc<Sink>>,
+}
+
+impl Tap for CallState {
+    fn on_message(&mut self, side: Side, compressed: bool, payload: Bytes) {
+        let msg = Message {
+            at: self.started.elapsed().as_millis() as u64,
+            after_requests: self.call.requests.len(),
+            compressed,
+            data: BASE64.encode(payload),
+        };
+        match side {
+            Side::Request => self.call.requests.push(msg),
+            Side::Response => self.call.responses.push(msg),
+        }
+    }
+
+    fn on_trailers(&mut self, trailers: &HeaderMap) {
+        self.call.trailers = header_strings(trailers);
+    }
+
+    fn finish(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            sink.write(&self.call);
+        }
+    }
+}
+
+/// Records every call made through the wrapped client transport to a session file, which
+/// `Replay` can serve back later.
+#[derive(Debug, Clone)]
+pub struct RecordLayer {
+    sink: Arc<Sink>,
+}
+
+impl RecordLayer {
+    /// Starts a new session, truncating `path`.
+    pub fn new(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            sink: Arc::new(Sink {
+                started: Instant::now(),
+                file: Mutex::new(File::create(path)?),
+            }),
+        })
+    }
+}
+
+impl<S> Layer<S> for RecordLayer {
+    type Service = Recorder<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Recorder {
+            inner,
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Recorder<S> {
+    inner: S,
+    sink: Arc<Sink>,
+}
+
+impl<S> Service<http::Request<TonicBody>> for Recorder<S>
+where
+    S: Service<http::Request<TonicBody>, Response = http::Response<TonicBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<TonicBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<TonicBody>) -> Self::Future {
+        let state = Arc::new(Mutex::new(CallState {
+            call: Call {
+                path: req.uri().path().to_string(),
+                started_at: self.sink.started.elapsed().as_millis() as u64,
+                ..Call::default()
+            },
+            started: Instant::now(),
+            sink: Some(self.sink.clone()),
+        }));
+
+        let req = req.map(|inner| TapBody::request(inner, state.clone()));
+        let fut = self.inner.call(req);
+        let finish = Finish::new(state.clone());
+
+        Box::pin(async move {
+            let resp = fut.await?;
+            state.lock().unwrap().call.headers = header_strings(resp.headers());
+            Ok(resp.map(|inner| TapBody::response(inner, finish)))
+        })
+    }
+}
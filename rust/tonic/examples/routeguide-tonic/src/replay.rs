@@ -0,0 +1,125 @@
+use crate::recording::{self, header_map, Call};
+
+use bytes::Bytes;
+use http_body::{Body, Frame};
+use http_body_util::{BodyExt, StreamBody};
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::body::Body as TonicBody;
+use tonic::Status;
+use tower::Service;
+use wire_tonic::frame;
+
+/// Serves a session recorded by `RecordLayer` back. Each incoming call is answered by the
+/// first call in the recording with the same method that has not been replayed yet. Response
+/// messages are held back until the client has sent as many requests as it had during the
+/// recording, and are released with the recorded timing.
+///
+/// `Replay` can stand in for a `Channel` in a generated client, or be served over the network
+/// with `Routes::from(axum::Router::new().fallback_service(replay))`.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    calls: Arc<Mutex<Vec<Option<Call>>>>,
+}
+
+impl Replay {
+    pub fn new(calls: Vec<Call>) -> Self {
+        Self {
+            calls: Arc::new(Mutex::new(calls.into_iter().map(Some).collect())),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::new(recording::load(path)?))
+    }
+
+    fn take(&self, path: &str) -> Option<Call> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|call| call.as_ref().is_some_and(|call| call.path == path))?
+            .take()
+    }
+}
+
+impl<B> Service<http::Request<B>> for Replay
+where
+    B: Body<Data = Bytes> + Send + 'static,
+{
+    type Response = http::Response<TonicBody>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let path = req.uri().path().to_string();
+        let Some(call) = self.take(&path) else {
+            let status = Status::failed_precondition(format!("no recorded call left for {path}"));
+            return ready(Ok(status.into_http()));
+        };
+
+        let mut resp = http::Response::new(());
+        *resp.headers_mut() = header_map(&call.headers);
+
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(replay(call, req.into_body(), tx));
+
+        let body = TonicBody::new(StreamBody::new(ReceiverStream::new(rx)));
+        ready(Ok(resp.map(|()| body)))
+    }
+}
+
+async fn replay<B>(call: Call, body: B, tx: mpsc::Sender<Result<Frame<Bytes>, Status>>)
+where
+    B: Body<Data = Bytes>,
+{
+    let started = Instant::now();
+    let mut body = Box::pin(body);
+    let mut parser = frame::Parser::default();
+    let mut requests = 0;
+    let mut requests_done = false;
+
+    for msg in &call.responses {
+        while requests < msg.after_requests && !requests_done {
+            match body.frame().await {
+                Some(Ok(frame)) => {
+                    if let Some(data) = frame.data_ref() {
+                        requests += parser.push(data).len();
+                    }
+                }
+                _ => requests_done = true,
+            }
+        }
+
+        tokio::time::sleep_until(started + Duration::from_millis(msg.at)).await;
+
+        let frame = match msg.frame() {
+            Ok(frame) => frame,
+            Err(err) => {
+                let status = Status::data_loss(format!("corrupt recording: {err}"));
+                let _ = tx.send(Err(status)).await;
+                return;
+            }
+        };
+        if tx.send(Ok(Frame::data(frame))).await.is_err() {
+            return;
+        }
+    }
+
+    if !call.trailers.is_empty() {
+        let _ = tx
+            .send(Ok(Frame::trailers(header_map(&call.trailers))))
+            .await;
+    }
+}
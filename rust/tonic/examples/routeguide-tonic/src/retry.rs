@@ -0,0 +1,295 @@
+use crate::descriptors;
+
+use bytes::Bytes;
+use futures_core::future::BoxFuture;
+use http::HeaderValue;
+use http_body::{Body, Frame, SizeHint};
+use http_body_util::{BodyExt, Full};
+use std::collections::HashSet;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+use tonic::body::Body as TonicBody;
+use tonic::{Code, Status};
+use tower::{Layer, Service, ServiceExt};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct RetryConfig {
+    /// Milliseconds a call may take, retries included, unless it sets its own deadline.
+    #[arg(long, default_value_t = 10_000)]
+    pub timeout: u64,
+
+    /// Times to send a call again when the server answers `Unavailable` or can't be reached.
+    /// Only calls taking a single request are sent again.
+    #[arg(long, default_value_t = 3)]
+    pub retries: u32,
+
+    /// Milliseconds to wait before the first retry, doubling before each one after it.
+    #[arg(long, default_value_t = 50)]
+    pub retry_backoff: u64,
+}
+
+/// Gives every call of a client a deadline, and sends calls taking a single request again
+/// while the server answers `Unavailable` straight away.
+///
+/// The deadline is the one the call set with `Request::set_timeout`, or else the configured
+/// timeout. It covers the retries and the whole response, and is passed on to the server.
+/// A call still running when it passes fails with `DeadlineExceeded`. Retries that would
+/// start after it are not sent.
+#[derive(Debug, Clone)]
+pub struct RetryLayer {
+    config: RetryConfig,
+    retryable: Arc<HashSet<String>>,
+}
+
+impl RetryLayer {
+    /// Retries the methods of the services in `descriptors`, an encoded file descriptor set.
+    pub fn new(config: RetryConfig, descriptors: &[u8]) -> Result<Self, prost::DecodeError> {
+        let retryable = descriptors::methods(descriptors)?
+            .into_iter()
+            .filter(|x| !x.client_streaming)
+            .map(|x| x.path)
+            .collect();
+        Ok(Self {
+            config,
+            retryable: Arc::new(retryable),
+        })
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = Retry<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Retry {
+            inner,
+            config: self.config.clone(),
+            retryable: self.retryable.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Retry<S> {
+    inner: S,
+    config: RetryConfig,
+    retryable: Arc<HashSet<String>>,
+}
+
+impl<S> Service<http::Request<TonicBody>> for Retry<S>
+where
+    S: Service<http::Request<TonicBody>, Response = http::Response<TonicBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<BoxError>,
+{
+    type Response = http::Response<TonicBody>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<TonicBody>) -> Self::Future {
+        // Keep the service that was made ready for the first attempt.
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        let retryable = self.retryable.contains(req.uri().path());
+
+        Box::pin(async move {
+            let timeout = req
+                .headers()
+                .get("grpc-timeout")
+                .and_then(parse_timeout)
+                .unwrap_or(Duration::from_millis(config.timeout));
+            let deadline = Instant::now() + timeout;
+
+            let attempts = async {
+                if retryable {
+                    send_with_retries(inner, req, &config, deadline).await
+                } else {
+                    send(inner, req, deadline).await
+                }
+            };
+            // The channel and the server are held to the same deadline, and fail the call
+            // when it passes, but that is reported as the deadline.
+            let resp = tokio::select! {
+                biased;
+                _ = tokio::time::sleep_until(deadline) => return Ok(deadline_exceeded().into_http()),
+                resp = attempts => resp?,
+            };
+            Ok(resp.map(|inner| {
+                TonicBody::new(DeadlineBody {
+                    inner,
+                    sleep: Box::pin(tokio::time::sleep_until(deadline)),
+                    expired: false,
+                })
+            }))
+        })
+    }
+}
+
+async fn send<S>(
+    inner: S,
+    mut req: http::Request<TonicBody>,
+    deadline: Instant,
+) -> Result<http::Response<TonicBody>, BoxError>
+where
+    S: Service<http::Request<TonicBody>, Response = http::Response<TonicBody>>,
+    S::Error: Into<BoxError>,
+{
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    req.headers_mut()
+        .insert("grpc-timeout", format_timeout(remaining));
+    inner.oneshot(req).await.map_err(Into::into)
+}
+
+async fn send_with_retries<S>(
+    inner: S,
+    req: http::Request<TonicBody>,
+    config: &RetryConfig,
+    deadline: Instant,
+) -> Result<http::Response<TonicBody>, BoxError>
+where
+    S: Service<http::Request<TonicBody>, Response = http::Response<TonicBody>> + Clone,
+    S::Error: Into<BoxError>,
+{
+    let buffered = Buffered::new(req).await?;
+    let mut backoff = Duration::from_millis(config.retry_backoff);
+    let mut retries = config.retries;
+    loop {
+        let resp = send(inner.clone(), buffered.request(), deadline).await;
+        let failed = match &resp {
+            Ok(resp) => unavailable(resp),
+            Err(_) => true,
+        };
+        if !failed || retries == 0 || Instant::now() + backoff >= deadline {
+            return resp;
+        }
+        retries -= 1;
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+fn deadline_exceeded() -> Status {
+    Status::deadline_exceeded("deadline exceeded")
+}
+
+/// Reads a `grpc-timeout` header, such as `250m` or `5S`.
+fn parse_timeout(value: &HeaderValue) -> Option<Duration> {
+    let value = value.to_str().ok()?;
+    let (digits, unit) = value.split_at(value.len().checked_sub(1)?);
+    let n: u64 = digits.parse().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(n * 3600),
+        "M" => Duration::from_secs(n * 60),
+        "S" => Duration::from_secs(n),
+        "m" => Duration::from_millis(n),
+        "u" => Duration::from_micros(n),
+        "n" => Duration::from_nanos(n),
+        _ => return None,
+    })
+}
+
+/// Writes a `grpc-timeout` header in milliseconds, which allows up to eight digits. Rounds
+/// up, so the deadline passes here first.
+fn format_timeout(timeout: Duration) -> HeaderValue {
+    let millis = timeout.as_micros().div_ceil(1000).clamp(1, 99_999_999);
+    HeaderValue::from_str(&format!("{}m", millis)).unwrap()
+}
+
+/// Whether the server answered `Unavailable` without starting the response.
+pub(crate) fn unavailable(resp: &http::Response<TonicBody>) -> bool {
+    resp.headers()
+        .get("grpc-status")
+        .is_some_and(|x| Code::from_bytes(x.as_bytes()) == Code::Unavailable)
+}
+
+/// A request read in full, to be sent more than once.
+pub(crate) struct Buffered {
+    parts: http::request::Parts,
+    body: Bytes,
+}
+
+impl Buffered {
+    pub(crate) async fn new(req: http::Request<TonicBody>) -> Result<Self, Status> {
+        let (parts, body) = req.into_parts();
+        let body = body.collect().await?.to_bytes();
+        Ok(Self { parts, body })
+    }
+
+    pub(crate) fn request(&self) -> http::Request<TonicBody> {
+        let mut req = http::Request::new(TonicBody::new(Full::new(self.body.clone())));
+        *req.method_mut() = self.parts.method.clone();
+        *req.uri_mut() = self.parts.uri.clone();
+        *req.version_mut() = self.parts.version;
+        *req.headers_mut() = self.parts.headers.clone();
+        req
+    }
+}
+
+/// Fails the response with `DeadlineExceeded` if it is still going when the deadline passes.
+struct DeadlineBody {
+    inner: TonicBody,
+    sleep: Pin<Box<Sleep>>,
+    expired: bool,
+}
+
+impl Body for DeadlineBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        if self.expired {
+            return Poll::Ready(None);
+        }
+        if let Poll::Ready(frame) = Pin::new(&mut self.inner).poll_frame(cx) {
+            return Poll::Ready(frame);
+        }
+        ready!(self.sleep.as_mut().poll(cx));
+        self.expired = true;
+        Poll::Ready(Some(Err(deadline_exceeded())))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.expired || self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_and_writes_grpc_timeouts() {
+        let parse = |x: &'static str| parse_timeout(&HeaderValue::from_static(x));
+        assert_eq!(parse("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(parse("2M"), Some(Duration::from_secs(120)));
+        assert_eq!(parse("7u"), Some(Duration::from_micros(7)));
+        assert_eq!(parse("m"), None);
+        assert_eq!(parse("5x"), None);
+        assert_eq!(parse(""), None);
+
+        assert_eq!(format_timeout(Duration::from_millis(1500)), "1500m");
+        assert_eq!(format_timeout(Duration::from_micros(1001)), "2m");
+        assert_eq!(format_timeout(Duration::ZERO), "1m");
+    }
+}
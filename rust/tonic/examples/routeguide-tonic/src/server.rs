@@ -1,199 +1,215 @@
-pub mod route_guide {
-    tonic::include_proto!("routeguide");
-}
-use route_guide::route_guide_server::{RouteGuide, RouteGuideServer};
-use route_guide::{Feature, Point, Rectangle, RouteNote, RouteSummary};
-
-use futures_core::stream::BoxStream;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
-use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::mpsc;
-use tokio_stream::wrappers::ReceiverStream;
+use routeguide_tonic::admin::{self, AdminService};
+use routeguide_tonic::chat::ChatConfig;
+use routeguide_tonic::compression::{CompressLayer, CompressionConfig};
+use routeguide_tonic::data;
+use routeguide_tonic::fault::{FaultConfig, FaultLayer};
+use routeguide_tonic::features::Reloader;
+use routeguide_tonic::geo::DistanceModel;
+use routeguide_tonic::map;
+use routeguide_tonic::route_guide::admin::admin_server::AdminServer;
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::{admin as admin_proto, FILE_DESCRIPTOR_SET};
+use routeguide_tonic::service::RouteGuideService;
+use routeguide_tonic::shard::ShardMap;
+use routeguide_tonic::shutdown;
+use routeguide_tonic::streams::ActiveStreams;
+
+use audit_tonic::AuditConfig;
+use clap::Parser;
+use std::net::SocketAddr;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::PathBuf;
+use std::time::Duration;
 use tonic::transport::Server;
-use tonic::{Request, Response, Status, Streaming};
-use tracing::info;
-
-mod data;
-
-#[derive(Debug)]
-pub struct RouteGuideService {
-    features: Arc<HashMap<Point, Feature>>,
-}
-
-impl RouteGuideService {
-    fn new(features: Vec<Feature>) -> Self {
-        let features: HashMap<Point, Feature> = features
-            .into_iter()
-            .map(|x| (x.location.unwrap(), x))
-            .collect();
-        Self {
-            features: Arc::new(features),
-        }
-    }
-}
-
-#[tonic::async_trait]
-impl RouteGuide for RouteGuideService {
-    async fn get_feature(&self, req: Request<Point>) -> Result<Response<Feature>, Status> {
-        info!("GetFeature: {:?}", req.get_ref());
-        if let Some(x) = self.features.get(req.get_ref()) {
-            Ok(Response::new(x.clone()))
-        } else {
-            Err(Status::not_found(""))
-        }
-    }
-
-    type ListFeaturesStream = ReceiverStream<Result<Feature, Status>>;
-
-    async fn list_features(
-        &self,
-        req: Request<Rectangle>,
-    ) -> Result<Response<Self::ListFeaturesStream>, Status> {
-        info!("ListFeatures: {:?}", req.get_ref());
-        let (tx, rx) = mpsc::channel(4);
-        let features = self.features.clone();
-        tokio::spawn(async move {
-            for f in features.values() {
-                if in_range(f.location.as_ref().unwrap(), req.get_ref()) {
-                    // TODO(kostya): What happens if the connection is busted or if a receiver
-                    // is closed before we're done? Will [unwrap] result in panic?
-                    tx.send(Ok(f.clone())).await.unwrap();
-                }
-            }
-        });
-
-        Ok(Response::new(ReceiverStream::new(rx)))
-    }
-
-    async fn record_route(
-        &self,
-        req: Request<Streaming<Point>>,
-    ) -> Result<Response<RouteSummary>, Status> {
-        use tokio_stream::StreamExt;
-        info!("RecordRoute");
+use tonic_health::ServingStatus;
+use tower::util::option_layer;
+use tracing::{info, warn};
 
-        let mut stream = req.into_inner();
-        let mut summary = RouteSummary::default();
-        let mut last_point = None;
-        let now = Instant::now();
+#[derive(Debug, Parser)]
+struct Args {
+    #[arg(long, default_value = "[::1]:10000")]
+    addr: SocketAddr,
 
-        while let Some(point) = stream.next().await {
-            let point = point?;
-            summary.point_count += 1;
+    /// Serve the Admin service here, for operators to inspect and control the server.
+    #[arg(long)]
+    admin_addr: Option<SocketAddr>,
 
-            if self.features.contains_key(&point) {
-                summary.feature_count += 1;
-            }
+    /// Serve an SVG map of the features and recorded routes over HTTP here, at `/map.svg`.
+    #[arg(long)]
+    http_addr: Option<SocketAddr>,
 
-            if let Some(ref last_point) = last_point {
-                summary.distance += calc_distance(last_point, &point);
-            }
+    /// Feature database to serve instead of the one shipped with the crate. It is reloaded
+    /// when it changes, or on SIGHUP.
+    #[arg(long)]
+    data: Option<PathBuf>,
 
-            last_point = Some(point);
-        }
+    /// Seconds between checks of the feature database for changes.
+    #[arg(long, default_value = "2")]
+    watch_interval: NonZeroU64,
 
-        summary.elapsed_time = now.elapsed().as_secs() as i32;
+    /// Only serve the features of shard INDEX out of COUNT, written as INDEX/COUNT.
+    #[arg(long, value_parser = parse_shard)]
+    shard: Option<(usize, usize)>,
 
-        Ok(Response::new(summary))
-    }
+    #[command(flatten)]
+    compression: CompressionConfig,
 
-    type RouteChatStream = BoxStream<'static, Result<RouteNote, Status>>;
+    #[command(flatten)]
+    chat: ChatConfig,
 
-    async fn route_chat(
-        &self,
-        req: Request<Streaming<RouteNote>>,
-    ) -> Result<Response<Self::RouteChatStream>, Status> {
-        use tokio_stream::StreamExt;
+    /// Routes recorded through RecordRoute to keep in memory. The oldest are forgotten first.
+    #[arg(long, default_value = "10000")]
+    max_routes: NonZeroUsize,
 
-        info!("RouteChat");
+    /// How to measure route distances and nearness to features.
+    #[arg(long, value_enum, default_value_t)]
+    distance: DistanceModel,
 
-        let mut notes = HashMap::new();
-        let mut stream = req.into_inner();
+    #[command(flatten)]
+    audit: AuditConfig,
 
-        let output = async_stream::try_stream! {
-            while let Some(note) = stream.next().await {
-                let note = note?;
+    /// Inject the faults described in this JSON file, to test how clients cope.
+    #[arg(long)]
+    faults: Option<PathBuf>,
 
-                let location = note.location.unwrap();
-                let location_notes = notes.entry(location).or_insert(vec![]);
-                location_notes.push(note);
-
-                for note in location_notes {
-                    yield note.clone();
-                }
-            }
-        };
+    /// Seconds to wait for open calls to finish after SIGINT or SIGTERM.
+    #[arg(long, default_value_t = 10)]
+    drain_timeout: u64,
+}
 
-        Ok(Response::new(Box::pin(output) as Self::RouteChatStream))
+fn parse_shard(s: &str) -> Result<(usize, usize), String> {
+    let (index, count) = s.split_once('/').ok_or("expected INDEX/COUNT")?;
+    let index: usize = index.parse().map_err(|err| format!("bad index: {err}"))?;
+    let count: usize = count.parse().map_err(|err| format!("bad count: {err}"))?;
+    if index >= count {
+        return Err(format!("index {index} is out of range for {count} shards"));
     }
+    Ok((index, count))
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
-
-    let addr = "[::1]:10000".parse()?;
-    info!("listening on {}", addr);
+    let log_filter = admin::init_logging();
+    let args = Args::parse();
+
+    let path = args.data.clone().unwrap_or_else(data::default_path);
+    let service = RouteGuideService::new(vec![])
+        .with_distance(args.distance)
+        .with_chat(args.chat.clone())
+        .with_max_routes(args.max_routes);
+    let mut reloader = Reloader::new(path, service.features());
+    if let Some((index, count)) = args.shard {
+        let shards = ShardMap::new(count);
+        reloader = reloader.filter(move |f| shards.shard_of(f.location.as_ref().unwrap()) == index);
+    }
+    let loaded = reloader.reload()?;
+    match args.shard {
+        Some((index, count)) => {
+            info!("serving shard {}/{} with {} features", index, count, loaded)
+        }
+        None => info!("serving {} features", loaded),
+    }
+    tokio::spawn(
+        reloader
+            .clone()
+            .watch(Duration::from_secs(args.watch_interval.get())),
+    );
+
+    info!("listening on {}", args.addr);
+
+    let drain = service.drain();
+    let streams = ActiveStreams::default();
+    if let Some(addr) = args.admin_addr {
+        let admin = AdminService::new(service.features(), streams.clone())
+            .with_reloader(reloader)
+            .with_log_filter(log_filter);
+        let reflection = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(admin_proto::FILE_DESCRIPTOR_SET)
+            .build_v1()?;
+        info!("admin listening on {}", addr);
+        let serve = Server::builder()
+            .add_service(AdminServer::new(admin))
+            .add_service(reflection)
+            .serve_with_shutdown(addr, {
+                let drain = drain.clone();
+                async move { drain.started().await }
+            });
+        tokio::spawn(async move {
+            if let Err(err) = serve.await {
+                warn!("admin server failed: {}", err);
+            }
+        });
+    }
+    if let Some(addr) = args.http_addr {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        info!("serving the map on http://{}/map.svg", addr);
+        let serve = axum::serve(listener, map::router(service.features(), service.routes()))
+            .with_graceful_shutdown({
+                let drain = drain.clone();
+                async move { drain.started().await }
+            });
+        tokio::spawn(async move {
+            if let Err(err) = serve.await {
+                warn!("map server failed: {}", err);
+            }
+        });
+    }
 
-    let route_guide = RouteGuideServer::new(RouteGuideService::new(data::load()?));
+    let mut route_guide = RouteGuideServer::new(service);
+    for encoding in args.compression.accepted() {
+        route_guide = route_guide.accept_compressed(encoding);
+    }
 
-    Server::builder()
+    let audit = args.audit.layer(FILE_DESCRIPTOR_SET)?;
+    let faults = match &args.faults {
+        Some(path) => {
+            warn!("injecting the faults in {}", path.display());
+            Some(FaultLayer::new(FaultConfig::load(path)?))
+        }
+        None => None,
+    };
+    // Clients balancing over several servers stop sending calls here once draining starts.
+    let (health, health_service) = tonic_health::server::health_reporter();
+    health
+        .set_serving::<RouteGuideServer<RouteGuideService>>()
+        .await;
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .build_v1()?;
+
+    // Once signalled, the server stops accepting connections and waits for the open calls,
+    // which the drain asks to finish.
+    let serve = Server::builder()
+        .layer(CompressLayer::server(args.compression))
+        .layer(option_layer(audit))
+        .layer(option_layer(faults))
+        .layer(streams.layer(FILE_DESCRIPTOR_SET)?)
         .add_service(route_guide)
-        .serve(addr)
-        .await?;
-
-    Ok(())
-}
+        .add_service(reflection)
+        .add_service(health_service)
+        .serve_with_shutdown(args.addr, {
+            let drain = drain.clone();
+            async move {
+                shutdown::signal().await;
+                info!("shutting down, draining open calls");
+                health
+                    .set_service_status("", ServingStatus::NotServing)
+                    .await;
+                health
+                    .set_not_serving::<RouteGuideServer<RouteGuideService>>()
+                    .await;
+                drain.start();
+            }
+        });
 
-impl Hash for Point {
-    fn hash<H>(&self, state: &mut H)
-    where
-        H: Hasher,
-    {
-        self.latitude.hash(state);
-        self.longitude.hash(state);
+    tokio::select! {
+        res = serve => res?,
+        _ = async {
+            drain.started().await;
+            tokio::time::sleep(Duration::from_secs(args.drain_timeout)).await;
+        } => warn!("calls still open after {}s, exiting anyway", args.drain_timeout),
     }
-}
-
-impl Eq for Point {}
-
-fn in_range(p: &Point, rect: &Rectangle) -> bool {
-    use std::cmp;
-
-    let lo = rect.lo.as_ref().unwrap();
-    let hi = rect.hi.as_ref().unwrap();
-
-    let left = cmp::min(lo.longitude, hi.longitude);
-    let right = cmp::max(lo.longitude, hi.longitude);
-    let bottom = cmp::min(lo.latitude, hi.latitude);
-    let top = cmp::max(lo.latitude, hi.latitude);
-
-    p.longitude >= left && p.longitude <= right && p.latitude >= bottom && p.latitude <= top
-}
 
-/// Calculates the distance between two points using the "haversine" formula.
-/// This code was taken from http://www.movable-type.co.uk/scripts/latlong.html.
-fn calc_distance(p1: &Point, p2: &Point) -> i32 {
-    const CORD_FACTOR: f64 = 1e7;
-    const R: f64 = 6_371_000.0; // meters
-
-    let lat1 = p1.latitude as f64 / CORD_FACTOR;
-    let lat2 = p2.latitude as f64 / CORD_FACTOR;
-    let lng1 = p1.longitude as f64 / CORD_FACTOR;
-    let lng2 = p2.longitude as f64 / CORD_FACTOR;
-
-    let lat_rad1 = lat1.to_radians();
-    let lat_rad2 = lat2.to_radians();
-
-    let delta_lat = (lat2 - lat1).to_radians();
-    let delta_lng = (lng2 - lng1).to_radians();
-
-    let a = (delta_lat / 2f64).sin() * (delta_lat / 2f64).sin()
-        + (lat_rad1).cos() * (lat_rad2).cos() * (delta_lng / 2f64).sin() * (delta_lng / 2f64).sin();
-
-    let c = 2f64 * a.sqrt().atan2((1f64 - a).sqrt());
-
-    (R * c) as i32
+    info!("stopped");
+    Ok(())
 }
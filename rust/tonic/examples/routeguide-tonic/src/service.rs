@@ -0,0 +1,360 @@
+use crate::geo::{in_range, DistanceModel};
+use crate::route_guide::route_guide_server::RouteGuide;
+use crate::route_guide::{
+    Feature, FeatureQuery, NearestFeature, NearestFeatureRequest, Point, Rectangle, Route,
+    RouteNote, RouteQuery, RouteRequest, RouteSummary, SimplifyRequest,
+};
+
+use futures_core::stream::BoxStream;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::info;
+
+use crate::chat::{ChatConfig, ChatHistory};
+use crate::features::Features;
+use crate::shutdown::{self, Drain};
+use crate::store::RouteStore;
+
+#[derive(Debug)]
+pub struct RouteGuideService {
+    features: Features,
+    routes: Arc<RouteStore>,
+    drain: Drain,
+    distance: DistanceModel,
+    chat: ChatConfig,
+}
+
+impl RouteGuideService {
+    pub fn new(features: Vec<Feature>) -> Self {
+        Self {
+            features: Features::new(features),
+            routes: Arc::new(RouteStore::default()),
+            drain: Drain::default(),
+            distance: DistanceModel::default(),
+            chat: ChatConfig::default(),
+        }
+    }
+
+    /// Measures route distances and nearness to features with `model`.
+    pub fn with_distance(self, model: DistanceModel) -> Self {
+        Self {
+            distance: model,
+            ..self
+        }
+    }
+
+    /// Keeps at most `max_routes` recorded routes, forgetting the oldest first.
+    pub fn with_max_routes(self, max_routes: NonZeroUsize) -> Self {
+        Self {
+            routes: Arc::new(RouteStore::new(max_routes)),
+            ..self
+        }
+    }
+
+    /// Bounds what each RouteChat remembers, and how much it sends back, by `config`.
+    pub fn with_chat(self, config: ChatConfig) -> Self {
+        Self {
+            chat: config,
+            ..self
+        }
+    }
+
+    /// The features this service answers from, to replace while serving.
+    pub fn features(&self) -> Features {
+        self.features.clone()
+    }
+
+    /// The routes recorded through this service.
+    pub fn routes(&self) -> Arc<RouteStore> {
+        self.routes.clone()
+    }
+
+    /// Ends the open streams of this service once started.
+    pub fn drain(&self) -> Drain {
+        self.drain.clone()
+    }
+
+    /// Streams the features of the current snapshot that `matches` accepts.
+    fn stream_features(
+        &self,
+        matches: impl Fn(&Feature) -> bool + Send + 'static,
+    ) -> ReceiverStream<Result<Feature, Status>> {
+        let (tx, rx) = mpsc::channel(4);
+        let features = self.features.snapshot();
+        let drain = self.drain.clone();
+        tokio::spawn(async move {
+            for f in features.values() {
+                if drain.is_started() {
+                    let _ = tx.send(Err(shutdown::status())).await;
+                    return;
+                }
+                // The receiver is gone once the client hangs up; stop producing then.
+                if matches(f) && tx.send(Ok(f.clone())).await.is_err() {
+                    return;
+                }
+            }
+        });
+        ReceiverStream::new(rx)
+    }
+}
+
+#[tonic::async_trait]
+impl RouteGuide for RouteGuideService {
+    async fn get_feature(&self, req: Request<Point>) -> Result<Response<Feature>, Status> {
+        info!("GetFeature: {:?}", req.get_ref());
+        if let Some(x) = self.features.snapshot().get(req.get_ref()) {
+            Ok(Response::new(x.clone()))
+        } else {
+            Err(Status::not_found(""))
+        }
+    }
+
+    type ListFeaturesStream = ReceiverStream<Result<Feature, Status>>;
+
+    async fn list_features(
+        &self,
+        req: Request<Rectangle>,
+    ) -> Result<Response<Self::ListFeaturesStream>, Status> {
+        info!("ListFeatures: {:?}", req.get_ref());
+        let rect = req.into_inner();
+        Ok(Response::new(self.stream_features(move |f| {
+            in_range(f.location.as_ref().unwrap(), &rect)
+        })))
+    }
+
+    type SearchFeaturesStream = ReceiverStream<Result<Feature, Status>>;
+
+    async fn search_features(
+        &self,
+        req: Request<FeatureQuery>,
+    ) -> Result<Response<Self::SearchFeaturesStream>, Status> {
+        info!("SearchFeatures: {:?}", req.get_ref());
+        let query = req.into_inner();
+        feature_query_args(&query)?;
+        Ok(Response::new(
+            self.stream_features(move |f| matches_query(f, &query)),
+        ))
+    }
+
+    async fn record_route(
+        &self,
+        req: Request<Streaming<Point>>,
+    ) -> Result<Response<RouteSummary>, Status> {
+        use tokio_stream::StreamExt;
+        info!("RecordRoute");
+
+        let features = self.features.snapshot();
+        let mut stream = req.into_inner();
+        let mut summary = RouteSummary::default();
+        let mut points: Vec<Point> = vec![];
+        let mut distance = 0f64;
+        let now = Instant::now();
+
+        loop {
+            let point = tokio::select! {
+                point = stream.next() => point,
+                _ = self.drain.started() => return Err(shutdown::status()),
+            };
+            let Some(point) = point else {
+                break;
+            };
+            let point = point?;
+            summary.point_count += 1;
+
+            if features.contains_key(&point) {
+                summary.feature_count += 1;
+            }
+
+            if let Some(last_point) = points.last() {
+                distance += self.distance.distance(last_point, &point);
+            }
+
+            points.push(point);
+        }
+
+        summary.distance = distance.round() as i64;
+        summary.elapsed_time = now.elapsed().as_secs() as i32;
+
+        let route = self.routes.insert(summary, points);
+
+        Ok(Response::new(route.summary.unwrap()))
+    }
+
+    type RouteChatStream = BoxStream<'static, Result<RouteNote, Status>>;
+
+    async fn route_chat(
+        &self,
+        req: Request<Streaming<RouteNote>>,
+    ) -> Result<Response<Self::RouteChatStream>, Status> {
+        use tokio_stream::StreamExt;
+
+        info!("RouteChat");
+
+        let mut history = ChatHistory::new(self.chat.clone());
+        let mut stream = req.into_inner();
+        let drain = self.drain.clone();
+
+        let output = async_stream::try_stream! {
+            loop {
+                let note = tokio::select! {
+                    note = stream.next() => note,
+                    _ = drain.started() => Some(Err(shutdown::status())),
+                };
+                let Some(note) = note else {
+                    break;
+                };
+                let note = note?;
+
+                for note in history.add(note) {
+                    yield note;
+                }
+            }
+        };
+
+        Ok(Response::new(Box::pin(output) as Self::RouteChatStream))
+    }
+
+    async fn get_route(&self, req: Request<RouteRequest>) -> Result<Response<Route>, Status> {
+        get_route(&self.routes, req)
+    }
+
+    type ListRoutesStream = BoxStream<'static, Result<Route, Status>>;
+
+    async fn list_routes(
+        &self,
+        req: Request<RouteQuery>,
+    ) -> Result<Response<Self::ListRoutesStream>, Status> {
+        list_routes(&self.routes, req)
+    }
+
+    async fn get_simplified_route(
+        &self,
+        req: Request<SimplifyRequest>,
+    ) -> Result<Response<Route>, Status> {
+        get_simplified_route(&self.routes, req)
+    }
+
+    async fn get_nearest_feature(
+        &self,
+        req: Request<NearestFeatureRequest>,
+    ) -> Result<Response<NearestFeature>, Status> {
+        info!("GetNearestFeature: {:?}", req.get_ref());
+        let (location, tolerance) = nearest_feature_args(req.get_ref())?;
+
+        let features = self.features.snapshot();
+        if let Some(x) = features.get(&location) {
+            return Ok(Response::new(NearestFeature {
+                feature: Some(x.clone()),
+                distance: 0f64,
+            }));
+        }
+
+        let nearest = features
+            .values()
+            .map(|f| {
+                let distance = self
+                    .distance
+                    .distance(&location, f.location.as_ref().unwrap());
+                (f, distance)
+            })
+            .filter(|(_, distance)| *distance <= tolerance)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        match nearest {
+            Some((feature, distance)) => Ok(Response::new(NearestFeature {
+                feature: Some(feature.clone()),
+                distance,
+            })),
+            None => Err(Status::not_found("")),
+        }
+    }
+}
+
+/// Answers `GetRoute` from `routes`, for both the service and the proxy.
+#[allow(clippy::result_large_err)] // the status goes straight back to the client
+pub(crate) fn get_route(
+    routes: &RouteStore,
+    req: Request<RouteRequest>,
+) -> Result<Response<Route>, Status> {
+    info!("GetRoute: {:?}", req.get_ref());
+    match routes.get(req.get_ref().id) {
+        Some(route) => Ok(Response::new(route)),
+        None => Err(Status::not_found("")),
+    }
+}
+
+/// Answers `ListRoutes` from `routes`, for both the service and the proxy.
+#[allow(clippy::result_large_err)] // the status goes straight back to the client
+pub(crate) fn list_routes(
+    routes: &RouteStore,
+    req: Request<RouteQuery>,
+) -> Result<Response<BoxStream<'static, Result<Route, Status>>>, Status> {
+    info!("ListRoutes: {:?}", req.get_ref());
+    if let Some(area) = &req.get_ref().area {
+        if area.lo.is_none() || area.hi.is_none() {
+            return Err(Status::invalid_argument("area must have both corners"));
+        }
+    }
+
+    let routes = routes.list(req.get_ref());
+    let output = tokio_stream::iter(routes.into_iter().map(Ok));
+
+    Ok(Response::new(Box::pin(output)))
+}
+
+/// Answers `GetSimplifiedRoute` from `routes`, for both the service and the proxy.
+#[allow(clippy::result_large_err)] // the status goes straight back to the client
+pub(crate) fn get_simplified_route(
+    routes: &RouteStore,
+    req: Request<SimplifyRequest>,
+) -> Result<Response<Route>, Status> {
+    info!("GetSimplifiedRoute: {:?}", req.get_ref());
+    let SimplifyRequest { id, tolerance } = req.into_inner();
+    if tolerance.is_nan() || tolerance < 0f64 {
+        return Err(Status::invalid_argument("tolerance must be non-negative"));
+    }
+
+    match routes.simplified(id, tolerance) {
+        Some(route) => Ok(Response::new(route)),
+        None => Err(Status::not_found("")),
+    }
+}
+
+/// Checks a `GetNearestFeature` request, returning its location and tolerance.
+#[allow(clippy::result_large_err)] // the status goes straight back to the client
+pub(crate) fn nearest_feature_args(req: &NearestFeatureRequest) -> Result<(Point, f64), Status> {
+    let Some(location) = req.location else {
+        return Err(Status::invalid_argument("location is required"));
+    };
+    let tolerance = req.tolerance_meters;
+    if tolerance.is_nan() || tolerance < 0f64 {
+        return Err(Status::invalid_argument(
+            "tolerance_meters must be non-negative",
+        ));
+    }
+    Ok((location, tolerance))
+}
+
+/// Checks a `SearchFeatures` request, whose area may be unset but not half set.
+#[allow(clippy::result_large_err)] // the status goes straight back to the client
+pub(crate) fn feature_query_args(query: &FeatureQuery) -> Result<(), Status> {
+    match &query.area {
+        Some(area) if area.lo.is_none() || area.hi.is_none() => {
+            Err(Status::invalid_argument("area must have both corners"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether `f` is in the area of `query` and has all of its tags.
+fn matches_query(f: &Feature, query: &FeatureQuery) -> bool {
+    query
+        .area
+        .as_ref()
+        .is_none_or(|area| in_range(f.location.as_ref().unwrap(), area))
+        && query.tags.iter().all(|tag| f.tags.contains(tag))
+}
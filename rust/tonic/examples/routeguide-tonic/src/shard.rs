@@ -0,0 +1,114 @@
+use crate::route_guide::{Point, Rectangle};
+use std::cmp;
+
+/// Side of a grid cell, one degree in E7 units.
+const CELL: i32 = 10_000_000;
+
+/// Assigns locations to shards by one-degree grid cell. Neighbouring cells land on different
+/// shards, so a dense area is spread out rather than pinned to a single process.
+#[derive(Debug, Clone, Copy)]
+pub struct ShardMap {
+    count: usize,
+}
+
+impl ShardMap {
+    pub fn new(count: usize) -> Self {
+        assert!(count > 0, "need at least one shard");
+        Self { count }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn shard_of(&self, p: &Point) -> usize {
+        self.shard_of_cell(p.latitude.div_euclid(CELL), p.longitude.div_euclid(CELL))
+    }
+
+    /// Returns, in ascending order, every shard owning a cell that overlaps `rect`.
+    pub fn shards_in(&self, rect: &Rectangle) -> Vec<usize> {
+        let lo = rect.lo.as_ref().unwrap();
+        let hi = rect.hi.as_ref().unwrap();
+
+        let bottom = cmp::min(lo.latitude, hi.latitude).div_euclid(CELL);
+        let top = cmp::max(lo.latitude, hi.latitude).div_euclid(CELL);
+        let left = cmp::min(lo.longitude, hi.longitude).div_euclid(CELL);
+        let right = cmp::max(lo.longitude, hi.longitude).div_euclid(CELL);
+
+        let mut owned = vec![false; self.count];
+        let mut remaining = self.count;
+        'outer: for lat in bottom..=top {
+            for lng in left..=right {
+                let shard = self.shard_of_cell(lat, lng);
+                if !owned[shard] {
+                    owned[shard] = true;
+                    remaining -= 1;
+                    if remaining == 0 {
+                        break 'outer;
+                    }
+                }
+            }
+        }
+
+        (0..self.count).filter(|&shard| owned[shard]).collect()
+    }
+
+    fn shard_of_cell(&self, lat: i32, lng: i32) -> usize {
+        (lat as i64 * 361 + lng as i64).rem_euclid(self.count as i64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(latitude: i32, longitude: i32) -> Point {
+        Point {
+            latitude,
+            longitude,
+        }
+    }
+
+    fn rect(lo: Point, hi: Point) -> Rectangle {
+        Rectangle {
+            lo: Some(lo),
+            hi: Some(hi),
+        }
+    }
+
+    #[test]
+    fn same_cell_same_shard() {
+        let shards = ShardMap::new(4);
+        assert_eq!(
+            shards.shard_of(&point(400_000_001, -740_000_001)),
+            shards.shard_of(&point(409_999_999, -749_999_999)),
+        );
+    }
+
+    #[test]
+    fn rectangle_inside_one_cell() {
+        let shards = ShardMap::new(4);
+        let p = point(405_000_000, -745_000_000);
+        let r = rect(
+            point(401_000_000, -741_000_000),
+            point(409_000_000, -749_000_000),
+        );
+        assert_eq!(shards.shards_in(&r), vec![shards.shard_of(&p)]);
+    }
+
+    #[test]
+    fn rectangle_covers_owners_of_its_points() {
+        let shards = ShardMap::new(16);
+        let r = rect(
+            point(405_000_000, -735_000_000),
+            point(395_000_000, -745_000_000),
+        );
+        let owners = shards.shards_in(&r);
+        assert_eq!(owners.len(), 4);
+        for lat in (395_000_000..=405_000_000).step_by(1_000_000) {
+            for lng in (-745_000_000..=-735_000_000).step_by(1_000_000) {
+                assert!(owners.contains(&shards.shard_of(&point(lat, lng))));
+            }
+        }
+    }
+}
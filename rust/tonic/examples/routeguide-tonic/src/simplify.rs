@@ -0,0 +1,104 @@
+use crate::route_guide::Point;
+
+const CORD_FACTOR: f64 = 1e7;
+const R: f64 = 6_371_000.0; // meters
+
+/// Simplifies a polyline with the Douglas-Peucker algorithm. Every dropped point lies within
+/// `tolerance` meters of the simplified line. The first and last points are always kept.
+pub fn simplify(points: &[Point], tolerance: f64) -> Vec<Point> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    // Project onto a local plane around the first point. Routes are short enough for an
+    // equirectangular projection to be accurate to well below any sensible tolerance.
+    let origin = &points[0];
+    let cos_lat = (origin.latitude as f64 / CORD_FACTOR).to_radians().cos();
+    let projected: Vec<(f64, f64)> = points
+        .iter()
+        .map(|p| {
+            let x = ((p.longitude - origin.longitude) as f64 / CORD_FACTOR).to_radians() * cos_lat;
+            let y = ((p.latitude - origin.latitude) as f64 / CORD_FACTOR).to_radians();
+            (R * x, R * y)
+        })
+        .collect();
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = ranges.pop() {
+        let mut max_distance = 0f64;
+        let mut max_index = first;
+        for i in first + 1..last {
+            let distance = segment_distance(projected[i], projected[first], projected[last]);
+            if distance > max_distance {
+                max_distance = distance;
+                max_index = i;
+            }
+        }
+
+        if max_distance > tolerance {
+            keep[max_index] = true;
+            ranges.push((first, max_index));
+            ranges.push((max_index, last));
+        }
+    }
+
+    points
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(p, _)| *p)
+        .collect()
+}
+
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 == 0f64 {
+        0f64
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len2).clamp(0f64, 1f64)
+    };
+    let (x, y) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - x).powi(2) + (p.1 - y).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(latitude: i32, longitude: i32) -> Point {
+        Point {
+            latitude,
+            longitude,
+        }
+    }
+
+    #[test]
+    fn keeps_short_routes() {
+        let route = vec![point(0, 0), point(10, 10)];
+        assert_eq!(simplify(&route, 100f64), route);
+    }
+
+    #[test]
+    fn drops_collinear_points() {
+        let route = vec![
+            point(0, 0),
+            point(0, 1_000),
+            point(0, 2_000),
+            point(0, 3_000),
+        ];
+        assert_eq!(simplify(&route, 1f64), vec![point(0, 0), point(0, 3_000)]);
+    }
+
+    #[test]
+    fn respects_tolerance() {
+        // The middle point is about 111 meters off the straight line.
+        let route = vec![point(0, 0), point(10_000, 10_000), point(0, 20_000)];
+        assert_eq!(simplify(&route, 200f64).len(), 2);
+        assert_eq!(simplify(&route, 50f64), route);
+    }
+}
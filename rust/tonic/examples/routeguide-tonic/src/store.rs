@@ -0,0 +1,183 @@
+use crate::route_guide::{Point, Route, RouteQuery, RouteSummary};
+use crate::simplify::simplify;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Keeps the routes recorded through `RecordRoute` in memory, keyed by the ID handed out in
+/// their summary. Once it holds `max_routes`, each new route pushes out the oldest, whose ID
+/// is not handed out again.
+#[derive(Debug)]
+pub struct RouteStore {
+    routes: RwLock<BTreeMap<u64, Route>>,
+    max_routes: NonZeroUsize,
+}
+
+impl Default for RouteStore {
+    fn default() -> Self {
+        Self::new(NonZeroUsize::new(10_000).unwrap())
+    }
+}
+
+impl RouteStore {
+    pub fn new(max_routes: NonZeroUsize) -> Self {
+        Self {
+            routes: RwLock::default(),
+            max_routes,
+        }
+    }
+
+    pub fn insert(&self, mut summary: RouteSummary, points: Vec<Point>) -> Route {
+        let recorded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs() as i64);
+
+        let mut routes = self.routes.write().unwrap();
+        let id = routes.last_key_value().map_or(1, |(id, _)| id + 1);
+        summary.route_id = id;
+        let route = Route {
+            id,
+            recorded_at,
+            summary: Some(summary),
+            points,
+        };
+        routes.insert(id, route.clone());
+        while routes.len() > self.max_routes.get() {
+            routes.pop_first();
+        }
+        route
+    }
+
+    pub fn get(&self, id: u64) -> Option<Route> {
+        self.routes.read().unwrap().get(&id).cloned()
+    }
+
+    /// Returns the route with its points reduced by `simplify` to the given tolerance in meters.
+    pub fn simplified(&self, id: u64, tolerance: f64) -> Option<Route> {
+        let routes = self.routes.read().unwrap();
+        let route = routes.get(&id)?;
+        Some(Route {
+            id: route.id,
+            recorded_at: route.recorded_at,
+            summary: route.summary,
+            points: simplify(&route.points, tolerance),
+        })
+    }
+
+    /// Returns the matching routes ordered by ID, without their points.
+    pub fn list(&self, query: &RouteQuery) -> Vec<Route> {
+        self.routes
+            .read()
+            .unwrap()
+            .values()
+            .filter(|route| {
+                (query.recorded_after == 0 || route.recorded_at >= query.recorded_after)
+                    && (query.recorded_before == 0 || route.recorded_at <= query.recorded_before)
+                    && query.area.as_ref().is_none_or(|area| {
+                        route.points.iter().any(|p| crate::geo::in_range(p, area))
+                    })
+            })
+            .map(|route| Route {
+                id: route.id,
+                recorded_at: route.recorded_at,
+                summary: route.summary,
+                points: vec![],
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route_guide::Rectangle;
+
+    fn point(latitude: i32, longitude: i32) -> Point {
+        Point {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// A straight line of `n` points heading north from `start`.
+    fn line(start: Point, n: i32) -> Vec<Point> {
+        (0..n)
+            .map(|i| point(start.latitude + i * 1_000, start.longitude))
+            .collect()
+    }
+
+    fn ids(routes: &[Route]) -> Vec<u64> {
+        routes.iter().map(|x| x.id).collect()
+    }
+
+    #[test]
+    fn hands_out_increasing_ids() {
+        let store = RouteStore::default();
+        let first = store.insert(RouteSummary::default(), line(point(0, 0), 3));
+        let second = store.insert(RouteSummary::default(), vec![]);
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(first.summary.unwrap().route_id, 1);
+
+        assert_eq!(store.get(1), Some(first));
+        assert_eq!(store.get(2), Some(second));
+        assert_eq!(store.get(3), None);
+        assert_eq!(store.simplified(3, 0.0), None);
+    }
+
+    #[test]
+    fn lists_by_time_and_area_without_points() {
+        let store = RouteStore::default();
+        assert!(store.list(&RouteQuery::default()).is_empty());
+
+        store.insert(RouteSummary::default(), line(point(0, 0), 3));
+        store.insert(RouteSummary::default(), line(point(500_000_000, 0), 3));
+
+        let all = store.list(&RouteQuery::default());
+        assert_eq!(ids(&all), [1, 2]);
+        assert!(all.iter().all(|x| x.points.is_empty()));
+
+        let area = Rectangle {
+            lo: Some(point(400_000_000, -1)),
+            hi: Some(point(600_000_000, 1)),
+        };
+        let query = RouteQuery {
+            area: Some(area),
+            ..RouteQuery::default()
+        };
+        assert_eq!(ids(&store.list(&query)), [2]);
+
+        let recorded_at = all[1].recorded_at;
+        let query = RouteQuery {
+            recorded_after: recorded_at + 1,
+            ..RouteQuery::default()
+        };
+        assert!(store.list(&query).is_empty());
+        let query = RouteQuery {
+            recorded_before: recorded_at,
+            ..RouteQuery::default()
+        };
+        assert_eq!(ids(&store.list(&query)), [1, 2]);
+    }
+
+    #[test]
+    fn simplifies_a_copy() {
+        let store = RouteStore::default();
+        store.insert(RouteSummary::default(), line(point(0, 0), 10));
+
+        let simplified = store.simplified(1, 10.0).unwrap();
+        assert_eq!(simplified.points, [point(0, 0), point(9_000, 0)]);
+        assert_eq!(store.get(1).unwrap().points.len(), 10);
+    }
+
+    #[test]
+    fn pushes_out_the_oldest_routes_when_full() {
+        let store = RouteStore::new(NonZeroUsize::new(2).unwrap());
+        for _ in 0..5 {
+            store.insert(RouteSummary::default(), vec![]);
+        }
+        assert_eq!(ids(&store.list(&RouteQuery::default())), [4, 5]);
+        assert_eq!(store.get(3), None);
+        assert_eq!(store.insert(RouteSummary::default(), vec![]).id, 6);
+    }
+}
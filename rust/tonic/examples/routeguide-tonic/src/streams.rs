@@ -0,0 +1,253 @@
+use crate::descriptors;
+
+use bytes::Bytes;
+use futures_core::future::BoxFuture;
+use http::HeaderMap;
+use http_body::{Body, Frame, SizeHint};
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime};
+use tokio::sync::oneshot;
+use tonic::body::Body as TonicBody;
+use tonic::transport::server::TcpConnectInfo;
+use tonic::Status;
+use tower::{Layer, Service};
+
+/// A streaming call open on the server.
+#[derive(Debug, Clone)]
+pub struct StreamInfo {
+    pub id: u64,
+
+    /// As `/package.Service/Method`.
+    pub method: String,
+
+    pub peer: Option<String>,
+
+    pub started_at: SystemTime,
+
+    started: Instant,
+}
+
+impl StreamInfo {
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    info: StreamInfo,
+    cancel: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    next_id: u64,
+    streams: BTreeMap<u64, Entry>,
+}
+
+/// Keeps track of the streaming calls open on a server, through the layer it makes, so they
+/// can be listed and cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct ActiveStreams {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl ActiveStreams {
+    /// Tracks calls to the streaming methods in `descriptors`, an encoded file descriptor set.
+    pub fn layer(&self, descriptors: &[u8]) -> Result<StreamsLayer, prost::DecodeError> {
+        let streaming = descriptors::methods(descriptors)?
+            .into_iter()
+            .filter(|x| x.client_streaming || x.server_streaming)
+            .map(|x| x.path)
+            .collect();
+        Ok(StreamsLayer {
+            streams: self.clone(),
+            streaming: Arc::new(streaming),
+        })
+    }
+
+    /// The open streams, oldest first.
+    pub fn list(&self) -> Vec<StreamInfo> {
+        let registry = self.registry.lock().unwrap();
+        registry.streams.values().map(|x| x.info.clone()).collect()
+    }
+
+    /// Ends the stream `id` with `CANCELLED`, returning whether it was open.
+    pub fn cancel(&self, id: u64) -> bool {
+        let mut registry = self.registry.lock().unwrap();
+        let cancel = registry.streams.get_mut(&id).and_then(|x| x.cancel.take());
+        match cancel {
+            Some(cancel) => cancel.send(()).is_ok(),
+            None => false,
+        }
+    }
+
+    fn register(
+        &self,
+        method: String,
+        peer: Option<String>,
+    ) -> (Registered, oneshot::Receiver<()>) {
+        let (tx, rx) = oneshot::channel();
+        let mut registry = self.registry.lock().unwrap();
+        registry.next_id += 1;
+        let id = registry.next_id;
+        let info = StreamInfo {
+            id,
+            method,
+            peer,
+            started_at: SystemTime::now(),
+            started: Instant::now(),
+        };
+        registry.streams.insert(
+            id,
+            Entry {
+                info,
+                cancel: Some(tx),
+            },
+        );
+        let registered = Registered {
+            streams: self.clone(),
+            id,
+        };
+        (registered, rx)
+    }
+}
+
+/// Removes a stream from the registry once it is over.
+struct Registered {
+    streams: ActiveStreams,
+    id: u64,
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        let mut registry = self.streams.registry.lock().unwrap();
+        registry.streams.remove(&self.id);
+    }
+}
+
+fn cancelled() -> Status {
+    Status::cancelled("cancelled by an operator")
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamsLayer {
+    streams: ActiveStreams,
+    streaming: Arc<HashSet<String>>,
+}
+
+impl<S> Layer<S> for StreamsLayer {
+    type Service = TrackStreams<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TrackStreams {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackStreams<S> {
+    inner: S,
+    layer: StreamsLayer,
+}
+
+impl<S> Service<http::Request<TonicBody>> for TrackStreams<S>
+where
+    S: Service<http::Request<TonicBody>, Response = http::Response<TonicBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<TonicBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<TonicBody>) -> Self::Future {
+        let method = req.uri().path();
+        if !self.layer.streaming.contains(method) {
+            return Box::pin(self.inner.call(req));
+        }
+
+        let peer = req
+            .extensions()
+            .get::<TcpConnectInfo>()
+            .and_then(|x| x.remote_addr())
+            .map(|x| x.to_string());
+        let (registered, mut cancel) = self.layer.streams.register(method.to_string(), peer);
+        let fut = self.inner.call(req);
+
+        Box::pin(async move {
+            // A call cancelled before it responds, such as a client stream, is dropped.
+            tokio::select! {
+                resp = fut => Ok(resp?.map(|inner| {
+                    TonicBody::new(CancellableBody {
+                        inner,
+                        cancel: Some(cancel),
+                        ended: false,
+                        _registered: registered,
+                    })
+                })),
+                Ok(()) = &mut cancel => Ok(cancelled().into_http()),
+            }
+        })
+    }
+}
+
+/// Passes a response through until it is cancelled, then ends it with `CANCELLED`
+/// trailers, dropping the rest.
+struct CancellableBody {
+    inner: TonicBody,
+    cancel: Option<oneshot::Receiver<()>>,
+    ended: bool,
+    _registered: Registered,
+}
+
+impl Body for CancellableBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        if self.ended {
+            return Poll::Ready(None);
+        }
+        if let Some(cancel) = &mut self.cancel {
+            match Pin::new(cancel).poll(cx) {
+                Poll::Ready(Ok(())) => {
+                    self.cancel = None;
+                    self.ended = true;
+                    // Dropping the body ends the stream producing it.
+                    self.inner = TonicBody::default();
+                    let mut trailers = HeaderMap::new();
+                    cancelled().add_header(&mut trailers).unwrap();
+                    return Poll::Ready(Some(Ok(Frame::trailers(trailers))));
+                }
+                Poll::Ready(Err(_)) => self.cancel = None,
+                Poll::Pending => {}
+            }
+        }
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.ended || self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        if self.ended {
+            SizeHint::with_exact(0)
+        } else {
+            SizeHint::default()
+        }
+    }
+}
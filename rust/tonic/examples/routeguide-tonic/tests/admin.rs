@@ -0,0 +1,172 @@
+use routeguide_tonic::admin::AdminService;
+use routeguide_tonic::features::Reloader;
+use routeguide_tonic::route_guide::admin::admin_client::AdminClient;
+use routeguide_tonic::route_guide::admin::admin_server::AdminServer;
+use routeguide_tonic::route_guide::admin::{
+    CancelStreamRequest, ListStreamsRequest, ReloadDataRequest, SetLogLevelRequest, StatsRequest,
+};
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::FILE_DESCRIPTOR_SET;
+use routeguide_tonic::service::RouteGuideService;
+use routeguide_tonic::streams::ActiveStreams;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{Channel, Server};
+use tonic::{Code, Request};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{reload, EnvFilter};
+
+mod common;
+
+use common::{connect, features, note, rectangle, serve_with, write_features};
+
+/// Serves routeguide and its admin service on one port, from the features in `name`.
+async fn start_server(name: &str) -> (RouteGuideClient<Channel>, AdminClient<Channel>) {
+    let service = RouteGuideService::new(vec![]);
+    let reloader = Reloader::new(write_features(name), service.features());
+    reloader.reload().unwrap();
+    let streams = ActiveStreams::default();
+    let admin = AdminService::new(service.features(), streams.clone()).with_reloader(reloader);
+
+    let addr = serve_with(|incoming| {
+        Server::builder()
+            .layer(streams.layer(FILE_DESCRIPTOR_SET).unwrap())
+            .add_service(RouteGuideServer::new(service))
+            .add_service(AdminServer::new(admin))
+            .serve_with_incoming(incoming)
+    })
+    .await;
+    let channel = connect(addr).await;
+    (
+        RouteGuideClient::new(channel.clone()),
+        AdminClient::new(channel),
+    )
+}
+
+#[tokio::test]
+async fn reports_stats_and_reloads() {
+    let (_, mut admin) = start_server("admin-stats").await;
+
+    let stats = admin.get_stats(StatsRequest {}).await.unwrap().into_inner();
+    assert_eq!(stats.feature_count, 64);
+    assert_eq!(
+        stats.bounds,
+        Some(rectangle(
+            (398_000_000, -752_000_000),
+            (419_000_000, -731_000_000)
+        ))
+    );
+    assert!(stats.loaded_at > 0);
+
+    let path = write_features("admin-stats");
+    std::fs::write(&path, "[]").unwrap();
+    let stats = admin
+        .reload_data(ReloadDataRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(stats.feature_count, 0);
+    assert_eq!(stats.bounds, None);
+
+    // A broken file is reported, and the features stay as they were.
+    std::fs::write(&path, "[{\"name\": ").unwrap();
+    let err = admin.reload_data(ReloadDataRequest {}).await.unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+    assert!(err.message().contains("EOF"), "{}", err.message());
+    let stats = admin.get_stats(StatsRequest {}).await.unwrap().into_inner();
+    assert_eq!(stats.feature_count, 0);
+}
+
+#[tokio::test]
+async fn lists_and_cancels_streams() {
+    let (mut client, mut admin) = start_server("admin-streams").await;
+    let list = |admin: &mut AdminClient<Channel>| {
+        let mut admin = admin.clone();
+        async move {
+            let resp = admin.list_streams(ListStreamsRequest {}).await.unwrap();
+            resp.into_inner().streams
+        }
+    };
+    assert!(list(&mut admin).await.is_empty());
+
+    let (tx, rx) = mpsc::channel(4);
+    let mut chat = client
+        .route_chat(Request::new(ReceiverStream::new(rx)))
+        .await
+        .unwrap()
+        .into_inner();
+    tx.send(note("hello")).await.unwrap();
+    chat.message().await.unwrap().unwrap();
+
+    // Unary calls, such as the admin's own, are not listed.
+    let streams = list(&mut admin).await;
+    assert_eq!(streams.len(), 1);
+    let stream = &streams[0];
+    assert_eq!(stream.method, "/routeguide.RouteGuide/RouteChat");
+    assert!(stream.peer.starts_with("127.0.0.1:"), "{}", stream.peer);
+    assert!(stream.duration >= 0.0);
+
+    let err = admin
+        .cancel_stream(CancelStreamRequest { id: stream.id + 1 })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    admin
+        .cancel_stream(CancelStreamRequest { id: stream.id })
+        .await
+        .unwrap();
+    let err = chat.message().await.unwrap_err();
+    assert_eq!(err.code(), Code::Cancelled);
+    assert_eq!(err.message(), "cancelled by an operator");
+    assert!(list(&mut admin).await.is_empty());
+
+    // Other calls go on as before.
+    client
+        .get_feature(features()[0].location.unwrap())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn changes_the_log_filter() {
+    let (filter, handle) = reload::Layer::new(EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::registry().with(filter);
+    let service = RouteGuideService::new(vec![]);
+    let admin = AdminService::new(service.features(), ActiveStreams::default())
+        .with_log_filter(handle.clone());
+
+    let addr = serve_with(|incoming| {
+        Server::builder()
+            .add_service(AdminServer::new(admin))
+            .serve_with_incoming(incoming)
+    })
+    .await;
+    let mut admin = AdminClient::new(connect(addr).await);
+
+    let filter = "routeguide_tonic=debug,warn";
+    let resp = admin
+        .set_log_level(SetLogLevelRequest {
+            filter: filter.into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.into_inner().previous, "info");
+    assert_eq!(handle.with_current(|x| x.to_string()).unwrap(), filter);
+
+    let err = admin
+        .set_log_level(SetLogLevelRequest {
+            filter: "routeguide_tonic=loud".into(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(handle.with_current(|x| x.to_string()).unwrap(), filter);
+
+    // Without a reloader there is nothing to reload.
+    let err = admin.reload_data(ReloadDataRequest {}).await.unwrap_err();
+    assert_eq!(err.code(), Code::Unimplemented);
+    drop(subscriber);
+}
@@ -0,0 +1,182 @@
+use routeguide_tonic::balance::{BalanceConfig, Balancer, Policy};
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::{Feature, RouteNote, FILE_DESCRIPTOR_SET};
+
+use std::net::SocketAddr;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Code, Request, Status};
+
+mod common;
+
+use common::{features, spawn_server, spawn_server_at, ServerProcess};
+
+async fn start_servers(name: &str, count: usize) -> (Vec<ServerProcess>, Vec<SocketAddr>) {
+    let mut servers = vec![];
+    let mut addrs = vec![];
+    for index in 0..count {
+        let (process, addr, _) = spawn_server(&format!("{name}-{index}"), &[]).await;
+        servers.push(process);
+        addrs.push(addr);
+    }
+    (servers, addrs)
+}
+
+fn uri(addr: &SocketAddr) -> String {
+    format!("http://{addr}")
+}
+
+fn config(addrs: &[SocketAddr], policy: Policy) -> BalanceConfig {
+    BalanceConfig {
+        addrs: addrs.iter().map(uri).collect(),
+        endpoints_file: None,
+        policy,
+        health_interval: NonZeroU64::new(100).unwrap(),
+    }
+}
+
+fn calls(balancer: &Balancer) -> Vec<u64> {
+    balancer.status().iter().map(|x| x.calls).collect()
+}
+
+async fn lookup(client: &mut RouteGuideClient<Balancer>) -> Result<Feature, Status> {
+    let location = features()[0].location.unwrap();
+    Ok(client
+        .get_feature(Request::new(location))
+        .await?
+        .into_inner())
+}
+
+async fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+    for _ in 0..200 {
+        if done() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("timed out waiting until {what}");
+}
+
+#[tokio::test]
+async fn round_robin_fails_over_while_servers_stop_and_start() {
+    let (mut servers, addrs) = start_servers("balance-failover", 3).await;
+    let balancer = Balancer::new(&config(&addrs, Policy::RoundRobin), FILE_DESCRIPTOR_SET).unwrap();
+    let mut client = RouteGuideClient::new(balancer.clone());
+
+    for _ in 0..30 {
+        lookup(&mut client).await.unwrap();
+    }
+    assert_eq!(calls(&balancer), [10, 10, 10]);
+
+    // Killing a server loses no calls: the one sent to it goes to another.
+    drop(servers.remove(0));
+    for _ in 0..30 {
+        lookup(&mut client).await.unwrap();
+    }
+    let status = balancer.status();
+    assert!(!status[0].healthy);
+    assert!(status[0].calls <= 11, "{:?}", status);
+    assert_eq!(
+        status.iter().map(|x| x.calls).sum::<u64>(),
+        60 + status[0].calls - 10
+    );
+
+    // Once back, the server passes its health check and gets its share again.
+    servers.push(spawn_server_at("balance-failover-0", addrs[0], &[]));
+    wait_until("the restarted server is healthy", || {
+        balancer.status()[0].healthy
+    })
+    .await;
+    let before = calls(&balancer);
+    for _ in 0..30 {
+        lookup(&mut client).await.unwrap();
+    }
+    let after = calls(&balancer);
+    let share: Vec<_> = after.iter().zip(&before).map(|(a, b)| a - b).collect();
+    assert_eq!(share, [10, 10, 10]);
+}
+
+#[tokio::test]
+async fn least_loaded_avoids_busy_servers() {
+    let (_servers, addrs) = start_servers("balance-least", 2).await;
+    let balancer =
+        Balancer::new(&config(&addrs, Policy::LeastLoaded), FILE_DESCRIPTOR_SET).unwrap();
+    let mut client = RouteGuideClient::new(balancer.clone());
+
+    // Keep a chat open on one server.
+    let (tx, rx) = mpsc::channel(4);
+    let mut chat = client
+        .route_chat(Request::new(ReceiverStream::new(rx)))
+        .await
+        .unwrap()
+        .into_inner();
+    tx.send(RouteNote {
+        location: features()[0].location,
+        message: "hello".into(),
+    })
+    .await
+    .unwrap();
+    chat.message().await.unwrap().unwrap();
+
+    let busy = balancer.status().iter().position(|x| x.in_flight == 1);
+    let busy = busy.expect("the chat is open on one server");
+
+    for _ in 0..10 {
+        lookup(&mut client).await.unwrap();
+    }
+    let mut expected = [10, 10];
+    expected[busy] = 1;
+    assert_eq!(calls(&balancer), expected);
+
+    drop(tx);
+    assert!(chat.message().await.unwrap().is_none());
+    drop(chat);
+    wait_until("the chat is closed", || {
+        balancer.status().iter().all(|x| x.in_flight == 0)
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn follows_the_endpoints_file() {
+    let (_servers, addrs) = start_servers("balance-file", 2).await;
+    let path = std::env::temp_dir().join(format!(
+        "routeguide-{}-balance-endpoints.txt",
+        std::process::id()
+    ));
+    let write = |path: &PathBuf, addrs: &[SocketAddr]| {
+        let lines: Vec<_> = addrs.iter().map(uri).collect();
+        std::fs::write(path, lines.join("\n")).unwrap();
+    };
+
+    write(&path, &addrs[..1]);
+    let config = BalanceConfig {
+        endpoints_file: Some(path.clone()),
+        ..config(&[], Policy::RoundRobin)
+    };
+    let balancer = Balancer::new(&config, FILE_DESCRIPTOR_SET).unwrap();
+    let mut client = RouteGuideClient::new(balancer.clone());
+    for _ in 0..4 {
+        lookup(&mut client).await.unwrap();
+    }
+    assert_eq!(calls(&balancer), [4]);
+
+    write(&path, &addrs);
+    wait_until("the new server is picked up", || {
+        balancer.status().len() == 2
+    })
+    .await;
+    for _ in 0..4 {
+        lookup(&mut client).await.unwrap();
+    }
+    assert_eq!(calls(&balancer), [6, 2]);
+
+    write(&path, &[]);
+    wait_until("the servers are dropped", || balancer.status().is_empty()).await;
+    let err = lookup(&mut client).await.unwrap_err();
+    assert_eq!(err.code(), Code::Unavailable);
+    assert_eq!(err.message(), "no healthy servers");
+}
@@ -0,0 +1,56 @@
+use routeguide_tonic::chat::ChatConfig;
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::service::RouteGuideService;
+
+use std::num::NonZeroUsize;
+use tonic::transport::Channel;
+use tonic::Request;
+
+mod common;
+
+use common::{features, note, start_server};
+
+fn service(config: ChatConfig) -> RouteGuideService {
+    RouteGuideService::new(features()).with_chat(config)
+}
+
+/// Sends a chat of `messages`, all at one location, returning what comes back.
+async fn chat(client: &mut RouteGuideClient<Channel>, messages: &[&str]) -> Vec<String> {
+    let notes: Vec<_> = messages.iter().map(|x| note(x)).collect();
+    let mut stream = client
+        .route_chat(Request::new(tokio_stream::iter(notes)))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut received = vec![];
+    while let Some(note) = stream.message().await.unwrap() {
+        received.push(note.message);
+    }
+    received
+}
+
+#[tokio::test]
+async fn replays_a_bounded_history() {
+    let mut client = start_server(service(ChatConfig {
+        per_location: NonZeroUsize::new(3).unwrap(),
+        ..ChatConfig::default()
+    }))
+    .await;
+    let messages: Vec<_> = (0..100).map(|x| x.to_string()).collect();
+    let messages: Vec<_> = messages.iter().map(String::as_str).collect();
+
+    let received = chat(&mut client, &messages).await;
+    assert_eq!(received.len(), 1 + 2 + 3 * 98);
+    assert_eq!(received[received.len() - 3..], ["97", "98", "99"]);
+}
+
+#[tokio::test]
+async fn sends_only_new_notes() {
+    let mut client = start_server(service(ChatConfig {
+        only_new: true,
+        ..ChatConfig::default()
+    }))
+    .await;
+    let received = chat(&mut client, &["a", "b", "a", "c", "b"]).await;
+    assert_eq!(received, ["a", "b", "c"]);
+}
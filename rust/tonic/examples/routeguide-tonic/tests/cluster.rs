@@ -0,0 +1,203 @@
+use routeguide_tonic::proxy::RouteGuideProxy;
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::{FeatureQuery, NearestFeatureRequest, Point, RouteNote};
+use routeguide_tonic::service::RouteGuideService;
+
+use prost::Message;
+use std::collections::BTreeSet;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::codec::ProstCodec;
+use tonic::transport::{Channel, Server};
+use tonic::{Code, Request};
+
+mod common;
+
+use common::{
+    connect, features, note, rectangle, serve_with, spawn_server, start_server, ServerProcess,
+};
+
+const SHARD_COUNT: usize = 3;
+
+async fn start_cluster(name: &str) -> (Vec<ServerProcess>, RouteGuideClient<Channel>) {
+    let mut shards = vec![];
+    let mut channels = vec![];
+    for index in 0..SHARD_COUNT {
+        let shard = format!("{index}/{SHARD_COUNT}");
+        let (process, _, channel) = spawn_server(name, &["--shard", &shard]).await;
+        shards.push(process);
+        channels.push(channel);
+    }
+
+    (shards, start_server(RouteGuideProxy::new(channels)).await)
+}
+
+#[tokio::test]
+async fn proxy_merges_features_from_all_shards() {
+    let (_shards, mut client) = start_cluster("list").await;
+
+    let rect = rectangle((400_000_000, -750_000_000), (420_000_000, -730_000_000));
+    let expected: BTreeSet<_> = features()
+        .into_iter()
+        .filter(|f| routeguide_tonic::geo::in_range(f.location.as_ref().unwrap(), &rect))
+        .map(|f| f.name)
+        .collect();
+
+    let mut actual = BTreeSet::new();
+    let mut stream = client
+        .list_features(Request::new(rect))
+        .await
+        .unwrap()
+        .into_inner();
+    while let Some(f) = stream.message().await.unwrap() {
+        assert!(actual.insert(f.name), "feature listed twice");
+    }
+
+    assert!(expected.len() < features().len());
+    assert_eq!(actual, expected);
+}
+
+#[tokio::test]
+async fn proxy_searches_every_shard_without_an_area() {
+    let (_shards, mut client) = start_cluster("search").await;
+
+    let query = FeatureQuery {
+        area: None,
+        tags: vec!["col-3".into()],
+    };
+    let mut names = BTreeSet::new();
+    let mut stream = client
+        .search_features(Request::new(query))
+        .await
+        .unwrap()
+        .into_inner();
+    while let Some(f) = stream.message().await.unwrap() {
+        assert!(names.insert(f.name), "feature listed twice");
+    }
+    let expected: BTreeSet<_> = (0..8).map(|lat| format!("feature {lat}/3")).collect();
+    assert_eq!(names, expected);
+}
+
+#[tokio::test]
+async fn proxy_routes_lookups_and_records_to_owning_shards() {
+    let (_shards, mut client) = start_cluster("lookup").await;
+
+    let features = features();
+    for f in features.iter().take(10) {
+        let resp = client
+            .get_feature(Request::new(f.location.unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(resp.into_inner().name, f.name);
+    }
+
+    let missing = Point {
+        latitude: 1,
+        longitude: 1,
+    };
+    let err = client.get_feature(Request::new(missing)).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    let points: Vec<_> = features
+        .iter()
+        .take(5)
+        .map(|f| f.location.unwrap())
+        .chain([missing])
+        .collect();
+    let summary = client
+        .record_route(Request::new(tokio_stream::iter(points)))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(summary.point_count, 6);
+    assert_eq!(summary.feature_count, 5);
+}
+
+#[tokio::test]
+async fn proxy_finds_nearest_features_across_shard_borders() {
+    let (_shards, mut client) = start_cluster("nearest").await;
+
+    // Just south of the 40th parallel, but nearer to feature 1/0 to the north of it.
+    let reading = Point {
+        latitude: 399_900_000,
+        longitude: -752_000_000,
+    };
+    let nearest = client
+        .get_nearest_feature(Request::new(NearestFeatureRequest {
+            location: Some(reading),
+            tolerance_meters: 25_000f64,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(nearest.feature.unwrap().name, "feature 1/0");
+
+    let err = client
+        .get_nearest_feature(Request::new(NearestFeatureRequest {
+            location: Some(reading),
+            tolerance_meters: 10_000f64,
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+}
+
+/// A `RouteNote` with its location left encoded, so a client can send one that doesn't decode.
+#[derive(Clone, PartialEq, prost::Message)]
+struct RawNote {
+    #[prost(bytes = "vec", tag = "1")]
+    location: Vec<u8>,
+    #[prost(string, tag = "2")]
+    message: String,
+}
+
+#[tokio::test]
+async fn proxy_passes_on_client_stream_errors_in_chats() {
+    // Any shard can host a chat, so one in process will do.
+    let shard = serve_with(|incoming| {
+        Server::builder()
+            .add_service(RouteGuideServer::new(RouteGuideService::new(features())))
+            .serve_with_incoming(incoming)
+    })
+    .await;
+    let proxy = RouteGuideProxy::new(vec![connect(shard).await]);
+    let addr = serve_with(|incoming| {
+        Server::builder()
+            .add_service(RouteGuideServer::new(proxy))
+            .serve_with_incoming(incoming)
+    })
+    .await;
+    let mut client = tonic::client::Grpc::new(connect(addr).await);
+
+    let (tx, rx) = mpsc::channel(4);
+    client.ready().await.unwrap();
+    let mut chat = client
+        .streaming(
+            Request::new(ReceiverStream::new(rx)),
+            "/routeguide.RouteGuide/RouteChat".parse().unwrap(),
+            ProstCodec::<RawNote, RouteNote>::default(),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+
+    let good = note("hello");
+    tx.send(RawNote {
+        location: good.location.unwrap().encode_to_vec(),
+        message: good.message.clone(),
+    })
+    .await
+    .unwrap();
+    assert_eq!(chat.message().await.unwrap(), Some(good));
+
+    // The proxy fails to decode this one, and has to say so rather than end the chat.
+    tx.send(RawNote {
+        location: vec![0xff],
+        message: "garbled".into(),
+    })
+    .await
+    .unwrap();
+    let err = chat.message().await.unwrap_err();
+    assert_eq!(err.code(), Code::Internal, "{:?}", err);
+}
@@ -0,0 +1,152 @@
+#![allow(dead_code)]
+
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::route_guide_server::{RouteGuide, RouteGuideServer};
+use routeguide_tonic::route_guide::{Feature, Point, Rectangle, RouteNote};
+
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::time::Duration;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Endpoint, Server};
+
+/// Kills the server process when the test is done with it, even if the test panics.
+pub struct ServerProcess(pub Child);
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// A grid of features around New York, spanning several one-degree shard cells. Each is
+/// tagged with its row and column.
+pub fn features() -> Vec<Feature> {
+    let mut features = vec![];
+    for lat in 0..8 {
+        for lng in 0..8 {
+            features.push(Feature {
+                name: format!("feature {lat}/{lng}"),
+                location: Some(Point {
+                    latitude: 398_000_000 + lat * 3_000_000,
+                    longitude: -752_000_000 + lng * 3_000_000,
+                }),
+                tags: vec![format!("row-{lat}"), format!("col-{lng}")],
+                ..Feature::default()
+            });
+        }
+    }
+    features
+}
+
+pub fn point(latitude: i32, longitude: i32) -> Point {
+    Point {
+        latitude,
+        longitude,
+    }
+}
+
+pub fn rectangle(lo: (i32, i32), hi: (i32, i32)) -> Rectangle {
+    Rectangle {
+        lo: Some(point(lo.0, lo.1)),
+        hi: Some(point(hi.0, hi.1)),
+    }
+}
+
+/// A rectangle around the whole world.
+pub fn everywhere() -> Rectangle {
+    rectangle((-900_000_000, -1_800_000_000), (900_000_000, 1_800_000_000))
+}
+
+/// A note at the first of `features()`.
+pub fn note(message: &str) -> RouteNote {
+    RouteNote {
+        location: features()[0].location,
+        message: message.into(),
+    }
+}
+
+pub fn write_features(name: &str) -> PathBuf {
+    let json: Vec<_> = features()
+        .into_iter()
+        .map(|f| {
+            let location = f.location.unwrap();
+            serde_json::json!({
+                "name": f.name,
+                "location": {
+                    "latitude": location.latitude,
+                    "longitude": location.longitude,
+                },
+                "tags": f.tags,
+            })
+        })
+        .collect();
+
+    let path = std::env::temp_dir().join(format!("routeguide-{}-{name}.json", std::process::id()));
+    std::fs::write(&path, serde_json::to_vec(&json).unwrap()).unwrap();
+    path
+}
+
+pub fn free_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+pub async fn connect(addr: SocketAddr) -> Channel {
+    let endpoint = Endpoint::from_shared(format!("http://{addr}")).unwrap();
+    for _ in 0..100 {
+        if let Ok(channel) = endpoint.connect().await {
+            return channel;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("server at {addr} did not come up");
+}
+
+/// Runs the server `serve` builds around the incoming connections of a free port, in the
+/// background, and returns its address.
+pub async fn serve_with<F>(serve: impl FnOnce(TcpListenerStream) -> F) -> SocketAddr
+where
+    F: Future<Output = Result<(), tonic::transport::Error>> + Send + 'static,
+{
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(TcpListenerStream::new(listener)));
+    addr
+}
+
+/// Serves `service` in process on a free port, and connects a client to it.
+pub async fn start_server<S: RouteGuide>(service: S) -> RouteGuideClient<Channel> {
+    let addr = serve_with(|incoming| {
+        Server::builder()
+            .add_service(RouteGuideServer::new(service))
+            .serve_with_incoming(incoming)
+    })
+    .await;
+    RouteGuideClient::new(connect(addr).await)
+}
+
+/// Starts `routeguide-server` on a free port, serving `features()`.
+pub async fn spawn_server(name: &str, args: &[&str]) -> (ServerProcess, SocketAddr, Channel) {
+    let addr = free_addr();
+    let process = spawn_server_at(name, addr, args);
+    let channel = connect(addr).await;
+    (process, addr, channel)
+}
+
+/// Starts `routeguide-server` on `addr`, serving `features()`, without waiting for it.
+pub fn spawn_server_at(name: &str, addr: SocketAddr, args: &[&str]) -> ServerProcess {
+    let child = Command::new(env!("CARGO_BIN_EXE_routeguide-server"))
+        .args(["--addr", &addr.to_string()])
+        .arg("--data")
+        .arg(write_features(name))
+        .args(args)
+        .spawn()
+        .unwrap();
+    ServerProcess(child)
+}
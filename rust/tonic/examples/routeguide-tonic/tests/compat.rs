@@ -0,0 +1,113 @@
+//! Clients built against the RouteGuide proto before features had metadata must keep working.
+
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::Feature;
+use routeguide_tonic::service::RouteGuideService;
+
+use prost::Message;
+use tonic::codec::ProstCodec;
+use tonic::transport::Server;
+use tonic::Request;
+
+mod common;
+
+use common::{connect, features, serve_with};
+
+/// The messages as they were, with `Feature` holding only a name and a location.
+mod v1 {
+    #[derive(Clone, Copy, PartialEq, prost::Message)]
+    pub struct Point {
+        #[prost(int32, tag = "1")]
+        pub latitude: i32,
+        #[prost(int32, tag = "2")]
+        pub longitude: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Rectangle {
+        #[prost(message, optional, tag = "1")]
+        pub lo: Option<Point>,
+        #[prost(message, optional, tag = "2")]
+        pub hi: Option<Point>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Feature {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(message, optional, tag = "2")]
+        pub location: Option<Point>,
+    }
+}
+
+#[test]
+fn old_and_new_features_decode_each_other() {
+    let new = features().remove(9);
+    let old = v1::Feature::decode(new.encode_to_vec().as_slice()).unwrap();
+    assert_eq!(old.name, new.name);
+    assert_eq!(
+        old.location.unwrap().latitude,
+        new.location.unwrap().latitude
+    );
+
+    let decoded = Feature::decode(old.encode_to_vec().as_slice()).unwrap();
+    assert_eq!(
+        decoded,
+        Feature {
+            name: new.name,
+            location: new.location,
+            ..Feature::default()
+        }
+    );
+    assert!(decoded.tags.is_empty());
+    assert_eq!(decoded.elevation, None);
+    assert_eq!(decoded.updated_at, 0);
+}
+
+#[tokio::test]
+async fn old_clients_get_and_list_features() {
+    let addr = serve_with(|incoming| {
+        Server::builder()
+            .add_service(RouteGuideServer::new(RouteGuideService::new(features())))
+            .serve_with_incoming(incoming)
+    })
+    .await;
+    let mut client = tonic::client::Grpc::new(connect(addr).await);
+
+    let want = &features()[9];
+    let location = want.location.unwrap();
+    let point = v1::Point {
+        latitude: location.latitude,
+        longitude: location.longitude,
+    };
+    client.ready().await.unwrap();
+    let resp = client
+        .unary(
+            Request::new(point),
+            "/routeguide.RouteGuide/GetFeature".parse().unwrap(),
+            ProstCodec::<v1::Point, v1::Feature>::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.get_ref().name, want.name);
+
+    let rect = v1::Rectangle {
+        lo: Some(point),
+        hi: Some(point),
+    };
+    client.ready().await.unwrap();
+    let mut stream = client
+        .server_streaming(
+            Request::new(rect),
+            "/routeguide.RouteGuide/ListFeatures".parse().unwrap(),
+            ProstCodec::<v1::Rectangle, v1::Feature>::default(),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+    let mut names = vec![];
+    while let Some(f) = stream.message().await.unwrap() {
+        names.push(f.name);
+    }
+    assert_eq!(names, [want.name.as_str()]);
+}
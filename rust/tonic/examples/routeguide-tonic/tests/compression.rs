@@ -0,0 +1,139 @@
+use routeguide_tonic::compression::{CompressLayer, CompressionConfig, Encoding};
+use routeguide_tonic::recording::{self, RecordLayer};
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::Feature;
+use routeguide_tonic::service::RouteGuideService;
+
+use std::net::SocketAddr;
+use tonic::transport::Server;
+use tonic::{Code, Request};
+use tower::Layer;
+
+mod common;
+
+use common::{connect, everywhere, features, point, serve_with};
+
+fn config(encodings: &[Encoding]) -> CompressionConfig {
+    CompressionConfig {
+        encodings: encodings.to_vec(),
+        min_size: 64,
+    }
+}
+
+/// The common features, with every other one renamed to be well above the minimum size.
+fn mixed_size_features() -> Vec<Feature> {
+    features()
+        .into_iter()
+        .enumerate()
+        .map(|(i, f)| Feature {
+            name: if i % 2 == 0 {
+                f.name
+            } else {
+                format!("{} ", f.name).repeat(20)
+            },
+            ..f
+        })
+        .collect()
+}
+
+async fn start_server(config: CompressionConfig) -> SocketAddr {
+    let mut route_guide = RouteGuideServer::new(RouteGuideService::new(mixed_size_features()));
+    for encoding in config.accepted() {
+        route_guide = route_guide.accept_compressed(encoding);
+    }
+
+    serve_with(|incoming| {
+        Server::builder()
+            .layer(CompressLayer::server(config))
+            .add_service(route_guide)
+            .serve_with_incoming(incoming)
+    })
+    .await
+}
+
+#[tokio::test]
+async fn server_compresses_large_messages_with_negotiated_encoding() {
+    let addr = start_server(config(&[Encoding::Zstd, Encoding::Gzip])).await;
+
+    let path = std::env::temp_dir().join(format!("routeguide-{}.compression", std::process::id()));
+    let client_config = config(&[Encoding::Gzip, Encoding::Deflate]);
+    let channel = CompressLayer::client(client_config.clone()).layer(connect(addr).await);
+    let mut client = RouteGuideClient::new(RecordLayer::new(&path).unwrap().layer(channel));
+    for encoding in client_config.accepted() {
+        client = client.accept_compressed(encoding);
+    }
+
+    let mut stream = client
+        .list_features(Request::new(everywhere()))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut names = vec![];
+    while let Some(f) = stream.message().await.unwrap() {
+        names.push(f.name);
+    }
+    names.sort();
+    let mut expected: Vec<_> = mixed_size_features().into_iter().map(|f| f.name).collect();
+    expected.sort();
+    assert_eq!(names, expected);
+
+    let calls = recording::load(&path).unwrap();
+    assert_eq!(calls[0].headers["grpc-encoding"], "gzip");
+    assert_eq!(calls[0].responses.len(), 64);
+    for msg in &calls[0].responses {
+        if !msg.compressed {
+            assert!(msg.frame().unwrap().len() - 5 < 64);
+        }
+    }
+    assert_eq!(
+        calls[0].responses.iter().filter(|x| x.compressed).count(),
+        32
+    );
+}
+
+#[tokio::test]
+async fn identity_when_nothing_in_common() {
+    let addr = start_server(config(&[Encoding::Zstd])).await;
+
+    let mut client = RouteGuideClient::new(connect(addr).await)
+        .accept_compressed(tonic::codec::CompressionEncoding::Gzip);
+    let resp = client
+        .get_feature(Request::new(features()[0].location.unwrap()))
+        .await
+        .unwrap();
+    assert!(!resp.metadata().contains_key("grpc-encoding"));
+}
+
+#[tokio::test]
+async fn identity_when_listed_first() {
+    let addr = start_server(config(&[Encoding::Identity, Encoding::Gzip])).await;
+
+    let mut client = RouteGuideClient::new(connect(addr).await)
+        .accept_compressed(tonic::codec::CompressionEncoding::Gzip);
+    let mut stream = client
+        .list_features(Request::new(everywhere()))
+        .await
+        .unwrap();
+    assert!(!stream.metadata().contains_key("grpc-encoding"));
+    let mut count = 0;
+    while stream.get_mut().message().await.unwrap().is_some() {
+        count += 1;
+    }
+    assert_eq!(count, 64);
+}
+
+#[tokio::test]
+async fn server_rejects_requests_in_unaccepted_encoding() {
+    let addr = start_server(config(&[Encoding::Zstd])).await;
+
+    let client_config = CompressionConfig {
+        encodings: vec![Encoding::Deflate],
+        min_size: 0,
+    };
+    let channel = CompressLayer::client(client_config).layer(connect(addr).await);
+    let mut client = RouteGuideClient::new(channel);
+    let points = tokio_stream::iter([point(1, 1)]);
+    let err = client.record_route(Request::new(points)).await.unwrap_err();
+    assert_eq!(err.code(), Code::Unimplemented);
+}
@@ -0,0 +1,55 @@
+use routeguide_tonic::geo::DistanceModel;
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::Point;
+use routeguide_tonic::service::RouteGuideService;
+
+use tonic::transport::Channel;
+use tonic::Request;
+
+mod common;
+
+use common::{features, point, start_server};
+
+fn service(model: DistanceModel) -> RouteGuideService {
+    RouteGuideService::new(features()).with_distance(model)
+}
+
+async fn route_distance(client: &mut RouteGuideClient<Channel>, points: Vec<Point>) -> i64 {
+    client
+        .record_route(Request::new(tokio_stream::iter(points)))
+        .await
+        .unwrap()
+        .into_inner()
+        .distance
+}
+
+#[tokio::test]
+async fn long_routes_do_not_overflow() {
+    let mut client = start_server(service(DistanceModel::Haversine)).await;
+
+    // 200 trips halfway round the equator come to 4 million km, past `i32::MAX` meters.
+    let points = (0..=200)
+        .map(|i| point(0, if i % 2 == 0 { 0 } else { 1_800_000_000 }))
+        .collect();
+    let distance = route_distance(&mut client, points).await;
+    assert!(distance > i32::MAX as i64);
+    assert_eq!(
+        distance,
+        (200f64 * 6_371_000f64 * std::f64::consts::PI).round() as i64
+    );
+}
+
+#[tokio::test]
+async fn karney_measures_on_the_ellipsoid() {
+    // Flinders Peak to Buninyong, 54972.271 m on the ellipsoid.
+    let points = vec![
+        point(-379_510_334, 1_444_248_679),
+        point(-376_528_211, 1_439_264_955),
+    ];
+
+    let mut client = start_server(service(DistanceModel::Karney)).await;
+    assert_eq!(route_distance(&mut client, points.clone()).await, 54_972);
+
+    let mut client = start_server(service(DistanceModel::Haversine)).await;
+    assert_ne!(route_distance(&mut client, points).await, 54_972);
+}
@@ -0,0 +1,192 @@
+use routeguide_tonic::fault::{Fault, FaultConfig, FaultHandle, FaultLayer};
+use routeguide_tonic::retry::{Retry, RetryConfig, RetryLayer};
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::{Feature, FILE_DESCRIPTOR_SET};
+use routeguide_tonic::service::RouteGuideService;
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tonic::transport::{Channel, Server};
+use tonic::{Code, Request, Status};
+use tower::Layer;
+
+mod common;
+
+use common::{connect, everywhere, features, serve_with};
+
+type Client = RouteGuideClient<Retry<Channel>>;
+
+/// Retries quickly, so tests that exhaust their retries stay short.
+fn retry(timeout: u64, retries: u32) -> RetryConfig {
+    RetryConfig {
+        timeout,
+        retries,
+        retry_backoff: 5,
+    }
+}
+
+/// Serves the common features through `faults`, to a client retrying as `retry` says.
+async fn start_server(faults: Vec<Fault>, retry: RetryConfig) -> (FaultHandle, Client) {
+    let layer = FaultLayer::new(FaultConfig {
+        seed: Some(1),
+        faults,
+    });
+    let handle = layer.handle();
+
+    let addr = serve_with(|incoming| {
+        Server::builder()
+            .layer(layer)
+            .add_service(RouteGuideServer::new(RouteGuideService::new(features())))
+            .serve_with_incoming(incoming)
+    })
+    .await;
+
+    let retry = RetryLayer::new(retry, FILE_DESCRIPTOR_SET).unwrap();
+    (
+        handle,
+        RouteGuideClient::new(retry.layer(connect(addr).await)),
+    )
+}
+
+/// Lists every feature, returning those received before the stream ended, and how it ended.
+async fn list_all(client: &mut Client) -> (Vec<Feature>, Result<(), Status>) {
+    let mut stream = match client.list_features(everywhere()).await {
+        Ok(resp) => resp.into_inner(),
+        Err(status) => return (vec![], Err(status)),
+    };
+    let mut received = vec![];
+    loop {
+        match stream.message().await {
+            Ok(Some(feature)) => received.push(feature),
+            Ok(None) => return (received, Ok(())),
+            Err(status) => return (received, Err(status)),
+        }
+    }
+}
+
+#[tokio::test]
+async fn latency_trips_client_deadlines() {
+    let fault = Fault {
+        latency_ms: 300,
+        ..Fault::new("*")
+    };
+    let (_faults, mut client) = start_server(vec![fault], retry(50, 3)).await;
+    let point = features()[0].location.unwrap();
+
+    let started = Instant::now();
+    let err = client.get_feature(point).await.unwrap_err();
+    assert_eq!(err.code(), Code::DeadlineExceeded, "{:?}", err);
+    assert!(started.elapsed() < Duration::from_millis(300));
+
+    let (received, end) = list_all(&mut client).await;
+    assert!(received.is_empty());
+    assert_eq!(end.unwrap_err().code(), Code::DeadlineExceeded);
+
+    // A deadline set on the call replaces the configured one.
+    let started = Instant::now();
+    let mut request = Request::new(point);
+    request.set_timeout(Duration::from_secs(5));
+    let feature = client.get_feature(request).await.unwrap().into_inner();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(feature.location, Some(point));
+}
+
+#[tokio::test]
+async fn retries_ride_out_injected_errors() {
+    let fault = Fault {
+        error_rate: 0.5,
+        ..Fault::new("GetFeature")
+    };
+
+    let (_faults, mut client) = start_server(vec![fault.clone()], retry(5000, 10)).await;
+    for expected in features().iter().take(20) {
+        let feature = client
+            .get_feature(expected.location.unwrap())
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(&feature, expected);
+    }
+
+    // The same faults get through to a client that doesn't retry.
+    let (_faults, mut client) = start_server(vec![fault], retry(5000, 0)).await;
+    let mut failures = 0;
+    for expected in features().iter().take(20) {
+        if let Err(status) = client.get_feature(expected.location.unwrap()).await {
+            assert_eq!(status.code(), Code::Unavailable);
+            assert_eq!(status.message(), "injected fault");
+            failures += 1;
+        }
+    }
+    assert!(failures > 0);
+}
+
+#[tokio::test]
+async fn retries_give_up_at_the_deadline() {
+    let fault = Fault {
+        error_rate: 1.0,
+        ..Fault::new("GetFeature")
+    };
+    let (_faults, mut client) = start_server(vec![fault], retry(200, 1000)).await;
+
+    let started = Instant::now();
+    let err = client
+        .get_feature(features()[0].location.unwrap())
+        .await
+        .unwrap_err();
+    // Backing off 5, 10, 20, 40 and 80ms leaves no time for the next retry.
+    assert_eq!(err.code(), Code::Unavailable, "{:?}", err);
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(155) && elapsed < Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn dropped_messages_shorten_streams_without_errors() {
+    let fault = Fault {
+        drop_rate: 0.5,
+        ..Fault::new("ListFeatures")
+    };
+    let (_faults, mut client) = start_server(vec![fault], retry(5000, 3)).await;
+
+    let (received, end) = list_all(&mut client).await;
+    end.unwrap();
+    assert!(!received.is_empty() && received.len() < features().len());
+    let names: HashSet<_> = received.iter().map(|f| &f.name).collect();
+    assert_eq!(names.len(), received.len(), "feature listed twice");
+}
+
+#[tokio::test]
+async fn aborted_streams_keep_partial_results_until_faults_are_cleared() {
+    let fault = Fault {
+        abort_after: Some(5),
+        ..Fault::new("ListFeatures")
+    };
+    let (faults, mut client) = start_server(vec![fault], retry(5000, 3)).await;
+
+    // The reset can overtake the last messages, as a real one would.
+    let (received, end) = list_all(&mut client).await;
+    assert!(received.len() <= 5, "{} features", received.len());
+    let status = end.unwrap_err();
+    assert_eq!(status.code(), Code::Internal, "{:?}", status);
+
+    faults.set(FaultConfig::default());
+    let (received, end) = list_all(&mut client).await;
+    end.unwrap();
+    assert_eq!(received.len(), features().len());
+}
+
+#[test]
+fn config_reads_status_codes_by_name() {
+    let config: FaultConfig = serde_json::from_str(
+        r#"{ "faults": [{ "method": "*", "error_rate": 1, "error_code": "deadline_exceeded" }] }"#,
+    )
+    .unwrap();
+    assert_eq!(config.faults[0].error_code, Code::DeadlineExceeded);
+
+    let err = serde_json::from_str::<FaultConfig>(
+        r#"{ "faults": [{ "method": "*", "error_code": "sometimes" }] }"#,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("unknown status code"), "{}", err);
+}
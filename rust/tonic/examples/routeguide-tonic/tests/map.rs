@@ -0,0 +1,88 @@
+use routeguide_tonic::map;
+use routeguide_tonic::recording::{Call, Message};
+use routeguide_tonic::route_guide::{Point, RouteSummary};
+use routeguide_tonic::service::RouteGuideService;
+
+use http_body_util::BodyExt;
+use std::process::Command;
+use tower::ServiceExt;
+
+mod common;
+
+use common::{features, write_features};
+
+async fn get(service: &RouteGuideService, uri: &str) -> (http::StatusCode, String) {
+    let router = map::router(service.features(), service.routes());
+    let req = http::Request::get(uri)
+        .body(axum::body::Body::empty())
+        .unwrap();
+    let resp = router.oneshot(req).await.unwrap();
+    let status = resp.status();
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+fn route() -> Vec<Point> {
+    features()
+        .iter()
+        .step_by(9)
+        .map(|x| x.location.unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn serves_features_and_recorded_routes() {
+    let service = RouteGuideService::new(features());
+    let id = service.routes().insert(RouteSummary::default(), route()).id;
+
+    let (status, svg) = get(&service, &format!("/map.svg?routes={id}")).await;
+    assert_eq!(status, 200);
+    assert_eq!(svg.matches("<circle").count(), 64);
+    assert_eq!(svg.matches("<text").count(), 64);
+    assert_eq!(svg.matches("<polyline").count(), 1);
+
+    // Half the grid in each direction.
+    let (status, svg) = get(
+        &service,
+        "/map.svg?bbox=39.8,-75.2,40.7,-74.3&labels=false&projection=equirectangular",
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(svg.matches("<circle").count(), 16);
+    assert!(!svg.contains("<text"));
+
+    for (uri, status) in [
+        ("/map.svg?routes=99", 404),
+        ("/map.svg?routes=one", 400),
+        ("/map.svg?bbox=40,-75", 400),
+        ("/map.svg?size=0", 400),
+        ("/map.svg?projection=globe", 400),
+    ] {
+        assert_eq!(get(&service, uri).await.0, status, "{}", uri);
+    }
+}
+
+#[test]
+fn draws_a_data_file_and_session() {
+    let dir = std::env::temp_dir();
+    let session = dir.join(format!("routeguide-{}-map.session", std::process::id()));
+    let call = Call {
+        path: "/routeguide.RouteGuide/RecordRoute".into(),
+        requests: route().iter().map(|p| Message::encode(0, 0, p)).collect(),
+        ..Call::default()
+    };
+    std::fs::write(&session, serde_json::to_string(&call).unwrap() + "\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_routeguide-map"))
+        .arg("--data")
+        .arg(write_features("map"))
+        .arg("--session")
+        .arg(&session)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let svg = String::from_utf8(output.stdout).unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(svg.matches("<circle").count(), 64);
+    assert!(svg.contains("route 1 (8 points)"), "{}", svg);
+}
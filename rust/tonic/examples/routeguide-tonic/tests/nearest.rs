@@ -0,0 +1,91 @@
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::{NearestFeature, NearestFeatureRequest, Point};
+use routeguide_tonic::service::RouteGuideService;
+
+use tonic::transport::Channel;
+use tonic::{Code, Request, Status};
+
+mod common;
+
+use common::{features, start_server};
+
+async fn nearest(
+    client: &mut RouteGuideClient<Channel>,
+    location: Option<Point>,
+    tolerance_meters: f64,
+) -> Result<NearestFeature, Status> {
+    let req = NearestFeatureRequest {
+        location,
+        tolerance_meters,
+    };
+    Ok(client
+        .get_nearest_feature(Request::new(req))
+        .await?
+        .into_inner())
+}
+
+#[tokio::test]
+async fn snaps_to_features_within_tolerance() {
+    let mut client = start_server(RouteGuideService::new(features())).await;
+    let feature = &features()[9];
+    let location = feature.location.unwrap();
+
+    let exact = nearest(&mut client, Some(location), 0f64).await.unwrap();
+    assert_eq!(exact.feature.as_ref(), Some(feature));
+    assert_eq!(exact.distance, 0f64);
+
+    // One E7 unit is about a centimeter.
+    let reading = Point {
+        latitude: location.latitude + 1,
+        ..location
+    };
+    let snapped = nearest(&mut client, Some(reading), 1f64).await.unwrap();
+    assert_eq!(snapped.feature.as_ref(), Some(feature));
+    assert!(
+        snapped.distance > 0f64 && snapped.distance < 0.02,
+        "{}",
+        snapped.distance
+    );
+
+    let err = nearest(&mut client, Some(reading), 0f64).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn picks_the_nearest_of_several_candidates() {
+    let mut client = start_server(RouteGuideService::new(features())).await;
+
+    // Features are 0.3 degrees apart; this is a third of the way from 4/4 to 5/4.
+    let reading = Point {
+        latitude: 410_000_000 + 1_000_000,
+        longitude: -740_000_000,
+    };
+    let found = nearest(&mut client, Some(reading), 50_000f64)
+        .await
+        .unwrap();
+    assert_eq!(found.feature.unwrap().name, "feature 4/4");
+    assert!(
+        (found.distance - 11_119.5).abs() < 1f64,
+        "{}",
+        found.distance
+    );
+
+    let err = nearest(&mut client, Some(reading), 10_000f64)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn rejects_bad_requests() {
+    let mut client = start_server(RouteGuideService::new(features())).await;
+
+    let err = nearest(&mut client, None, 10f64).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    let location = features()[0].location;
+    for tolerance in [-1f64, f64::NAN] {
+        let err = nearest(&mut client, location, tolerance).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+}
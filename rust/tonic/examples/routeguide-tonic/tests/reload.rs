@@ -0,0 +1,188 @@
+use routeguide_tonic::features::Reloader;
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::{Feature, Point};
+use routeguide_tonic::service::RouteGuideService;
+
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+
+mod common;
+
+use common::{everywhere, features, spawn_server, write_features};
+
+fn data_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "routeguide-{}-reload-{name}.json",
+        std::process::id()
+    ))
+}
+
+fn write_data(path: &Path, features: &[Feature]) {
+    let json: Vec<_> = features
+        .iter()
+        .map(|f| {
+            let location = f.location.unwrap();
+            json!({
+                "name": f.name,
+                "location": {
+                    "latitude": location.latitude,
+                    "longitude": location.longitude,
+                },
+            })
+        })
+        .collect();
+    std::fs::write(path, serde_json::to_vec(&json).unwrap()).unwrap();
+}
+
+fn renamed(features: &[Feature], prefix: &str) -> Vec<Feature> {
+    features
+        .iter()
+        .map(|f| Feature {
+            name: format!("{prefix} {}", f.name),
+            ..f.clone()
+        })
+        .collect()
+}
+
+/// Serves the features in `path`, returning a reloader for them.
+async fn start_server(path: &Path) -> (Reloader, RouteGuideClient<Channel>) {
+    let service = RouteGuideService::new(vec![]);
+    let reloader = Reloader::new(path.to_path_buf(), service.features());
+    reloader.reload().unwrap();
+
+    (reloader, common::start_server(service).await)
+}
+
+async fn name_at(client: &mut RouteGuideClient<Channel>, location: Point) -> Option<String> {
+    match client.get_feature(Request::new(location)).await {
+        Ok(resp) => Some(resp.into_inner().name),
+        Err(status) if status.code() == Code::NotFound => None,
+        Err(status) => panic!("{status}"),
+    }
+}
+
+#[tokio::test]
+async fn reload_swaps_features_and_rejects_invalid_files() {
+    let path = data_path("swap");
+    let features = features();
+    write_data(&path, &features[..10]);
+    let (reloader, mut client) = start_server(&path).await;
+    let location = features[20].location.unwrap();
+    assert_eq!(name_at(&mut client, location).await, None);
+
+    write_data(&path, &features);
+    assert_eq!(reloader.reload().unwrap(), features.len());
+    assert_eq!(
+        name_at(&mut client, location).await,
+        Some(features[20].name.clone())
+    );
+
+    // Each bad file is rejected, and the features loaded before stay live.
+    let mut repeated = features.clone();
+    repeated.push(features[3].clone());
+    let mut off_the_map = features.clone();
+    off_the_map[5].location = Some(Point {
+        latitude: 950_000_000,
+        longitude: 0,
+    });
+    for (contents, error) in [
+        (None, "EOF"),
+        (Some(repeated), "repeats the location"),
+        (Some(off_the_map), "out of range"),
+    ] {
+        match contents {
+            Some(contents) => write_data(&path, &contents),
+            None => std::fs::write(&path, "[{\"name\": ").unwrap(),
+        }
+        let err = reloader.reload().unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+        assert_eq!(
+            name_at(&mut client, location).await,
+            Some(features[20].name.clone())
+        );
+    }
+}
+
+#[tokio::test]
+async fn open_streams_finish_on_the_old_features() {
+    let path = data_path("snapshot");
+    let features = features();
+    write_data(&path, &features);
+    let (reloader, mut client) = start_server(&path).await;
+
+    let mut stream = client
+        .list_features(Request::new(everywhere()))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut names = vec![stream.message().await.unwrap().unwrap().name];
+
+    write_data(&path, &renamed(&features[..5], "new"));
+    reloader.reload().unwrap();
+
+    while let Some(f) = stream.message().await.unwrap() {
+        names.push(f.name);
+    }
+    assert_eq!(names.len(), features.len());
+    assert!(names.iter().all(|x| !x.starts_with("new")));
+
+    let location = features[0].location.unwrap();
+    let name = name_at(&mut client, location).await.unwrap();
+    assert!(name.starts_with("new"), "{}", name);
+}
+
+#[tokio::test]
+async fn watching_picks_up_changes() {
+    let path = data_path("watch");
+    let features = features();
+    write_data(&path, &features);
+    let (reloader, mut client) = start_server(&path).await;
+    let watch = tokio::spawn(reloader.watch(Duration::from_millis(50)));
+    let location = features[0].location.unwrap();
+
+    write_data(&path, &renamed(&features, "changed"));
+    for _ in 0..100 {
+        if name_at(&mut client, location).await != Some(features[0].name.clone()) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(
+        name_at(&mut client, location).await,
+        Some(format!("changed {}", features[0].name))
+    );
+    watch.abort();
+}
+
+#[tokio::test]
+async fn sighup_reloads_the_server() {
+    // Only SIGHUP should notice the change, not the watch.
+    let args = ["--watch-interval", "3600"];
+    let (server, _, channel) = spawn_server("reload-sighup", &args).await;
+    let mut client = RouteGuideClient::new(channel);
+    let features = features();
+    let location = features[0].location.unwrap();
+
+    let path = write_features("reload-sighup");
+    write_data(&path, &renamed(&features, "changed"));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        name_at(&mut client, location).await,
+        Some(features[0].name.clone())
+    );
+
+    assert_eq!(unsafe { libc::kill(server.0.id() as i32, libc::SIGHUP) }, 0);
+    for _ in 0..100 {
+        if name_at(&mut client, location).await != Some(features[0].name.clone()) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(
+        name_at(&mut client, location).await,
+        Some(format!("changed {}", features[0].name))
+    );
+}
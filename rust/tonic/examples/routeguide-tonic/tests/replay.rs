@@ -0,0 +1,150 @@
+use routeguide_tonic::recording::{self, Call, Message, RecordLayer};
+use routeguide_tonic::replay::Replay;
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::route_guide_server::RouteGuideServer;
+use routeguide_tonic::route_guide::{Feature, RouteNote};
+use routeguide_tonic::service::RouteGuideService;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::time::Instant;
+use tonic::body::Body as TonicBody;
+use tonic::client::GrpcService;
+use tonic::codegen::{Body, Bytes, StdError};
+use tonic::transport::Server;
+use tonic::{Code, Request};
+use tower::Layer;
+
+mod common;
+
+use common::{connect, note, point, rectangle, serve_with};
+
+/// What a client sees from one call of each shape.
+#[derive(Debug, PartialEq)]
+struct Session {
+    found: Feature,
+    missing: Code,
+    listed: Vec<Feature>,
+    chat: Vec<RouteNote>,
+}
+
+async fn run_session<T>(mut client: RouteGuideClient<T>) -> Session
+where
+    T: GrpcService<TonicBody>,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    let found = client
+        .get_feature(Request::new(point(10, 10)))
+        .await
+        .unwrap()
+        .into_inner();
+    let missing = client
+        .get_feature(Request::new(point(0, 0)))
+        .await
+        .unwrap_err()
+        .code();
+
+    let rect = rectangle((0, 0), (100, 100));
+    let mut stream = client
+        .list_features(Request::new(rect))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut listed = vec![];
+    while let Some(f) = stream.message().await.unwrap() {
+        listed.push(f);
+    }
+
+    let notes = tokio_stream::iter([note("a"), note("b"), note("c")]);
+    let mut stream = client
+        .route_chat(Request::new(notes))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut chat = vec![];
+    while let Some(note) = stream.message().await.unwrap() {
+        chat.push(note);
+    }
+
+    Session {
+        found,
+        missing,
+        listed,
+        chat,
+    }
+}
+
+#[tokio::test]
+async fn replay_answers_like_the_recorded_server() {
+    let features = vec![Feature {
+        name: "ten".into(),
+        location: Some(point(10, 10)),
+        ..Feature::default()
+    }];
+    let addr = serve_with(|incoming| {
+        Server::builder()
+            .add_service(RouteGuideServer::new(RouteGuideService::new(features)))
+            .serve_with_incoming(incoming)
+    })
+    .await;
+
+    let path = std::env::temp_dir().join(format!("routeguide-{}.session", std::process::id()));
+    let channel = connect(addr).await;
+    let layer = RecordLayer::new(&path).unwrap();
+    let recorded = run_session(RouteGuideClient::new(layer.layer(channel))).await;
+
+    let calls = recording::load(&path).unwrap();
+    assert_eq!(calls.len(), 4);
+    assert_eq!(calls[3].path, "/routeguide.RouteGuide/RouteChat");
+    assert_eq!(calls[3].requests.len(), 3);
+    assert_eq!(calls[3].responses.len(), 6);
+
+    let replayed = run_session(RouteGuideClient::new(Replay::new(calls))).await;
+    assert_eq!(replayed, recorded);
+    assert_eq!(recorded.chat.len(), 6);
+}
+
+#[tokio::test(start_paused = true)]
+async fn replay_keeps_recorded_timing() {
+    let call = Call {
+        path: "/routeguide.RouteGuide/RouteChat".into(),
+        headers: BTreeMap::from([("content-type".into(), "application/grpc".into())]),
+        responses: vec![
+            Message::encode(10, 1, &note("first")),
+            Message::encode(1_500, 2, &note("second")),
+        ],
+        trailers: BTreeMap::from([("grpc-status".into(), "0".into())]),
+        ..Call::default()
+    };
+    let mut client = RouteGuideClient::new(Replay::new(vec![call]));
+
+    let start = Instant::now();
+    let outbound = async_stream::stream! {
+        yield note("a");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        yield note("b");
+    };
+    let mut inbound = client
+        .route_chat(Request::new(outbound))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let first = inbound.message().await.unwrap().unwrap();
+    assert_eq!(first.message, "first");
+    assert_eq!(start.elapsed(), Duration::from_millis(10));
+
+    let second = inbound.message().await.unwrap().unwrap();
+    assert_eq!(second.message, "second");
+    assert_eq!(start.elapsed(), Duration::from_millis(1_500));
+
+    assert!(inbound.message().await.unwrap().is_none());
+
+    let err = client
+        .route_chat(Request::new(tokio_stream::iter([note("c")])))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+}
@@ -0,0 +1,135 @@
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::{Point, Route, RouteQuery, RouteRequest, SimplifyRequest};
+use routeguide_tonic::service::RouteGuideService;
+
+use std::num::NonZeroUsize;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Status};
+
+mod common;
+
+use common::{features, point, rectangle, start_server};
+
+/// Records a route through `points`, returning its ID.
+async fn record(client: &mut RouteGuideClient<Channel>, points: Vec<Point>) -> u64 {
+    client
+        .record_route(Request::new(tokio_stream::iter(points)))
+        .await
+        .unwrap()
+        .into_inner()
+        .route_id
+}
+
+async fn list(
+    client: &mut RouteGuideClient<Channel>,
+    query: RouteQuery,
+) -> Result<Vec<Route>, Status> {
+    let mut stream = client.list_routes(query).await?.into_inner();
+    let mut routes = vec![];
+    while let Some(route) = stream.message().await? {
+        routes.push(route);
+    }
+    Ok(routes)
+}
+
+fn ids(routes: &[Route]) -> Vec<u64> {
+    routes.iter().map(|x| x.id).collect()
+}
+
+#[tokio::test]
+async fn get_route_returns_what_was_recorded() {
+    let mut client = start_server(RouteGuideService::new(features())).await;
+
+    let err = client.get_route(RouteRequest { id: 1 }).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    let points = vec![point(0, 0), point(1_000, 0), point(2_000, 0)];
+    let id = record(&mut client, points.clone()).await;
+    let route = client
+        .get_route(RouteRequest { id })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(route.id, id);
+    assert_eq!(route.points, points);
+    assert_eq!(route.summary.unwrap().point_count, 3);
+
+    let simplified = client
+        .get_simplified_route(SimplifyRequest {
+            id,
+            tolerance: 10.0,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(simplified.points, [point(0, 0), point(2_000, 0)]);
+
+    let err = client
+        .get_simplified_route(SimplifyRequest {
+            id: id + 1,
+            tolerance: 10.0,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    let err = client
+        .get_simplified_route(SimplifyRequest {
+            id,
+            tolerance: -1.0,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn list_routes_filters_by_area_and_time() {
+    let mut client = start_server(RouteGuideService::new(features())).await;
+    assert!(list(&mut client, RouteQuery::default())
+        .await
+        .unwrap()
+        .is_empty());
+
+    let south = record(&mut client, vec![point(0, 0), point(1_000, 0)]).await;
+    let north = record(&mut client, vec![point(500_000_000, 0)]).await;
+
+    let all = list(&mut client, RouteQuery::default()).await.unwrap();
+    assert_eq!(ids(&all), [south, north]);
+    assert!(all.iter().all(|x| x.points.is_empty()));
+
+    let query = RouteQuery {
+        area: Some(rectangle((400_000_000, -1), (600_000_000, 1))),
+        ..RouteQuery::default()
+    };
+    assert_eq!(ids(&list(&mut client, query).await.unwrap()), [north]);
+
+    let query = RouteQuery {
+        area: Some(rectangle((-1, -1), (1, 1))),
+        recorded_after: all[1].recorded_at + 1,
+        ..RouteQuery::default()
+    };
+    assert!(list(&mut client, query).await.unwrap().is_empty());
+
+    let mut area = rectangle((-1, -1), (1, 1));
+    area.hi = None;
+    let query = RouteQuery {
+        area: Some(area),
+        ..RouteQuery::default()
+    };
+    let err = list(&mut client, query).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn only_the_newest_routes_are_kept() {
+    let service = RouteGuideService::new(features()).with_max_routes(NonZeroUsize::new(2).unwrap());
+    let mut client = start_server(service).await;
+
+    for _ in 0..3 {
+        record(&mut client, vec![point(0, 0)]).await;
+    }
+    let routes = list(&mut client, RouteQuery::default()).await.unwrap();
+    assert_eq!(ids(&routes), [2, 3]);
+    let err = client.get_route(RouteRequest { id: 1 }).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+}
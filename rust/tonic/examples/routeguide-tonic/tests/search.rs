@@ -0,0 +1,135 @@
+use routeguide_tonic::data;
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+use routeguide_tonic::route_guide::{Feature, FeatureQuery, Point, Rectangle};
+use routeguide_tonic::service::RouteGuideService;
+
+use std::collections::BTreeSet;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Status};
+
+mod common;
+
+use common::{start_server, write_features};
+
+async fn search(
+    client: &mut RouteGuideClient<Channel>,
+    area: Option<Rectangle>,
+    tags: &[&str],
+) -> Result<BTreeSet<String>, Status> {
+    let query = FeatureQuery {
+        area,
+        tags: tags.iter().map(|x| x.to_string()).collect(),
+    };
+    let mut stream = client
+        .search_features(Request::new(query))
+        .await?
+        .into_inner();
+    let mut names = BTreeSet::new();
+    while let Some(f) = stream.message().await? {
+        names.insert(f.name);
+    }
+    Ok(names)
+}
+
+fn names(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|x| x.to_string()).collect()
+}
+
+#[tokio::test]
+async fn searches_by_tags_and_area() {
+    // Serves the features `write_features` writes, as loaded back from the file.
+    let features = data::load_from(&write_features("search")).unwrap();
+    let mut client = start_server(RouteGuideService::new(features)).await;
+
+    assert_eq!(search(&mut client, None, &[]).await.unwrap().len(), 64);
+    assert_eq!(
+        search(&mut client, None, &["row-2"]).await.unwrap().len(),
+        8
+    );
+    assert_eq!(
+        search(&mut client, None, &["row-2", "col-5"])
+            .await
+            .unwrap(),
+        names(&["feature 2/5"])
+    );
+    assert!(search(&mut client, None, &["row-2", "row-3"])
+        .await
+        .unwrap()
+        .is_empty());
+
+    // The first three columns of row 1.
+    let area = Rectangle {
+        lo: Some(Point {
+            latitude: 398_000_000,
+            longitude: -752_000_000,
+        }),
+        hi: Some(Point {
+            latitude: 401_000_000,
+            longitude: -746_000_000,
+        }),
+    };
+    assert_eq!(
+        search(&mut client, Some(area), &["row-1"]).await.unwrap(),
+        names(&["feature 1/0", "feature 1/1", "feature 1/2"])
+    );
+
+    let half = Rectangle { lo: None, ..area };
+    let err = search(&mut client, Some(half), &[]).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[test]
+fn loads_metadata_and_defaults_missing_fields() {
+    let path =
+        std::env::temp_dir().join(format!("routeguide-{}-metadata.json", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"[
+            {
+                "name": "Summit",
+                "location": { "latitude": 1, "longitude": 2 },
+                "tags": ["peak", "view"],
+                "description": "The highest point around.",
+                "elevation": 512.5,
+                "updated_at": 1700000000
+            },
+            { "name": "Old", "location": { "latitude": 3, "longitude": 4 } }
+        ]"#,
+    )
+    .unwrap();
+
+    let features = data::load_from(&path).unwrap();
+    assert_eq!(
+        features[0],
+        Feature {
+            name: "Summit".into(),
+            location: Some(Point {
+                latitude: 1,
+                longitude: 2,
+            }),
+            tags: vec!["peak".into(), "view".into()],
+            description: "The highest point around.".into(),
+            elevation: Some(512.5),
+            updated_at: 1_700_000_000,
+        }
+    );
+    assert_eq!(
+        features[1],
+        Feature {
+            name: "Old".into(),
+            location: Some(Point {
+                latitude: 3,
+                longitude: 4,
+            }),
+            ..Feature::default()
+        }
+    );
+
+    std::fs::write(
+        &path,
+        r#"[{ "name": "x", "location": { "latitude": 1, "longitude": 2 }, "tags": [""] }]"#,
+    )
+    .unwrap();
+    let err = data::load_from(&path).unwrap_err();
+    assert!(err.to_string().contains("empty tag"), "{}", err);
+}
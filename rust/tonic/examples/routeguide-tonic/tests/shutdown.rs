@@ -0,0 +1,81 @@
+use routeguide_tonic::route_guide::route_guide_client::RouteGuideClient;
+
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Code, Request};
+
+mod common;
+
+use common::{connect, free_addr, note, spawn_server, ServerProcess};
+
+fn terminate(pid: u32) {
+    assert_eq!(unsafe { libc::kill(pid as i32, libc::SIGTERM) }, 0);
+}
+
+#[tokio::test]
+async fn sigterm_ends_open_streams_with_a_status_and_exits() {
+    let (mut server, addr, channel) = spawn_server("sigterm", &["--drain-timeout", "5"]).await;
+    let mut client = RouteGuideClient::new(channel);
+
+    let (tx, rx) = mpsc::channel(4);
+    let mut chat = client
+        .route_chat(Request::new(ReceiverStream::new(rx)))
+        .await
+        .unwrap()
+        .into_inner();
+    tx.send(note("before")).await.unwrap();
+    assert_eq!(chat.message().await.unwrap().unwrap().message, "before");
+
+    let started = Instant::now();
+    terminate(server.0.id());
+
+    let err = chat.message().await.unwrap_err();
+    assert_eq!(err.code(), Code::Unavailable);
+    assert_eq!(err.message(), "server is shutting down");
+
+    let status = tokio::task::spawn_blocking(move || server.0.wait().unwrap())
+        .await
+        .unwrap();
+    assert!(status.success());
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn sigterm_drains_the_proxy_too() {
+    let (_shard, shard_addr, _) = spawn_server("sigterm-proxy", &[]).await;
+    let addr = free_addr();
+    let proxy = Command::new(env!("CARGO_BIN_EXE_routeguide-proxy"))
+        .args(["--addr", &addr.to_string()])
+        .args(["--shard", &format!("http://{shard_addr}")])
+        .args(["--drain-timeout", "5"])
+        .spawn()
+        .unwrap();
+    let mut proxy = ServerProcess(proxy);
+    let mut client = RouteGuideClient::new(connect(addr).await);
+
+    let (tx, rx) = mpsc::channel(4);
+    let mut chat = client
+        .route_chat(Request::new(ReceiverStream::new(rx)))
+        .await
+        .unwrap()
+        .into_inner();
+    tx.send(note("before")).await.unwrap();
+    assert_eq!(chat.message().await.unwrap().unwrap().message, "before");
+
+    let started = Instant::now();
+    terminate(proxy.0.id());
+
+    let err = chat.message().await.unwrap_err();
+    assert_eq!(err.code(), Code::Unavailable);
+    assert_eq!(err.message(), "server is shutting down");
+
+    let status = tokio::task::spawn_blocking(move || proxy.0.wait().unwrap())
+        .await
+        .unwrap();
+    assert!(status.success());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
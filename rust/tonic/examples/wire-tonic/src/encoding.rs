@@ -0,0 +1,52 @@
+use flate2::write::{GzEncoder, ZlibEncoder};
+use std::io::Write;
+use tonic::codec::CompressionEncoding;
+
+/// A message encoding, as named in `grpc-encoding` and `grpc-accept-encoding` headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    Deflate,
+    Zstd,
+}
+
+impl Encoding {
+    /// The name used in `grpc-encoding` headers.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Identity => "identity",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    /// The tonic encoding, `None` for identity.
+    pub fn tonic(self) -> Option<CompressionEncoding> {
+        match self {
+            Encoding::Identity => None,
+            Encoding::Gzip => Some(CompressionEncoding::Gzip),
+            Encoding::Deflate => Some(CompressionEncoding::Deflate),
+            Encoding::Zstd => Some(CompressionEncoding::Zstd),
+        }
+    }
+
+    /// Compresses a message payload.
+    pub fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Identity => Ok(data.to_vec()),
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Zstd => zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! gRPC messages go over HTTP/2 as frames of a compressed flag, a 4-byte big-endian length
+//! and the payload. Data frames of the body don't line up with them: a message can be split
+//! across data frames, and a data frame can hold several messages.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+/// The length of the prefix before each message.
+pub const HEADER_LEN: usize = 5;
+
+/// Splits a byte stream into gRPC messages.
+#[derive(Debug, Default)]
+pub struct Parser {
+    buf: BytesMut,
+}
+
+impl Parser {
+    /// Returns the messages completed by `data` as `(compressed, payload)` pairs.
+    pub fn push(&mut self, data: &[u8]) -> Vec<(bool, Bytes)> {
+        self.buf.extend_from_slice(data);
+
+        let mut messages = vec![];
+        while self.buf.len() >= HEADER_LEN {
+            let len = u32::from_be_bytes(self.buf[1..HEADER_LEN].try_into().unwrap()) as usize;
+            if self.buf.len() < HEADER_LEN + len {
+                break;
+            }
+            let compressed = self.buf[0] == 1;
+            self.buf.advance(HEADER_LEN);
+            messages.push((compressed, self.buf.split_to(len).freeze()));
+        }
+        messages
+    }
+}
+
+/// Appends the frame carrying `payload` to `out`.
+pub fn put(out: &mut BytesMut, compressed: bool, payload: &[u8]) {
+    out.reserve(HEADER_LEN + payload.len());
+    out.put_u8(compressed as u8);
+    out.put_u32(payload.len() as u32);
+    out.put_slice(payload);
+}
+
+/// The frame carrying `payload`.
+pub fn encode(compressed: bool, payload: &[u8]) -> Bytes {
+    let mut out = BytesMut::new();
+    put(&mut out, compressed, payload);
+    out.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser_splits_messages_across_chunks() {
+        let a = encode(false, b"first");
+        let b = encode(true, b"second");
+        let stream = [a.as_ref(), b.as_ref()].concat();
+
+        let mut parser = Parser::default();
+        assert!(parser.push(&stream[..3]).is_empty());
+        let first = parser.push(&stream[3..a.len() + 2]);
+        let second = parser.push(&stream[a.len() + 2..]);
+
+        assert_eq!(first, vec![(false, Bytes::from_static(b"first"))]);
+        assert_eq!(second, vec![(true, Bytes::from_static(b"second"))]);
+    }
+
+    #[test]
+    fn parser_splits_messages_sharing_a_chunk() {
+        let mut out = BytesMut::new();
+        put(&mut out, false, b"");
+        put(&mut out, false, b"x");
+
+        let messages = Parser::default().push(&out);
+        assert_eq!(
+            messages,
+            vec![(false, Bytes::new()), (false, Bytes::from_static(b"x"))]
+        );
+    }
+}
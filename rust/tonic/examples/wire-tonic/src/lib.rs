@@ -0,0 +1,8 @@
+//! Pieces shared by the examples: the gRPC wire format, with length-prefixed message frames,
+//! a body that reports the messages going through it and the message encodings, and graceful
+//! shutdown.
+
+pub mod encoding;
+pub mod frame;
+pub mod shutdown;
+pub mod tap;
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Tells long-running streams that the server is going away, so they can end with a final
+/// status instead of being cut off.
+#[derive(Debug, Clone)]
+pub struct Drain {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Drain {
+    fn default() -> Self {
+        Self {
+            tx: Arc::new(watch::channel(false).0),
+        }
+    }
+}
+
+impl Drain {
+    pub fn start(&self) {
+        self.tx.send_replace(true);
+    }
+
+    pub fn is_started(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Resolves once draining has started.
+    pub async fn started(&self) {
+        let mut rx = self.tx.subscribe();
+        // The sender lives as long as `self`, so this can't fail.
+        let _ = rx.wait_for(|started| *started).await;
+    }
+}
+
+/// The status streams end with when the server drains.
+pub fn status() -> tonic::Status {
+    tonic::Status::unavailable("server is shutting down")
+}
+
+/// Resolves when the process is asked to stop, with SIGINT or SIGTERM.
+pub async fn signal() {
+    let interrupt = tokio::signal::ctrl_c();
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        signal(SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
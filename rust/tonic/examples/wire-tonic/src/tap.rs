@@ -0,0 +1,188 @@
+use crate::frame::Parser;
+
+use bytes::Bytes;
+use http::HeaderMap;
+use http_body::{Body, Frame, SizeHint};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use tonic::body::Body as TonicBody;
+
+/// Which body of a call a message was in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Request,
+    Response,
+}
+
+/// Follows the traffic of one call. The request and response bodies of the call share it.
+pub trait Tap: Send + 'static {
+    /// A whole message went by on `side`.
+    fn on_message(&mut self, side: Side, compressed: bool, payload: Bytes);
+
+    /// The response trailers went by. `finish` is called right after.
+    fn on_trailers(&mut self, trailers: &HeaderMap);
+
+    /// The call is over: the response ended, failed, or was dropped before it ended. This can
+    /// be called more than once.
+    fn finish(&mut self);
+}
+
+/// Finishes a tap when dropped, which catches calls whose response never arrives or is
+/// dropped half read. Create it before awaiting the response, and hand it to its body.
+pub struct Finish<T: Tap>(Arc<Mutex<T>>);
+
+impl<T: Tap> Finish<T> {
+    pub fn new(tap: Arc<Mutex<T>>) -> Self {
+        Self(tap)
+    }
+}
+
+impl<T: Tap> Drop for Finish<T> {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().finish();
+    }
+}
+
+/// Passes a body through unchanged, telling a `Tap` about each message and the trailers.
+pub struct TapBody<T: Tap> {
+    inner: TonicBody,
+    tap: Arc<Mutex<T>>,
+    side: Side,
+    parser: Parser,
+    _finish: Option<Finish<T>>,
+}
+
+impl<T: Tap> TapBody<T> {
+    pub fn request(inner: TonicBody, tap: Arc<Mutex<T>>) -> TonicBody {
+        TonicBody::new(Self {
+            inner,
+            tap,
+            side: Side::Request,
+            parser: Parser::default(),
+            _finish: None,
+        })
+    }
+
+    /// The response body, which finishes the tap when it ends or is dropped.
+    pub fn response(inner: TonicBody, finish: Finish<T>) -> TonicBody {
+        TonicBody::new(Self {
+            inner,
+            tap: finish.0.clone(),
+            side: Side::Response,
+            parser: Parser::default(),
+            _finish: Some(finish),
+        })
+    }
+}
+
+impl<T: Tap> Body for TapBody<T> {
+    type Data = Bytes;
+    type Error = tonic::Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = &mut *self;
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        let mut tap = this.tap.lock().unwrap();
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    for (compressed, payload) in this.parser.push(data) {
+                        tap.on_message(this.side, compressed, payload);
+                    }
+                } else if let Some(trailers) = frame.trailers_ref() {
+                    tap.on_trailers(trailers);
+                    tap.finish();
+                }
+            }
+            _ if this.side == Side::Response => tap.finish(),
+            _ => {}
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame;
+    use std::future::poll_fn;
+
+    #[derive(Default)]
+    struct Seen {
+        messages: Vec<(Side, Bytes)>,
+        finished: usize,
+    }
+
+    impl Tap for Seen {
+        fn on_message(&mut self, side: Side, _compressed: bool, payload: Bytes) {
+            self.messages.push((side, payload));
+        }
+
+        fn on_trailers(&mut self, _trailers: &HeaderMap) {}
+
+        fn finish(&mut self) {
+            self.finished += 1;
+        }
+    }
+
+    fn body(data: &[&[u8]]) -> TonicBody {
+        TonicBody::new(http_body_util::Full::new(Bytes::from(data.concat())))
+    }
+
+    async fn drain(mut body: TonicBody) {
+        while poll_fn(|cx| Pin::new(&mut body).poll_frame(cx))
+            .await
+            .is_some()
+        {}
+    }
+
+    #[tokio::test]
+    async fn reports_messages_and_finishes_once_the_response_ends() {
+        let tap = Arc::new(Mutex::new(Seen::default()));
+        let first = frame::encode(false, b"a");
+        let second = frame::encode(false, b"b");
+
+        drain(TapBody::request(body(&[&first, &second]), tap.clone())).await;
+        assert_eq!(tap.lock().unwrap().finished, 0);
+
+        drain(TapBody::response(
+            body(&[&second]),
+            Finish::new(tap.clone()),
+        ))
+        .await;
+        let seen = tap.lock().unwrap();
+        let messages: Vec<_> = seen
+            .messages
+            .iter()
+            .map(|(side, x)| (*side, &x[..]))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (Side::Request, &b"a"[..]),
+                (Side::Request, b"b"),
+                (Side::Response, b"b")
+            ]
+        );
+        assert!(seen.finished >= 1);
+    }
+
+    #[test]
+    fn finishes_when_the_response_is_dropped() {
+        let tap = Arc::new(Mutex::new(Seen::default()));
+        drop(TapBody::response(body(&[]), Finish::new(tap.clone())));
+        assert_eq!(tap.lock().unwrap().finished, 1);
+    }
+}